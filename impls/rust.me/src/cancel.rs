@@ -0,0 +1,44 @@
+use crate::errors::MalErr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Checked by eval on every iteration so that long-running evaluations can be aborted
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Ask the running evaluation to stop at its next eval step.
+/// Safe to call from a signal handler or another thread.
+pub fn cancel() {
+    CANCELLED.store(true, Ordering::SeqCst);
+}
+
+/// Clear a pending cancellation, typically before starting a new evaluation
+pub fn reset() {
+    CANCELLED.store(false, Ordering::SeqCst);
+}
+
+/// Returns an Interrupted error if a cancellation has been requested
+pub fn check() -> Result<(), MalErr> {
+    if CANCELLED.load(Ordering::Relaxed) {
+        Err(MalErr::Interrupted)
+    } else {
+        Ok(())
+    }
+}
+
+/// Run f with Ctrl-C (SIGINT) cancelling the evaluation instead of ending the process
+#[cfg(unix)]
+pub fn interruptible<T>(f: impl FnOnce() -> T) -> T {
+    extern "C" fn interrupt(_: libc::c_int) {
+        cancel();
+    }
+    let handler = interrupt as extern "C" fn(libc::c_int);
+    // SAFETY: the handler only stores to an atomic, and the previous handler is put back after
+    let previous = unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+    let result = f();
+    unsafe { libc::signal(libc::SIGINT, previous) };
+    result
+}
+
+#[cfg(not(unix))]
+pub fn interruptible<T>(f: impl FnOnce() -> T) -> T {
+    f()
+}
@@ -0,0 +1,83 @@
+//! Dispatch tables of case forms, so a case inside a loop or function body sorts its keys once
+//! rather than on every pass through eval.
+//!
+//! As with macro expansions, entries are keyed by the address of the form's list and only used
+//! while that form is still alive. Keys are literals, so a table never goes stale.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use crate::seq::{Seq, SeqKey, WeakSeq};
+use crate::types::MalType;
+
+/// Each key of a case form, mapped to the index in the form of the branch it selects
+pub type Dispatch = BTreeMap<MalType, usize>;
+
+struct Entry {
+    form: WeakSeq,
+    dispatch: Rc<Dispatch>,
+}
+
+struct Cache {
+    entries: HashMap<SeqKey, Entry>,
+    /// how many entries there can be before the dead ones are dropped
+    limit: usize,
+}
+
+thread_local! {
+    static CACHE: RefCell<Cache> = RefCell::new(Cache {
+        entries: HashMap::new(),
+        limit: 4096,
+    });
+}
+
+/// The dispatch table of the case form `l`, building it the first time the form is seen
+pub fn dispatch(l: &Seq) -> Rc<Dispatch> {
+    let cached = CACHE.with(|c| {
+        c.borrow()
+            .entries
+            .get(&l.key())
+            .filter(|e| e.form.upgrade().is_some_and(|live| Seq::ptr_eq(&live, l)))
+            .map(|e| Rc::clone(&e.dispatch))
+    });
+    if let Some(dispatch) = cached {
+        return dispatch;
+    }
+    let dispatch = Rc::new(build(l));
+    CACHE.with(|c| {
+        let mut cache = c.borrow_mut();
+        if cache.entries.len() >= cache.limit {
+            cache.entries.retain(|_, e| e.form.strong_count() > 0);
+            cache.limit = (cache.entries.len() * 2).max(4096);
+        }
+        cache.entries.insert(
+            l.key(),
+            Entry {
+                form: l.downgrade(),
+                dispatch: Rc::clone(&dispatch),
+            },
+        );
+    });
+    dispatch
+}
+
+// Keys are not evaluated; a list key matches any of its elements, and the first clause to
+// mention a key wins
+fn build(l: &Seq) -> Dispatch {
+    let mut dispatch = BTreeMap::new();
+    for (i, c) in l[2..].chunks_exact(2).enumerate() {
+        let branch = 2 + 2 * i + 1;
+        match &c[0] {
+            MalType::List(keys, _) => {
+                for k in keys.iter() {
+                    dispatch.entry(k.clone()).or_insert(branch);
+                }
+            }
+            k => {
+                dispatch.entry(k.clone()).or_insert(branch);
+            }
+        }
+    }
+    dispatch
+}
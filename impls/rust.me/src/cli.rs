@@ -0,0 +1,119 @@
+//! The command line: options, then the file to run, if any, and the arguments it gets as
+//! *ARGV*. Anything after the file belongs to the program being run, flags included. Files
+//! given with --load are loaded first, so they can define what the rest uses.
+
+/// Options that only some binaries accept, each with its help. One written with a placeholder
+/// for its value, such as "--record <file>", takes the next argument as that value.
+pub type Flags = &'static [(&'static str, &'static str)];
+
+pub struct Cli {
+    /// files given with --load, to load in order before anything else
+    pub loads: Vec<String>,
+    /// expressions given with -e, to evaluate and print in order
+    pub exprs: Vec<String>,
+    pub file: Option<String>,
+    pub argv: Vec<String>,
+    /// the binary's own options that were given, with their values
+    flags: Vec<(String, Option<String>)>,
+}
+
+fn usage(flags: Flags) -> String {
+    let mut usage = format!(
+        "Usage: {} [options] [file [args...]]\n\n\
+         Runs file with *ARGV* bound to args, or else starts a REPL.\n\n\
+         Options:\n  \
+         -e, --eval <expr>   Evaluate expr and print its value, then exit; may be repeated\n  \
+         -l, --load <file>   Load file before the rest, or the REPL; may be repeated\n  \
+         -h, --help          Show this help\n  \
+         -V, --version       Show the version",
+        env!("CARGO_BIN_NAME")
+    );
+    for (flag, help) in flags {
+        usage.push_str(&format!("\n      {:<16}{}", flag, help));
+    }
+    usage
+}
+
+/// The option arg is one of flags, and whether it takes a value
+fn find(flags: Flags, arg: &str) -> Option<bool> {
+    flags
+        .iter()
+        .find_map(|(flag, _)| match flag.split_once(' ') {
+            Some((name, _)) => (name == arg).then_some(true),
+            None => (*flag == arg).then_some(false),
+        })
+}
+
+fn fail(message: &str, flags: Flags) -> ! {
+    eprintln!("Error: {}\n\n{}", message, usage(flags));
+    std::process::exit(2);
+}
+
+impl Cli {
+    /// Parse the command line, exiting after --help or --version, or with the usage for a
+    /// command line that doesn't make sense
+    pub fn parse(flags: Flags) -> Self {
+        let mut cli = Cli {
+            loads: Vec::new(),
+            exprs: Vec::new(),
+            file: None,
+            argv: Vec::new(),
+            flags: Vec::new(),
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-e" | "--eval" => match args.next() {
+                    Some(expr) => cli.exprs.push(expr),
+                    None => fail(&format!("{} needs an expression", arg), flags),
+                },
+                "-l" | "--load" => match args.next() {
+                    Some(file) => cli.loads.push(file),
+                    None => fail(&format!("{} needs a file", arg), flags),
+                },
+                "-h" | "--help" => {
+                    println!("{}", usage(flags));
+                    std::process::exit(0);
+                }
+                "-V" | "--version" => {
+                    println!("{} {}", env!("CARGO_BIN_NAME"), env!("CARGO_PKG_VERSION"));
+                    std::process::exit(0);
+                }
+                "--" => {
+                    cli.file = args.next();
+                    break;
+                }
+                _ if find(flags, &arg) == Some(false) => cli.flags.push((arg, None)),
+                _ if find(flags, &arg) == Some(true) => match args.next() {
+                    Some(value) => cli.flags.push((arg, Some(value))),
+                    None => fail(&format!("{} needs a value", arg), flags),
+                },
+                _ if arg.starts_with('-') && arg != "-" => {
+                    fail(&format!("Unknown option {}", arg), flags)
+                }
+                _ => {
+                    cli.file = Some(arg);
+                    break;
+                }
+            }
+        }
+        cli.argv = args.collect();
+        cli
+    }
+
+    /// Whether one of the binary's own flags was given
+    pub fn flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|(f, _)| f == flag)
+    }
+
+    /// The value given to one of the binary's own options, the last one if it was given more
+    /// than once
+    #[allow(dead_code)]
+    pub fn value(&self, flag: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|(f, _)| f == flag)
+            .and_then(|(_, value)| value.as_deref())
+    }
+}
@@ -0,0 +1,200 @@
+//! Commands for the REPL itself, such as `:load` and `:time`, which are handled before a line is
+//! read as mal. A line is a command when its first word is one of them, so any other keyword on
+//! its own still evaluates to itself.
+
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+
+use mal::env::Env;
+use mal::errors::MalErr;
+use mal::load_file;
+use mal::symbol::Sym;
+use mal::types::{keyword, MalType};
+
+use crate::session;
+
+const HELP: &str = "\
+:help            Show this list
+:quit            Leave the REPL
+:env             Show what has been defined this session
+:load <file>     Load a file of mal
+:reload          Load the file last loaded with :load again
+:doc <symbol>    Show a symbol's parameters and documentation
+:time on|off     Report how long each evaluation takes, as setting *time-results* does
+:save <file>     Write this session's definitions to a file
+:restore <file>  Load definitions written with :save
+:record <file>   Append what is typed and its results to a file, until :record off";
+
+/// Whether to report how long each evaluation takes, which is whether *time-results* is truthy
+pub fn timing(env: &Env) -> bool {
+    !matches!(
+        env.lookup(&Sym::new("*time-results*")),
+        Some(MalType::Nil | MalType::Bool(false)) | None
+    )
+}
+
+pub struct Commands {
+    env: Rc<Env>,
+    /// the names bound before the session started, which :env and :save leave out
+    initial: HashSet<String>,
+    /// the file :reload loads
+    loaded: Option<String>,
+    /// the file the session is being recorded in, and its path
+    transcript: Option<(File, String)>,
+}
+
+impl Commands {
+    pub fn new(env: &Rc<Env>) -> Self {
+        Commands {
+            env: Rc::clone(env),
+            initial: env.symbols().into_iter().collect(),
+            loaded: None,
+            transcript: None,
+        }
+    }
+
+    /// Start appending the lines evaluated to the file at path, each followed by what it gives as
+    /// comments, as a test file has them
+    pub fn record(&mut self, path: &str) -> Result<(), MalErr> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| MalErr::IoError(Some(path.to_string()), e))?;
+        self.transcript = Some((file, path.to_string()));
+        Ok(())
+    }
+
+    /// Add a line that was evaluated, and the value it printed or the error, to the transcript
+    /// if the session is being recorded
+    pub fn transcribe(&mut self, line: &str, result: &Result<String, MalErr>) {
+        let Some((file, path)) = &mut self.transcript else {
+            return;
+        };
+        let mut entry = format!("{}\n", line.trim_end());
+        let (prefix, printed) = match result {
+            Ok(printed) => (";=>", printed.clone()),
+            Err(e) => (";; Error: ", e.to_string()),
+        };
+        for printed_line in printed.lines() {
+            entry.push_str(&format!("{}{}\n", prefix, printed_line));
+        }
+        if let Err(e) = file.write_all(entry.as_bytes()) {
+            eprintln!("Error writing to {}, so no longer recording: {}", path, e);
+            self.transcript = None;
+        }
+    }
+
+    /// Run line if it is a command, returning None if it isn't one and Break to end the session
+    pub fn run(&mut self, line: &str) -> Option<ControlFlow<()>> {
+        let line = line.trim();
+        let (command, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let arg = arg.trim();
+        let result = match command {
+            ":help" => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            ":quit" => return Some(ControlFlow::Break(())),
+            ":env" => {
+                self.show_env();
+                Ok(())
+            }
+            ":load" => self.load(arg),
+            ":reload" => match self.loaded.clone() {
+                Some(file) => self.load(&file),
+                None => Err(MalErr::Generic(
+                    "Nothing has been loaded yet; use :load <file>".to_string(),
+                )),
+            },
+            ":doc" => self.doc(arg),
+            ":time" => self.time(arg),
+            ":save" if arg.is_empty() => Err(MalErr::Generic("Usage: :save <file>".to_string())),
+            ":save" => session::save(&self.env, &self.initial, arg),
+            ":restore" if arg.is_empty() => {
+                Err(MalErr::Generic("Usage: :restore <file>".to_string()))
+            }
+            ":restore" => load_file(&MalType::Str(arg.to_string()), &self.env).map(|_| ()),
+            ":record" => match arg {
+                "" => {
+                    match &self.transcript {
+                        Some((_, path)) => println!("Recording to {}", path),
+                        None => println!("Not recording"),
+                    }
+                    Ok(())
+                }
+                "off" => {
+                    self.transcript = None;
+                    Ok(())
+                }
+                path => self.record(path),
+            },
+            _ => return None,
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e.message());
+        }
+        Some(ControlFlow::Continue(()))
+    }
+
+    fn show_env(&self) {
+        for (name, value) in self.env.bindings() {
+            if !self.initial.contains(&name) {
+                println!("{} = {}", name, value.pr_str(true));
+            }
+        }
+    }
+
+    fn load(&mut self, file: &str) -> Result<(), MalErr> {
+        if file.is_empty() {
+            return Err(MalErr::Generic("Usage: :load <file>".to_string()));
+        }
+        self.loaded = Some(file.to_string());
+        load_file(&MalType::Str(file.to_string()), &self.env).map(|_| ())
+    }
+
+    fn doc(&self, name: &str) -> Result<(), MalErr> {
+        if name.is_empty() {
+            return Err(MalErr::Generic("Usage: :doc <symbol>".to_string()));
+        }
+        if mal::special_forms().any(|form| form == name) {
+            println!("{}\n  special form", name);
+            return Ok(());
+        }
+        let value = self.env.get(&Sym::new(name))?;
+        println!("{}", name);
+        if let MalType::Function(..) | MalType::MalFunction { .. } = value {
+            let arglists = self
+                .env
+                .get(&Sym::new("arglists"))?
+                .apply(vec![value.clone()])?;
+            if arglists != MalType::Nil {
+                println!("{}", arglists.pr_str(true));
+            }
+        }
+        if let Ok(MalType::HashMap(meta, _)) = value.get_meta() {
+            if let Some(doc) = meta.get(&keyword("doc")) {
+                println!("  {}", doc.pr_str(false));
+            }
+        }
+        Ok(())
+    }
+
+    fn time(&self, arg: &str) -> Result<(), MalErr> {
+        let on = match arg {
+            "on" => true,
+            "off" => false,
+            "" => {
+                println!("Timing is {}", if timing(&self.env) { "on" } else { "off" });
+                return Ok(());
+            }
+            _ => return Err(MalErr::Generic("Usage: :time on|off".to_string())),
+        };
+        self.env
+            .set("*time-results*".to_string(), MalType::Bool(on));
+        Ok(())
+    }
+}
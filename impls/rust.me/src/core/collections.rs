@@ -0,0 +1,795 @@
+//! Lists, vectors, hash-maps and bytes, and the sequence functions over them.
+
+use super::{hash_map, int, items, seq_items, text};
+use crate::errors::MalErr;
+use crate::types::{list_of, vector_of, MalType, Seq};
+use crate::{hashmap, list, vector};
+
+use itertools::Itertools;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+
+// From a string's UTF-8 encoding or a sequence of numbers from 0 to 255
+pub(super) fn bytes(value: &MalType) -> Result<Rc<Vec<u8>>, MalErr> {
+    match value {
+        MalType::Bytes(b) => Ok(Rc::clone(b)),
+        MalType::Str(_) => Ok(Rc::new(text(value)?.as_bytes().to_vec())),
+        _ => items(value)?
+            .iter()
+            .map(|x| match x {
+                MalType::Int(i) => u8::try_from(*i).ok(),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()
+            .map(Rc::new)
+            .ok_or_else(|| MalErr::type_error("numbers from 0 to 255", value)),
+    }
+}
+
+fn contains(value: &MalType, key: &MalType) -> Result<MalType, MalErr> {
+    match value {
+        MalType::HashMap(hm, _) => Ok(MalType::Bool(hm.contains_key(key))),
+        _ => Err(MalErr::expected("a hash-map")),
+    }
+}
+
+// (get m k not-found?), where nil is an empty map
+fn get(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let not_found = args.get(2).cloned().unwrap_or(MalType::Nil);
+    match &args[0] {
+        MalType::HashMap(hm, _) => Ok(hm.get(&args[1]).cloned().unwrap_or(not_found)),
+        MalType::Nil => Ok(not_found),
+        _ => Err(MalErr::expected("a hash-map")),
+    }
+}
+
+fn keys(value: &MalType) -> Result<MalType, MalErr> {
+    match value {
+        MalType::HashMap(hm, _) => Ok(list!(hm.keys().cloned().collect())),
+        _ => Err(MalErr::expected("a hash-map")),
+    }
+}
+
+fn vals(value: &MalType) -> Result<MalType, MalErr> {
+    match value {
+        MalType::HashMap(hm, _) => Ok(list!(hm.values().cloned().collect())),
+        _ => Err(MalErr::expected("a hash-map")),
+    }
+}
+
+fn assoc(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match &args[0] {
+        MalType::HashMap(hm, _) => {
+            let mut kvs: Vec<MalType> = (**hm)
+                .clone()
+                .into_iter()
+                .flat_map(|(k, v)| vec![k, v])
+                .collect();
+            kvs.extend_from_slice(&args[1..]);
+            hashmap!(kvs)
+        }
+        _ => Err(MalErr::expected("a hash-map")),
+    }
+}
+
+fn dissoc(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match &args[0] {
+        MalType::HashMap(hm, _) => {
+            let mut new_hm = (**hm).clone();
+            for key in &args[1..] {
+                new_hm.remove(key);
+            }
+            Ok(hash_map(new_hm))
+        }
+        _ => Err(MalErr::expected("a hash-map")),
+    }
+}
+
+fn cons(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match &args[1] {
+        MalType::List(l, _) | MalType::Vector(l, _) => {
+            let mut v = vec![args[0].clone()];
+            v.extend_from_slice(l);
+            Ok(list!(v))
+        }
+        _ => Err(MalErr::expected(
+            "a list/vector as the second parameter to cons",
+        )),
+    }
+}
+
+fn concat(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let mut v = Vec::new();
+    for a in args.iter() {
+        match a {
+            MalType::List(l, _) | MalType::Vector(l, _) => v.extend_from_slice(l),
+            _ => {
+                return Err(MalErr::FunctionErr(
+                    "concat does not support non-list items".to_string(),
+                ))
+            }
+        }
+    }
+    Ok(list!(v))
+}
+
+fn vec(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match &args[0] {
+        MalType::List(l, _) | MalType::Vector(l, _) => Ok(vector_of(l.clone())),
+        _ => Err(MalErr::expected("a list/vector to convert into vector")),
+    }
+}
+
+// (nth coll i not-found?) errors when i is out of range, unless given not-found
+fn nth(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let l = match &args[0] {
+        MalType::List(l, _) | MalType::Vector(l, _) => l,
+        x => return Err(MalErr::type_error("a list or vector", x)),
+    };
+    let i = int(&args[1])?;
+    match (usize::try_from(i).ok().and_then(|i| l.get(i)), args.get(2)) {
+        (Some(el), _) | (None, Some(el)) => Ok(el.clone()),
+        (None, None) => Err(MalErr::IndexOutOfBounds {
+            index: i,
+            of: if let MalType::Vector(..) = args[0] {
+                "vector"
+            } else {
+                "list"
+            },
+            len: l.len(),
+        }),
+    }
+}
+
+fn first(list: &MalType) -> Result<MalType, MalErr> {
+    match list {
+        MalType::List(l, _) | MalType::Vector(l, _) => {
+            Ok(l.first().cloned().unwrap_or(MalType::Nil))
+        }
+        _ => Ok(MalType::Nil),
+    }
+}
+
+fn rest(list: &MalType) -> Result<MalType, MalErr> {
+    match list {
+        MalType::List(l, _) | MalType::Vector(l, _) => Ok(list_of(l.rest())),
+        _ => Ok(list!(vec![])),
+    }
+}
+
+fn apply(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match args.as_slice() {
+        [f, middle @ .., MalType::List(l, _) | MalType::Vector(l, _)] => {
+            let mut v = middle.to_vec();
+            v.extend_from_slice(l);
+            // Let the caller make the call so that apply in tail position keeps TCO
+            Err(MalErr::TailCall(Box::new(f.clone()), v))
+        }
+        _ => Err(MalErr::expected("a list of args")),
+    }
+}
+
+// Each call's value is needed for the result, so unlike apply, map can't hand its calls back to
+// eval as tail calls. Each one returns before the next is made, so the stack doesn't grow with
+// the length of the sequence.
+fn map(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let f = &args[0];
+    match &args[1] {
+        MalType::List(l, _) | MalType::Vector(l, _) => {
+            let map_results = l.iter().map(|el| f.apply(vec![el.clone()])).collect();
+            match map_results {
+                Ok(m) => Ok(list!(m)),
+                Err(e) => Err(e),
+            }
+        }
+        _ => Err(MalErr::expected("a list of args")),
+    }
+}
+
+/// Call a mal predicate and report whether the result is truthy
+fn test(pred: &MalType, x: &MalType) -> Result<bool, MalErr> {
+    Ok(!matches!(
+        pred.apply(vec![x.clone()])?,
+        MalType::Nil | MalType::Bool(false)
+    ))
+}
+
+fn pred_coll(args: &[MalType]) -> Result<(&MalType, &[MalType]), MalErr> {
+    Ok((&args[0], items(&args[1])?))
+}
+
+// As pred_coll, for a part of the collection to be returned without copying it
+fn pred_seq(args: &[MalType]) -> Result<(&MalType, Seq), MalErr> {
+    Ok((&args[0], seq_items(&args[1])?))
+}
+
+fn filter(args: Vec<MalType>, keep: bool) -> Result<MalType, MalErr> {
+    let (pred, items) = pred_coll(&args)?;
+    let mut v = Vec::new();
+    for x in items {
+        if test(pred, x)? == keep {
+            v.push(x.clone());
+        }
+    }
+    Ok(list!(v))
+}
+
+// The first truthy result of the predicate, or nil
+fn some(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (pred, items) = pred_coll(&args)?;
+    for x in items {
+        match pred.apply(vec![x.clone()])? {
+            MalType::Nil | MalType::Bool(false) => (),
+            res => return Ok(res),
+        }
+    }
+    Ok(MalType::Nil)
+}
+
+fn every(args: Vec<MalType>, expected: bool) -> Result<MalType, MalErr> {
+    let (pred, items) = pred_coll(&args)?;
+    for x in items {
+        if test(pred, x)? != expected {
+            return Ok(MalType::Bool(false));
+        }
+    }
+    Ok(MalType::Bool(true))
+}
+
+// Strings reverse into a list of one-character strings, as seq would split them
+fn reverse(coll: &MalType) -> Result<MalType, MalErr> {
+    match coll {
+        MalType::Str(s) => Ok(list!(s
+            .chars()
+            .rev()
+            .map(|c| MalType::Str(c.to_string()))
+            .collect())),
+        _ => Ok(list!(items(coll)?.iter().rev().cloned().collect())),
+    }
+}
+
+fn last(coll: &MalType) -> Result<MalType, MalErr> {
+    Ok(items(coll)?.last().cloned().unwrap_or(MalType::Nil))
+}
+
+// nil rather than an empty list when nothing is left, as in Clojure
+fn butlast(coll: &MalType) -> Result<MalType, MalErr> {
+    let items = seq_items(coll)?;
+    match items.len() {
+        0 | 1 => Ok(MalType::Nil),
+        n => Ok(list_of(items.slice(..n - 1))),
+    }
+}
+
+fn count_coll(args: &[MalType]) -> Result<(usize, Seq), MalErr> {
+    let items = seq_items(&args[1])?;
+    // negative counts take nothing, as in Clojure
+    Ok((int(&args[0])?.clamp(0, items.len() as i64) as usize, items))
+}
+
+// The number of elements of a collection, where strings count characters rather than bytes
+pub(super) fn size(value: &MalType) -> Option<usize> {
+    match value {
+        MalType::List(l, _) | MalType::Vector(l, _) => Some(l.len()),
+        MalType::HashMap(hm, _) => Some(hm.len()),
+        MalType::Bytes(b) => Some(b.len()),
+        MalType::Nil => Some(0),
+        MalType::Str(s) => Some(s.chars().count()),
+        _ => None,
+    }
+}
+
+fn count(x: &MalType) -> Result<MalType, MalErr> {
+    size(x)
+        .map(|n| MalType::Int(n as i64))
+        .ok_or_else(|| MalErr::type_error("a countable collection", x))
+}
+
+// An empty collection of the same kind, keeping its metadata; nil for anything else
+fn empty(coll: &MalType) -> MalType {
+    match coll {
+        MalType::List(_, meta) => MalType::List(Seq::default(), Rc::clone(meta)),
+        MalType::Vector(_, meta) => MalType::Vector(Seq::default(), Rc::clone(meta)),
+        MalType::HashMap(_, meta) => MalType::HashMap(Rc::new(BTreeMap::new()), Rc::clone(meta)),
+        MalType::Bytes(_) => MalType::Bytes(Rc::new(vec![])),
+        MalType::Str(_) => MalType::Str(String::new()),
+        _ => MalType::Nil,
+    }
+}
+
+fn split_at(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (n, items) = count_coll(&args)?;
+    Ok(vector![
+        list_of(items.slice(..n)),
+        list_of(items.slice(n..))
+    ])
+}
+
+// Index of the first element failing the predicate
+fn prefix_len(pred: &MalType, items: &[MalType]) -> Result<usize, MalErr> {
+    for (i, x) in items.iter().enumerate() {
+        if !test(pred, x)? {
+            return Ok(i);
+        }
+    }
+    Ok(items.len())
+}
+
+fn take_while(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (pred, items) = pred_seq(&args)?;
+    Ok(list_of(items.slice(..prefix_len(pred, &items)?)))
+}
+
+fn drop_while(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (pred, items) = pred_seq(&args)?;
+    Ok(list_of(items.slice(prefix_len(pred, &items)?..)))
+}
+
+fn positive(value: &MalType) -> Result<usize, MalErr> {
+    match value {
+        MalType::Int(i) if *i > 0 => Ok(*i as usize),
+        _ => Err(MalErr::type_error("a positive number", value)),
+    }
+}
+
+// (partition n [step [pad]] coll): chunks of n taken every step elements. A short final chunk
+// is dropped, unless pad is given, in which case it is filled from pad (and may stay short).
+fn partition(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let n = positive(&args[0])?;
+    let step = match args.len() {
+        2 => n,
+        _ => positive(&args[1])?,
+    };
+    let pad = match args.len() {
+        4 => Some(items(&args[2])?),
+        _ => None,
+    };
+    let items = items(&args[args.len() - 1])?;
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < items.len() {
+        let chunk = &items[start..items.len().min(start + n)];
+        if chunk.len() == n {
+            chunks.push(list!(chunk.to_vec()));
+        } else {
+            if let Some(pad) = pad {
+                let mut v = chunk.to_vec();
+                v.extend(pad.iter().take(n - chunk.len()).cloned());
+                chunks.push(list!(v));
+            }
+            break;
+        }
+        start += step;
+    }
+    Ok(list!(chunks))
+}
+
+// Start a new chunk each time f returns a different value
+fn partition_by(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (f, items) = pred_coll(&args)?;
+    let mut chunks = Vec::new();
+    let mut chunk: Vec<MalType> = Vec::new();
+    let mut last_key = None;
+    for x in items {
+        let key = f.apply(vec![x.clone()])?;
+        if last_key.as_ref().is_some_and(|k| *k != key) {
+            chunks.push(list!(std::mem::take(&mut chunk)));
+        }
+        chunk.push(x.clone());
+        last_key = Some(key);
+    }
+    if !chunk.is_empty() {
+        chunks.push(list!(chunk));
+    }
+    Ok(list!(chunks))
+}
+
+// Stops at the end of the shortest collection
+fn interleave(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let colls = args.iter().map(items).collect::<Result<Vec<_>, _>>()?;
+    let shortest = colls.iter().map(|c| c.len()).min().unwrap_or(0);
+    Ok(list!((0..shortest)
+        .flat_map(|i| colls.iter().map(move |c| c[i].clone()))
+        .collect()))
+}
+
+fn interpose(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    Ok(list!(Itertools::intersperse(
+        items(&args[1])?.iter().cloned(),
+        args[0].clone()
+    )
+    .collect()))
+}
+
+fn flatten(coll: &MalType) -> Result<MalType, MalErr> {
+    fn walk(items: &[MalType], out: &mut Vec<MalType>) {
+        for x in items {
+            match x {
+                MalType::List(l, _) | MalType::Vector(l, _) => walk(l, out),
+                _ => out.push(x.clone()),
+            }
+        }
+    }
+    let mut out = Vec::new();
+    match coll {
+        MalType::List(l, _) | MalType::Vector(l, _) => walk(l, &mut out),
+        // anything that isn't sequential flattens to nothing, as in Clojure
+        _ => (),
+    }
+    Ok(list!(out))
+}
+
+// A value as distinct, frequencies and group-by tell values apart, which is as = does: a list
+// and a vector of the same elements are the same key, and metadata is ignored. Where several
+// equal values are seen, the first is the one kept.
+struct Key(MalType);
+
+// Lists and vectors order as one kind, by their elements, and maps by their entries; anything
+// else orders as MalType does
+fn key_cmp(a: &MalType, b: &MalType) -> Ordering {
+    let any_list = || MalType::List(Seq::default(), Rc::new(MalType::Nil));
+    match (a, b) {
+        (
+            MalType::List(x, _) | MalType::Vector(x, _),
+            MalType::List(y, _) | MalType::Vector(y, _),
+        ) => x
+            .iter()
+            .zip(y.iter())
+            .map(|(a, b)| key_cmp(a, b))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| x.len().cmp(&y.len())),
+        (MalType::List(..) | MalType::Vector(..), _) => any_list().cmp(b),
+        (_, MalType::List(..) | MalType::Vector(..)) => a.cmp(&any_list()),
+        (MalType::HashMap(x, _), MalType::HashMap(y, _)) => x
+            .iter()
+            .zip(y.iter())
+            .map(|((ka, va), (kb, vb))| key_cmp(ka, kb).then_with(|| key_cmp(va, vb)))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| x.len().cmp(&y.len())),
+        _ => a.cmp(b),
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        key_cmp(&self.0, &other.0).is_eq()
+    }
+}
+
+impl Eq for Key {}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        key_cmp(&self.0, &other.0)
+    }
+}
+
+fn distinct(coll: &MalType) -> Result<MalType, MalErr> {
+    let mut seen: BTreeSet<Key> = BTreeSet::new();
+    let mut kept = Vec::new();
+    for x in items(coll)? {
+        if seen.insert(Key(x.clone())) {
+            kept.push(x.clone());
+        }
+    }
+    Ok(list!(kept))
+}
+
+fn frequencies(coll: &MalType) -> Result<MalType, MalErr> {
+    let mut counts: BTreeMap<Key, i64> = BTreeMap::new();
+    for x in items(coll)? {
+        *counts.entry(Key(x.clone())).or_default() += 1;
+    }
+    Ok(hash_map(
+        counts
+            .into_iter()
+            .map(|(k, n)| (k.0, MalType::Int(n)))
+            .collect(),
+    ))
+}
+
+// Map from each result of f to a vector of the elements that produced it
+fn group_by(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (f, items) = pred_coll(&args)?;
+    let mut groups: BTreeMap<Key, Vec<MalType>> = BTreeMap::new();
+    for x in items {
+        groups
+            .entry(Key(f.apply(vec![x.clone()])?))
+            .or_default()
+            .push(x.clone());
+    }
+    Ok(hash_map(
+        groups.into_iter().map(|(k, v)| (k.0, vector!(v))).collect(),
+    ))
+}
+
+fn zipmap(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    Ok(hash_map(
+        items(&args[0])?
+            .iter()
+            .cloned()
+            .zip(items(&args[1])?.iter().cloned())
+            .collect(),
+    ))
+}
+
+// Later maps win, or are combined with f when one is given. nil arguments are skipped and
+// merging nothing but nils gives nil.
+fn merge(f: Option<&MalType>, maps: &[MalType]) -> Result<MalType, MalErr> {
+    let mut merged: Option<BTreeMap<MalType, MalType>> = None;
+    for m in maps {
+        let hm = match m {
+            MalType::HashMap(hm, _) => hm,
+            MalType::Nil => continue,
+            _ => return Err(MalErr::type_error("a hash-map", m)),
+        };
+        let acc = merged.get_or_insert_with(BTreeMap::new);
+        for (k, v) in hm.iter() {
+            let value = match (f, acc.remove(k)) {
+                (Some(f), Some(old)) => f.apply(vec![old, v.clone()])?,
+                _ => v.clone(),
+            };
+            acc.insert(k.clone(), value);
+        }
+    }
+    Ok(merged.map(hash_map).unwrap_or(MalType::Nil))
+}
+
+// Vectors are looked up by index; anything else that isn't a map has no entries
+fn lookup(coll: &MalType, key: &MalType) -> Option<MalType> {
+    match (coll, key) {
+        (MalType::HashMap(hm, _), _) => hm.get(key).cloned(),
+        (MalType::Vector(v, _), MalType::Int(i)) => {
+            usize::try_from(*i).ok().and_then(|i| v.get(i)).cloned()
+        }
+        _ => None,
+    }
+}
+
+// assoc on a single key, where nil stands for an empty map and a vector takes an index
+// (one past the end appends)
+fn assoc_key(coll: &MalType, key: MalType, value: MalType) -> Result<MalType, MalErr> {
+    let mut hm = match (coll, &key) {
+        (MalType::HashMap(hm, _), _) => (**hm).clone(),
+        (MalType::Nil, _) => BTreeMap::new(),
+        (MalType::Vector(v, _), MalType::Int(i)) if *i >= 0 && *i as usize <= v.len() => {
+            let mut v = v.to_vec();
+            match v.get_mut(*i as usize) {
+                Some(el) => *el = value,
+                None => v.push(value),
+            }
+            return Ok(vector!(v));
+        }
+        _ => return Err(MalErr::type_error("a hash-map or a vector and index", coll)),
+    };
+    hm.insert(key, value);
+    Ok(hash_map(hm))
+}
+
+fn get_in(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let mut value = args[0].clone();
+    for k in items(&args[1])? {
+        value = match lookup(&value, k) {
+            Some(v) => v,
+            None => return Ok(args.get(2).cloned().unwrap_or(MalType::Nil)),
+        };
+    }
+    Ok(value)
+}
+
+// Replace the value at the key path with f applied to it, creating missing maps on the way
+fn update_path(
+    m: &MalType,
+    ks: &[MalType],
+    f: &dyn Fn(MalType) -> Result<MalType, MalErr>,
+) -> Result<MalType, MalErr> {
+    match ks.split_first() {
+        Some((k, rest)) => {
+            let inner = lookup(m, k).unwrap_or(MalType::Nil);
+            assoc_key(m, k.clone(), update_path(&inner, rest, f)?)
+        }
+        None => f(m.clone()),
+    }
+}
+
+fn assoc_in(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let v = &args[2];
+    update_path(&args[0], key_path(&args[1])?, &|_| Ok(v.clone()))
+}
+
+fn key_path(ks: &MalType) -> Result<&[MalType], MalErr> {
+    match items(ks)? {
+        [] => Err(MalErr::type_error("a non-empty key path", ks)),
+        ks => Ok(ks),
+    }
+}
+
+// (update-in m ks f & args) calls (f old-value args...)
+fn update_in(
+    m: &MalType,
+    ks: &[MalType],
+    f: &MalType,
+    extra: &[MalType],
+) -> Result<MalType, MalErr> {
+    update_path(m, ks, &|old| {
+        let mut fargs = vec![old];
+        fargs.extend_from_slice(extra);
+        f.apply(fargs)
+    })
+}
+
+fn select_keys(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let m = &args[0];
+    Ok(hash_map(
+        items(&args[1])?
+            .iter()
+            .filter_map(|k| lookup(m, k).map(|v| (k.clone(), v)))
+            .collect(),
+    ))
+}
+
+fn reduce(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let f = &args[0];
+    let init = match args.as_slice() {
+        [_, init, _] => Some(init.clone()),
+        _ => None,
+    };
+    let items = items(&args[args.len() - 1])?;
+    let (mut acc, items) = match (init, items.split_first()) {
+        (Some(init), _) => (init, items),
+        (None, Some((first, rest))) => (first.clone(), rest),
+        // like Clojure, reducing nothing without an initial value calls f with no arguments
+        (None, None) => return f.apply(vec![]),
+    };
+    for item in items {
+        acc = f.apply(vec![acc, item.clone()])?;
+        if let MalType::Reduced(v) = acc {
+            return Ok((*v).clone());
+        }
+    }
+    Ok(acc)
+}
+
+fn conj(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match &args[0] {
+        MalType::List(l, _) => {
+            let mut new_list = l.to_vec();
+            for a in &args[1..] {
+                new_list.insert(0, a.clone());
+            }
+            Ok(list!(new_list))
+        }
+        MalType::Vector(v, _) => {
+            let mut new_vec = v.to_vec();
+            new_vec.extend_from_slice(&args[1..]);
+            Ok(vector!(new_vec))
+        }
+        // entries are [key value] pairs or whole maps
+        MalType::HashMap(hm, _) => {
+            let mut new_hm = (**hm).clone();
+            for a in &args[1..] {
+                match a {
+                    MalType::List(kv, _) | MalType::Vector(kv, _) if kv.len() == 2 => {
+                        new_hm.insert(kv[0].clone(), kv[1].clone());
+                    }
+                    MalType::HashMap(other, _) => {
+                        new_hm.extend(other.iter().map(|(k, v)| (k.clone(), v.clone())))
+                    }
+                    _ => {
+                        return Err(MalErr::FunctionErr(format!(
+                            "expected a [key value] pair or a hash-map, got {}",
+                            a
+                        )))
+                    }
+                }
+            }
+            Ok(hash_map(new_hm))
+        }
+        // conj onto nil builds a list, like conj onto ()
+        MalType::Nil => Ok(list!(args[1..].iter().rev().cloned().collect())),
+        _ => Err(MalErr::FunctionErr(
+            "expected a list, vector or hash-map".to_string(),
+        )),
+    }
+}
+
+fn into(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let mut v = vec![args[0].clone()];
+    match &args[1] {
+        // a map pours in as its [key value] entries
+        MalType::HashMap(hm, _) => {
+            v.extend(hm.iter().map(|(k, val)| vector![k.clone(), val.clone()]))
+        }
+        coll => v.extend_from_slice(items(coll)?),
+    }
+    conj(v)
+}
+
+fn seq(value: &MalType) -> Result<MalType, MalErr> {
+    match value {
+        MalType::List(l, _) | MalType::Vector(l, _) if l.is_empty() => Ok(MalType::Nil),
+        MalType::List(l, _) | MalType::Vector(l, _) => Ok(list_of(l.clone())),
+        MalType::Str(s) if s.is_empty() => Ok(MalType::Nil),
+        MalType::Str(s) => Ok(list!(s
+            .chars()
+            .map(|c| { MalType::Str(c.to_string()) })
+            .collect())),
+        MalType::Bytes(b) if b.is_empty() => Ok(MalType::Nil),
+        MalType::Bytes(b) => Ok(list!(b.iter().map(|x| MalType::Int(*x as i64)).collect())),
+        MalType::Nil => Ok(MalType::Nil),
+        _ => Err(MalErr::FunctionErr("invalid value for seq".to_string())),
+    }
+}
+
+pub(super) fn register(ns: &mut HashMap<&'static str, MalType>) {
+    builtins! {ns;
+        "bytes" "([x])" [] => |a| bytes(&a[0]).map(MalType::Bytes),
+        "list" "([& xs])" [] => |a| Ok(list!(a)),
+        "vector" "([& xs])" [] => |a| Ok(vector!(a)),
+        "hash-map" "([& kvs])" [] => |a| hashmap!(a),
+        "contains?" "([m k])" [] => |a| contains(&a[0], &a[1]),
+        "get" "([m k] [m k not-found])" [] => get,
+        "keys" "([m])" [] => |a| keys(&a[0]),
+        "vals" "([m])" [] => |a| vals(&a[0]),
+        "assoc" "([m & kvs])" [] => assoc,
+        "dissoc" "([m & ks])" [] => dissoc,
+        "zipmap" "([keys vals])" [] => zipmap,
+        "merge" "([& maps])" [] => |a| merge(None, &a),
+        "merge-with" "([f & maps])" [Fn, Any] => |a| merge(Some(&a[0]), &a[1..]),
+        "get-in" "([m ks] [m ks not-found])" [] => get_in,
+        "assoc-in" "([m ks v])" [] => assoc_in,
+        "update" "([m k f & args])" [Any, Any, Fn, Any] => |a| {
+            update_in(&a[0], std::slice::from_ref(&a[1]), &a[2], &a[3..])
+        },
+        "update-in" "([m ks f & args])" [Any, Any, Fn, Any] => |a| {
+            update_in(&a[0], key_path(&a[1])?, &a[2], &a[3..])
+        },
+        "select-keys" "([m ks])" [] => select_keys,
+        "count" "([coll])" [] => |a| count(&a[0]),
+        "cons" "([x coll])" [] => cons,
+        "concat" "([& colls])" [] => concat,
+        "vec" "([coll])" [] => vec,
+        "nth" "([coll n] [coll n not-found])" [Any, Int, Any] => nth,
+        "first" "([coll])" [] => |a| first(&a[0]),
+        "rest" "([coll])" [] => |a| rest(&a[0]),
+        "reverse" "([coll])" [] => |a| reverse(&a[0]),
+        "empty" "([coll])" [] => |a| Ok(empty(&a[0])),
+        "not-empty" "([coll])" [] => |a| match size(&a[0]) {
+            Some(0) => Ok(MalType::Nil),
+            _ => Ok(a[0].clone()),
+        },
+        "last" "([coll])" [] => |a| last(&a[0]),
+        "butlast" "([coll])" [] => |a| butlast(&a[0]),
+        "take" "([n coll])" [] => |a| count_coll(&a).map(|(n, items)| list_of(items.slice(..n))),
+        "drop" "([n coll])" [] => |a| count_coll(&a).map(|(n, items)| list_of(items.slice(n..))),
+        "split-at" "([n coll])" [] => split_at,
+        "take-while" "([pred coll])" [Fn, Any] => take_while,
+        "drop-while" "([pred coll])" [Fn, Any] => drop_while,
+        "partition" "([n coll] [n step coll] [n step pad coll])" [] => partition,
+        "partition-by" "([f coll])" [Fn, Any] => partition_by,
+        "interleave" "([& colls])" [] => interleave,
+        "interpose" "([sep coll])" [] => interpose,
+        "flatten" "([coll])" [] => |a| flatten(&a[0]),
+        "distinct" "([coll])" [] => |a| distinct(&a[0]),
+        "frequencies" "([coll])" [] => |a| frequencies(&a[0]),
+        "group-by" "([f coll])" [Fn, Any] => group_by,
+        "apply" "([f args] [f x & args])" [Fn, Any] => apply,
+        "map" "([f coll])" [Fn, Any] => map,
+        "reduce" "([f coll] [f init coll])" [Fn, Any] => reduce,
+        "filter" "([pred coll])" [Fn, Any] => |a| filter(a, true),
+        "remove" "([pred coll])" [Fn, Any] => |a| filter(a, false),
+        "some" "([pred coll])" [Fn, Any] => some,
+        "every?" "([pred coll])" [Fn, Any] => |a| every(a, true),
+        "not-any?" "([pred coll])" [Fn, Any] => |a| every(a, false),
+        "conj" "([coll & xs])" [] => conj,
+        "into" "([to from])" [] => into,
+        "seq" "([coll])" [] => |a| seq(&a[0]),
+    }
+}
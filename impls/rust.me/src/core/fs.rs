@@ -0,0 +1,131 @@
+//! Reading, writing and managing files. Only built with the "fs" feature.
+
+use super::collections::bytes;
+use super::path::{path_arg, path_str};
+use super::strings::read_string;
+use super::text;
+use crate::errors::MalErr;
+use crate::list;
+use crate::types::{self, MalType};
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{self, Path};
+use std::rc::Rc;
+
+fn slurp(f: String) -> Result<MalType, MalErr> {
+    let mut s = String::new();
+    match File::open(&f).and_then(|mut file| file.read_to_string(&mut s)) {
+        Ok(_) => Ok(MalType::Str(s)),
+        Err(e) => Err(MalErr::IoError(Some(f), e)),
+    }
+}
+
+// (spit path content & opts), where opts may contain :append true. spit-bytes writes the
+// content as bytes rather than as its printed form.
+fn spit(args: Vec<MalType>, raw: bool) -> Result<MalType, MalErr> {
+    let (path, content, opts) = (text(&args[0])?, &args[1], &args[2..]);
+    if opts.len() % 2 != 0 {
+        return Err(MalErr::expected("a path, content and :append true/false"));
+    }
+    let mut append = false;
+    for opt in opts.chunks(2) {
+        match (&opt[0], &opt[1]) {
+            (k, v) if *k == types::keyword("append") => {
+                append = !matches!(v, MalType::Nil | MalType::Bool(false))
+            }
+            (k, _) => return Err(MalErr::FunctionErr(format!("Unknown spit option {}", k))),
+        }
+    }
+    let content = if raw {
+        bytes(content)?
+    } else {
+        Rc::new(content.pr_str(false).into_bytes())
+    };
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .and_then(|mut f| f.write_all(&content))
+        .map_err(|e| MalErr::IoError(Some(path.to_string()), e))?;
+    Ok(MalType::Nil)
+}
+
+fn io_result<T>(path: &str, res: std::io::Result<T>) -> Result<MalType, MalErr> {
+    res.map(|_| MalType::Nil)
+        .map_err(|e| MalErr::IoError(Some(path.to_string()), e))
+}
+
+// Removes a file, or a directory when it is empty
+fn delete_file(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let path = path_arg(&args)?;
+    io_result(
+        path,
+        if Path::new(path).is_dir() {
+            fs::remove_dir(path)
+        } else {
+            fs::remove_file(path)
+        },
+    )
+}
+
+// Entry names, sorted
+fn list_dir(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let path = path_arg(&args)?;
+    let mut names = fs::read_dir(path)
+        .and_then(|entries| {
+            entries
+                .map(|e| e.map(|e| e.file_name().to_string_lossy().into_owned()))
+                .collect::<std::io::Result<Vec<_>>>()
+        })
+        .map_err(|e| MalErr::IoError(Some(path.to_string()), e))?;
+    names.sort();
+    Ok(list!(names.into_iter().map(MalType::Str).collect()))
+}
+
+fn copy_or_rename(args: Vec<MalType>, rename: bool) -> Result<MalType, MalErr> {
+    let (from, to) = (text(&args[0])?, text(&args[1])?);
+    if rename {
+        io_result(from, fs::rename(from, to))
+    } else {
+        io_result(from, fs::copy(from, to))
+    }
+}
+
+fn slurp_bytes(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let path = path_arg(&args)?;
+    fs::read(path)
+        .map(|b| MalType::Bytes(Rc::new(b)))
+        .map_err(|e| MalErr::IoError(Some(path.to_string()), e))
+}
+
+pub(super) fn register(ns: &mut HashMap<&'static str, MalType>) {
+    builtins! {ns;
+        "slurp" "([path])" [Str] => |a| read_string(a, slurp),
+        "spit" "([path content & opts])" [Str, Any] => |a| spit(a, false),
+        "slurp-bytes" "([path])" [Str] => slurp_bytes,
+        "spit-bytes" "([path content & opts])" [Str, Any] => |a| spit(a, true),
+        "file-exists?" "([path])" [Str] => |a| {
+            Ok(MalType::Bool(Path::new(text(&a[0])?).exists()))
+        },
+        "directory?" "([path])" [Str] => |a| Ok(MalType::Bool(Path::new(text(&a[0])?).is_dir())),
+        "delete-file" "([path])" [Str] => delete_file,
+        "list-dir" "([path])" [Str] => list_dir,
+        "mkdir" "([path])" [Str] => |a| {
+            let path = path_arg(&a)?;
+            io_result(path, fs::create_dir_all(path))
+        },
+        "copy-file" "([from to])" [Str] => |a| copy_or_rename(a, false),
+        "rename-file" "([from to])" [Str] => |a| copy_or_rename(a, true),
+        // made absolute against the working directory, without touching the filesystem
+        "absolute-path" "([path])" [Str] => |a| {
+            let p = path_arg(&a)?;
+            path::absolute(p)
+                .map(|abs| path_str(&abs))
+                .map_err(|e| MalErr::IoError(Some(p.to_string()), e))
+        },
+    }
+}
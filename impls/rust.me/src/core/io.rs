@@ -0,0 +1,169 @@
+//! Printing and reading lines on the standard streams, which a test runner can redirect, and HTTP
+//! when it is built in.
+
+use crate::errors::MalErr;
+#[cfg(feature = "http")]
+use crate::http;
+use crate::printer::pr_list;
+use crate::types::MalType;
+
+#[cfg(feature = "readline")]
+use lazy_static::lazy_static;
+#[cfg(feature = "readline")]
+use rustyline::{error::ReadlineError, DefaultEditor};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+#[cfg(feature = "readline")]
+use std::sync::Mutex;
+
+/// The standard streams while they are redirected: lines of input still to be read, how many have
+/// been, and everything printed so far
+struct Redirected {
+    input: Vec<String>,
+    read: usize,
+    output: String,
+}
+
+thread_local! {
+    static REDIRECTED: RefCell<Option<Redirected>> = const { RefCell::new(None) };
+}
+
+/// Run f with readline and read-line reading from input instead of stdin, and with what prn,
+/// println, eprn, eprintln and the tracer print collected instead of written out, both
+/// streams together as a terminal would show them. Returns what f does, the output, and how many
+/// lines of input were read.
+pub fn redirect<T>(input: &[String], f: impl FnOnce() -> T) -> (T, String, usize) {
+    let redirected = Redirected {
+        input: input.to_vec(),
+        read: 0,
+        output: String::new(),
+    };
+    let outer = REDIRECTED.with(|r| r.replace(Some(redirected)));
+    let result = f();
+    let redirected = REDIRECTED.with(|r| r.replace(outer)).unwrap();
+    (result, redirected.output, redirected.read)
+}
+
+/// Add line to the output being collected, returning false if output isn't redirected
+fn collect(line: &str) -> bool {
+    REDIRECTED.with(|r| match r.borrow_mut().as_mut() {
+        Some(redirected) => {
+            redirected.output.push_str(line);
+            redirected.output.push('\n');
+            true
+        }
+        None => false,
+    })
+}
+
+/// Print line on stdout, unless output is redirected
+pub(crate) fn out_line(line: &str) {
+    if !collect(line) {
+        println!("{}", line);
+    }
+}
+
+/// Print line on stderr, unless output is redirected
+pub(crate) fn err_line(line: &str) {
+    if !collect(line) {
+        eprintln!("{}", line);
+    }
+}
+
+/// The next line of redirected input, with Some(None) at its end, or None if input isn't
+/// redirected
+fn next_line() -> Option<Option<String>> {
+    REDIRECTED.with(|r| {
+        let mut r = r.borrow_mut();
+        let redirected = r.as_mut()?;
+        let line = redirected.input.get(redirected.read).cloned();
+        redirected.read += line.is_some() as usize;
+        Some(line)
+    })
+}
+
+fn print_string(args: Vec<MalType>, print_readably: bool) -> Result<MalType, MalErr> {
+    out_line(&pr_list(&args, "", "", print_readably, " "));
+    Ok(MalType::Nil)
+}
+
+fn eprint_string(args: Vec<MalType>, print_readably: bool) -> Result<MalType, MalErr> {
+    err_line(&pr_list(&args, "", "", print_readably, " "));
+    Ok(MalType::Nil)
+}
+
+fn flush() -> Result<MalType, MalErr> {
+    std::io::stdout()
+        .flush()
+        .and_then(|_| std::io::stderr().flush())?;
+    Ok(MalType::Nil)
+}
+
+#[cfg(feature = "readline")]
+lazy_static! {
+    // None if the terminal couldn't be set up for line editing
+    static ref RL: Mutex<Option<DefaultEditor>> = Mutex::new(DefaultEditor::new().ok());
+}
+
+#[cfg(feature = "readline")]
+fn readline(prompt: &MalType) -> Result<MalType, MalErr> {
+    match prompt {
+        MalType::Str(p) => {
+            if let Some(line) = next_line() {
+                return Ok(line.map_or(MalType::Nil, MalType::Str));
+            }
+            let readline = match RL.lock().unwrap().as_mut() {
+                Some(editor) => editor.readline(p),
+                None => {
+                    print!("{}", p);
+                    std::io::stdout().flush()?;
+                    return read_line();
+                }
+            };
+            match readline {
+                Ok(line) => Ok(MalType::Str(line)),
+                Err(ReadlineError::Eof) => Ok(MalType::Nil),
+                Err(e) => Err(MalErr::FunctionErr(format!("readline error: {}", e))),
+            }
+        }
+        _ => Err(MalErr::FunctionErr(
+            "readline prompt should be a string".to_string(),
+        )),
+    }
+}
+
+// A line from stdin without its line ending, or nil at end of input. Unlike readline this
+// doesn't go through the line editor, so it works when stdin is a pipe or a file.
+fn read_line() -> Result<MalType, MalErr> {
+    if let Some(line) = next_line() {
+        return Ok(line.map_or(MalType::Nil, MalType::Str));
+    }
+    let mut line = String::new();
+    match std::io::stdin().read_line(&mut line) {
+        Ok(0) => Ok(MalType::Nil),
+        Ok(_) => {
+            let len = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(len);
+            Ok(MalType::Str(line))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub(super) fn register(ns: &mut HashMap<&'static str, MalType>) {
+    builtins! {ns;
+        "prn" "([& xs])" [] => |a| print_string(a, true),
+        "println" "([& xs])" [] => |a| print_string(a, false),
+        "eprn" "([& xs])" [] => |a| eprint_string(a, true),
+        "eprintln" "([& xs])" [] => |a| eprint_string(a, false),
+        "flush" "([])" [] => |_| flush(),
+        #[cfg(feature = "http")]
+        "http-get" "([url])" [Str] => http::get,
+        #[cfg(feature = "http")]
+        "http-request" "([opts])" [] => http::request,
+        #[cfg(feature = "readline")]
+        "readline" "([prompt])" [] => |a| readline(&a[0]),
+        "read-line" "([])" [] => |_| read_line(),
+    }
+}
@@ -0,0 +1,158 @@
+//! Type predicates, atoms, metadata and the other builtins about values and the runtime.
+
+use super::collections::size;
+use super::out_line;
+use crate::errors::MalErr;
+use crate::introspect;
+use crate::profile;
+#[cfg(feature = "sync")]
+use crate::thread;
+use crate::types::{self, atom, MalType};
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The kind of a value, as returned by (type x)
+fn type_name(value: &MalType) -> &'static str {
+    match value {
+        MalType::Nil => "nil",
+        MalType::Bool(..) => "boolean",
+        MalType::Int(..) => "number",
+        MalType::Keyword(_) => "keyword",
+        MalType::Str(..) => "string",
+        MalType::Symbol(..) => "symbol",
+        MalType::List(..) => "list",
+        MalType::Vector(..) => "vector",
+        MalType::HashMap(..) => "hash-map",
+        MalType::MalFunction { is_macro: true, .. } => "macro",
+        MalType::Function(..) | MalType::MalFunction { .. } => "function",
+        MalType::Atom(..) => "atom",
+        MalType::Reduced(..) => "reduced",
+        MalType::Bytes(..) => "bytes",
+        MalType::Pattern(..) => "regex",
+        #[cfg(feature = "sync")]
+        MalType::Thread(h) if h.future => "future",
+        #[cfg(feature = "sync")]
+        MalType::Thread(..) => "thread",
+        #[cfg(feature = "sync")]
+        MalType::Channel(..) => "channel",
+    }
+}
+
+fn is_variant(value: &MalType, variant: &str) -> Result<MalType, MalErr> {
+    let is_type = match (value, variant) {
+        (MalType::Bool(b), "true") => *b,
+        (MalType::Bool(b), "false") => !*b,
+        (MalType::List(..) | MalType::Vector(..), "sequential") => true,
+        (_, "empty") => size(value) == Some(0),
+        _ => type_name(value) == variant,
+    };
+    Ok(MalType::Bool(is_type))
+}
+
+fn deref(atom: &MalType) -> Result<MalType, MalErr> {
+    match atom {
+        MalType::Atom(a) => Ok(a.borrow().clone()),
+        #[cfg(feature = "sync")]
+        MalType::Thread(h) => h.wait(),
+        _ => Err(MalErr::FunctionErr("Cannot deref a non-atom".to_string())),
+    }
+}
+
+fn reset(atom: &MalType, new_val: &MalType) -> Result<MalType, MalErr> {
+    match atom {
+        MalType::Atom(a) => {
+            a.replace(new_val.clone());
+            Ok(new_val.clone())
+        }
+        _ => Err(MalErr::FunctionErr("Cannot reset a non-atom".to_string())),
+    }
+}
+
+fn swap(atom: &MalType, f: &MalType, optargs: Vec<MalType>) -> Result<MalType, MalErr> {
+    match atom {
+        MalType::Atom(a) => {
+            let mut args = optargs;
+            args.insert(0, a.borrow().clone());
+            // f may deref or reset the atom itself, so nothing stays borrowed while it runs. Its
+            // value is stored afterwards, so the call can't be handed back to eval as a tail call.
+            let new_val = f.apply(args)?;
+            a.replace(new_val.clone());
+            Ok(new_val)
+        }
+        _ => Err(MalErr::FunctionErr("Cannot swap a non-atom".to_string())),
+    }
+}
+
+fn time() -> Result<MalType, MalErr> {
+    let now = SystemTime::now();
+    let since_epoch = now
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| MalErr::FunctionErr("The system clock is before 1970".to_string()))?;
+    Ok(MalType::Int(since_epoch.as_millis() as i64))
+}
+
+pub(super) fn register(ns: &mut HashMap<&'static str, MalType>) {
+    builtins! {ns;
+        "bytes?" "([x])" [] => |a| is_variant(&a[0], "bytes"),
+        "list?" "([x])" [] => |a| is_variant(&a[0], "list"),
+        "empty?" "([coll])" [] => |a| is_variant(&a[0], "empty"),
+        "nil?" "([x])" [] => |a| is_variant(&a[0], "nil"),
+        "true?" "([x])" [] => |a| is_variant(&a[0], "true"),
+        "false?" "([x])" [] => |a| is_variant(&a[0], "false"),
+        "symbol?" "([x])" [] => |a| is_variant(&a[0], "symbol"),
+        "keyword?" "([x])" [] => |a| is_variant(&a[0], "keyword"),
+        "vector?" "([x])" [] => |a| is_variant(&a[0], "vector"),
+        "sequential?" "([x])" [] => |a| is_variant(&a[0], "sequential"),
+        "map?" "([x])" [] => |a| is_variant(&a[0], "hash-map"),
+        "atom" "([x])" [] => |a| Ok(atom(&a[0])),
+        "atom?" "([x])" [] => |a| is_variant(&a[0], "atom"),
+        "deref" "([ref])" [] => |a| deref(&a[0]),
+        "reset!" "([atom x])" [Atom, Any] => |a| reset(&a[0], &a[1]),
+        "swap!" "([atom f & args])" [Atom, Fn, Any] => |a| swap(&a[0], &a[1], a[2..].to_vec()),
+        "throw" "([x])" [] => |a| Err(MalErr::Throw(a[0].clone())),
+        "reduced" "([x])" [] => |a| Ok(MalType::Reduced(Rc::new(a[0].clone()))),
+        "reduced?" "([x])" [] => |a| is_variant(&a[0], "reduced"),
+        #[cfg(feature = "sync")]
+        "thread" "([f & args])" [] => thread::spawn,
+        #[cfg(feature = "sync")]
+        "join" "([thread])" [] => thread::join,
+        #[cfg(feature = "sync")]
+        "future-call" "([f])" [] => thread::future_call,
+        #[cfg(feature = "sync")]
+        "realized?" "([future])" [] => thread::is_realized,
+        #[cfg(feature = "sync")]
+        "pmap" "([f coll])" [] => thread::pmap,
+        #[cfg(feature = "sync")]
+        "chan" "([] [n])" [] => thread::chan,
+        #[cfg(feature = "sync")]
+        "send!" "([chan x])" [] => thread::send,
+        #[cfg(feature = "sync")]
+        "recv!" "([chan])" [] => thread::recv,
+        #[cfg(feature = "sync")]
+        "close!" "([chan])" [] => thread::close,
+        "time-ms" "([])" [] => |_| time(),
+        "meta" "([x])" [] => |a| a[0].get_meta(),
+        "with-meta" "([x m])" [] => |a| (a[0].clone()).set_meta(&a[1]),
+        "number?" "([x])" [] => |a| is_variant(&a[0], "number"),
+        "string?" "([x])" [] => |a| is_variant(&a[0], "string"),
+        "fn?" "([x])" [] => |a| is_variant(&a[0], "function"),
+        "macro?" "([x])" [] => |a| is_variant(&a[0], "macro"),
+        "type" "([x])" [] => |a| Ok(types::keyword(type_name(&a[0]))),
+        "profile-start" "([])" [] => |_| {
+            profile::start();
+            Ok(MalType::Nil)
+        },
+        "profile-stop" "([])" [] => |_| {
+            profile::stop();
+            Ok(MalType::Nil)
+        },
+        "profile-report" "([])" [] => |_| {
+            out_line(&profile::report());
+            Ok(MalType::Nil)
+        },
+        "arglists" "([f])" [] => introspect::arglists,
+        "arity-of" "([f])" [] => introspect::arity_of,
+    }
+}
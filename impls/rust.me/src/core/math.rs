@@ -0,0 +1,123 @@
+//! Arithmetic on integers, and equality and ordering of any values.
+
+use super::int;
+use crate::errors::MalErr;
+use crate::hash;
+use crate::types::MalType;
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+fn accumulate(args: Vec<MalType>, op: fn(i64, i64) -> Option<i64>) -> Result<MalType, MalErr> {
+    let first = int(&args[0])?;
+    args[1..]
+        .iter()
+        .try_fold(first, |acc, x| op(acc, int(x)?).ok_or_else(overflow))
+        .map(MalType::Int)
+}
+
+// checked_div can't tell a zero divisor from i64::MIN / -1, so report them separately
+fn quotient(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let first = int(&args[0])?;
+    args[1..]
+        .iter()
+        .try_fold(first, |acc, x| match int(x)? {
+            0 => Err(MalErr::ArithmeticError("Divide by zero")),
+            d => acc.checked_div(d).ok_or_else(overflow),
+        })
+        .map(MalType::Int)
+}
+
+fn compare(args: Vec<MalType>, op: fn(&MalType, &MalType) -> bool) -> Result<MalType, MalErr> {
+    Ok(MalType::Bool(op(&args[0], &args[1])))
+}
+
+/// Total ordering used by compare: nil sorts first, then values are ordered within their own
+/// kind (false before true, numbers, strings, keywords and symbols by name, and lists and vectors
+/// lexicographically, a prefix first). Mixing kinds is an error.
+fn order(a: &MalType, b: &MalType) -> Result<Ordering, MalErr> {
+    match (a, b) {
+        (MalType::Nil, MalType::Nil) => Ok(Ordering::Equal),
+        (MalType::Nil, _) => Ok(Ordering::Less),
+        (_, MalType::Nil) => Ok(Ordering::Greater),
+        (MalType::Bool(x), MalType::Bool(y)) => Ok(x.cmp(y)),
+        (MalType::Int(x), MalType::Int(y)) => Ok(x.cmp(y)),
+        (MalType::Str(x), MalType::Str(y)) => Ok(x.cmp(y)),
+        (MalType::Keyword(x), MalType::Keyword(y)) => Ok(x.cmp(y)),
+        (MalType::Symbol(x), MalType::Symbol(y)) => Ok(x.cmp(y)),
+        (
+            MalType::List(x, _) | MalType::Vector(x, _),
+            MalType::List(y, _) | MalType::Vector(y, _),
+        ) => {
+            for (x, y) in x.iter().zip(y.iter()) {
+                match order(x, y)? {
+                    Ordering::Equal => (),
+                    ord => return Ok(ord),
+                }
+            }
+            Ok(x.len().cmp(&y.len()))
+        }
+        _ => Err(MalErr::FunctionErr(format!(
+            "Cannot compare {} with {}",
+            a, b
+        ))),
+    }
+}
+
+fn compare_values(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    Ok(MalType::Int(order(&args[0], &args[1])? as i64))
+}
+
+fn overflow() -> MalErr {
+    MalErr::ArithmeticError("integer overflow")
+}
+
+fn unary(args: Vec<MalType>, op: fn(i64) -> Option<i64>) -> Result<MalType, MalErr> {
+    Ok(MalType::Int(op(int(&args[0])?).ok_or_else(overflow)?))
+}
+
+// rem takes the sign of the dividend, mod (floored) the sign of the divisor
+fn remainder(args: Vec<MalType>, floored: bool) -> Result<MalType, MalErr> {
+    let (n, d) = (int(&args[0])?, int(&args[1])?);
+    if d == 0 {
+        return Err(MalErr::ArithmeticError("Divide by zero"));
+    }
+    // wrapping only matters for i64::MIN by -1, whose remainder is 0
+    let r = n.wrapping_rem(d);
+    Ok(MalType::Int(if floored && r != 0 && (r < 0) != (d < 0) {
+        r + d
+    } else {
+        r
+    }))
+}
+
+fn extreme(args: Vec<MalType>, pick: fn(i64, i64) -> i64) -> Result<MalType, MalErr> {
+    let first = int(&args[0])?;
+    args[1..]
+        .iter()
+        .try_fold(first, |acc, x| Ok(pick(acc, int(x)?)))
+        .map(MalType::Int)
+}
+
+pub(super) fn register(ns: &mut HashMap<&'static str, MalType>) {
+    builtins! {ns;
+        "+" "([x y & more])" [Int] => |a| accumulate(a, i64::checked_add),
+        "-" "([x y & more])" [Int] => |a| accumulate(a, i64::checked_sub),
+        "*" "([x y & more])" [Int] => |a| accumulate(a, i64::checked_mul),
+        "/" "([x y & more])" [Int] => quotient,
+        "=" "([x y])" [] => |a| compare(a, |x, y| x == y),
+        "hash" "([x])" [] => |a| hash::hash(&a[0]).map(MalType::Int),
+        "<" "([x y])" [] => |a| compare(a, |x, y| x < y),
+        "<=" "([x y])" [] => |a| compare(a, |x, y| x <= y),
+        ">" "([x y])" [] => |a| compare(a, |x, y| x > y),
+        ">=" "([x y])" [] => |a| compare(a, |x, y| x >= y),
+        "compare" "([x y])" [] => compare_values,
+        "mod" "([n d])" [Int] => |a| remainder(a, true),
+        "rem" "([n d])" [Int] => |a| remainder(a, false),
+        "abs" "([x])" [Int] => |a| unary(a, i64::checked_abs),
+        "inc" "([x])" [Int] => |a| unary(a, |x| x.checked_add(1)),
+        "dec" "([x])" [Int] => |a| unary(a, |x| x.checked_sub(1)),
+        "min" "([x & more])" [Int] => |a| extreme(a, i64::min),
+        "max" "([x & more])" [Int] => |a| extreme(a, i64::max),
+    }
+}
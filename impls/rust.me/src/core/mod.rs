@@ -0,0 +1,225 @@
+//! The builtin functions of the core namespace.
+//!
+//! Each submodule registers a group of builtins with the builtins! macro, which checks every call
+//! against the arity and argument types the builtin is declared with. The builtins that touch the
+//! outside world can be compiled out for sandboxed or embedded builds: files with the "fs" feature,
+//! commands and the environment with "process", and the line editor with "readline".
+
+use crate::errors::MalErr;
+use crate::introspect;
+use crate::types::{func, MalType, Seq};
+
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+type Builtin = fn(Vec<MalType>) -> Result<MalType, MalErr>;
+
+/// Register builtins, each given as its name, its arglists as (arglists f) shows them, the
+/// types of its arguments (see check) and the function itself. Calls are checked against the
+/// arglists and types before the function runs, so it can index its arguments without checking
+/// how many there are.
+macro_rules! builtins {
+    ($ns:ident;
+     $($(#[$attr:meta])* $name:literal $arglists:literal [$($arg:ident),*] => $f:expr,)*) => {
+        $(
+            $(#[$attr])*
+            $ns.insert(
+                $name,
+                $crate::core::declare(
+                    |a| {
+                        const ARITY: (usize, Option<usize>) = $crate::core::arity($arglists);
+                        let f: $crate::core::Builtin = $f;
+                        $crate::core::check($name, ARITY, &[$($crate::core::Arg::$arg),*], &a)?;
+                        f(a)
+                    },
+                    $arglists,
+                ),
+            );
+        )*
+    };
+}
+
+mod collections;
+#[cfg(feature = "fs")]
+mod fs;
+mod io;
+mod lang;
+mod math;
+mod path;
+#[cfg(feature = "process")]
+mod process;
+mod strings;
+
+pub use io::redirect;
+pub(crate) use io::{err_line, out_line};
+#[cfg(feature = "process")]
+pub use process::set_argv;
+
+fn hash_map(hm: BTreeMap<MalType, MalType>) -> MalType {
+    MalType::HashMap(Rc::new(hm), Rc::new(MalType::Nil))
+}
+
+/// The elements of a list or vector, with nil standing for the empty sequence
+fn items(coll: &MalType) -> Result<&[MalType], MalErr> {
+    match coll {
+        MalType::List(l, _) | MalType::Vector(l, _) => Ok(l),
+        MalType::Nil => Ok(&[]),
+        _ => Err(MalErr::type_error("a list or vector", coll)),
+    }
+}
+
+/// Like items, but sharing them, for a part of them to be returned without copying it
+fn seq_items(coll: &MalType) -> Result<Seq, MalErr> {
+    match coll {
+        MalType::List(l, _) | MalType::Vector(l, _) => Ok(l.clone()),
+        MalType::Nil => Ok(Seq::default()),
+        _ => Err(MalErr::type_error("a list or vector", coll)),
+    }
+}
+
+fn int(value: &MalType) -> Result<i64, MalErr> {
+    match value {
+        MalType::Int(i) => Ok(*i),
+        _ => Err(MalErr::type_error("a number", value)),
+    }
+}
+
+fn text(value: &MalType) -> Result<&str, MalErr> {
+    match value {
+        MalType::Str(s) => Ok(s),
+        _ => Err(MalErr::type_error("a string", value)),
+    }
+}
+
+fn index(value: &MalType) -> Result<usize, MalErr> {
+    match value {
+        MalType::Int(i) if *i >= 0 => Ok(*i as usize),
+        _ => Err(MalErr::type_error("a non-negative index", value)),
+    }
+}
+
+/// What a builtin takes for one of its arguments
+#[derive(Clone, Copy)]
+enum Arg {
+    Any,
+    Int,
+    /// a string but not a keyword
+    Str,
+    /// anything that can be called: functions, and keywords, maps and vectors as lookups
+    Fn,
+    Atom,
+}
+
+impl Arg {
+    fn accepts(self, value: &MalType) -> bool {
+        match self {
+            Arg::Any => true,
+            Arg::Int => matches!(value, MalType::Int(_)),
+            Arg::Str => matches!(value, MalType::Str(_)),
+            Arg::Fn => {
+                matches!(
+                    value,
+                    MalType::Function(..)
+                        | MalType::MalFunction { .. }
+                        | MalType::HashMap(..)
+                        | MalType::Vector(..)
+                ) || value.is_keyword()
+            }
+            Arg::Atom => matches!(value, MalType::Atom(_)),
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Arg::Any => "anything",
+            Arg::Int => "a number",
+            Arg::Str => "a string",
+            Arg::Fn => "a function",
+            Arg::Atom => "an atom",
+        }
+    }
+}
+
+/// The fewest and most arguments (None for no limit) allowed by arglists such as
+/// "([x] [x y & more])". It runs at compile time, so checking a call costs no parsing.
+const fn arity(arglists: &str) -> (usize, Option<usize>) {
+    let bytes = arglists.as_bytes();
+    let (mut min, mut max) = (usize::MAX, Some(0));
+    let (mut i, mut params, mut variadic, mut in_word) = (0, 0, false, false);
+    while i < bytes.len() {
+        match bytes[i] {
+            b'[' => (params, variadic, in_word) = (0, false, false),
+            b']' => {
+                if params < min {
+                    min = params;
+                }
+                max = match max {
+                    Some(m) if !variadic && params > m => Some(params),
+                    Some(m) if !variadic => Some(m),
+                    _ => None,
+                };
+                in_word = false;
+            }
+            b' ' => in_word = false,
+            b'&' if !in_word => {
+                variadic = true;
+                in_word = true;
+            }
+            _ if !in_word && !variadic => {
+                params += 1;
+                in_word = true;
+            }
+            _ => in_word = true,
+        }
+        i += 1;
+    }
+    (min, max)
+}
+
+/// Check a call against the arity and argument types a builtin was registered with. Each
+/// argument is checked against the type in its position, or the last type for arguments past
+/// the end of the list.
+fn check(
+    name: &str,
+    (min, max): (usize, Option<usize>),
+    types: &[Arg],
+    args: &[MalType],
+) -> Result<(), MalErr> {
+    let n = args.len();
+    if n < min || max.is_some_and(|max| n > max) {
+        return Err(MalErr::arity(name, (min, max), n));
+    }
+    for (i, value) in args.iter().enumerate() {
+        let Some(arg) = types.get(i).or(types.last()) else {
+            break;
+        };
+        if !arg.accepts(value) {
+            return Err(MalErr::type_error(
+                format!("{} for argument {} of {}", arg.describe(), i + 1, name),
+                value,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Make a builtin, whose arglists (arglists f) reports
+fn declare(f: Builtin, arglists: &'static str) -> MalType {
+    introspect::declare(f, arglists);
+    func(f)
+}
+
+pub fn ns() -> HashMap<&'static str, MalType> {
+    let mut ns = HashMap::new();
+    math::register(&mut ns);
+    strings::register(&mut ns);
+    collections::register(&mut ns);
+    lang::register(&mut ns);
+    io::register(&mut ns);
+    path::register(&mut ns);
+    #[cfg(feature = "fs")]
+    fs::register(&mut ns);
+    #[cfg(feature = "process")]
+    process::register(&mut ns);
+    ns
+}
@@ -0,0 +1,49 @@
+//! Path manipulation, which works on strings alone and never touches the filesystem.
+
+use super::text;
+use crate::errors::MalErr;
+use crate::types::MalType;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub(super) fn path_arg(args: &[MalType]) -> Result<&str, MalErr> {
+    text(&args[0])
+}
+
+pub(super) fn path_str(path: &Path) -> MalType {
+    MalType::Str(path.to_string_lossy().into_owned())
+}
+
+// A part of a path, or nil when it has none
+fn path_part(
+    args: Vec<MalType>,
+    part: fn(&Path) -> Option<&std::ffi::OsStr>,
+) -> Result<MalType, MalErr> {
+    Ok(part(Path::new(path_arg(&args)?))
+        .map(|p| path_str(Path::new(p)))
+        .unwrap_or(MalType::Nil))
+}
+
+fn path_join(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let mut path = PathBuf::new();
+    for part in &args {
+        path.push(text(part)?);
+    }
+    Ok(path_str(&path))
+}
+
+pub(super) fn register(ns: &mut HashMap<&'static str, MalType>) {
+    builtins! {ns;
+        "path-join" "([& parts])" [Str] => path_join,
+        "path-parent" "([path])" [Str] => |a| {
+            path_part(a, |p| {
+                p.parent()
+                    .map(Path::as_os_str)
+                    .filter(|parent| !parent.is_empty())
+            })
+        },
+        "path-filename" "([path])" [Str] => |a| path_part(a, Path::file_name),
+        "path-extension" "([path])" [Str] => |a| path_part(a, Path::extension),
+    }
+}
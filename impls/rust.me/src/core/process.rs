@@ -0,0 +1,172 @@
+//! Running commands and the process environment. Only built with the "process" feature.
+
+use super::{hash_map, text};
+use crate::errors::MalErr;
+use crate::list;
+use crate::types::{self, MalType};
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// The name of a keyword, or a string as it is
+fn name(value: &MalType) -> Result<String, MalErr> {
+    match value {
+        MalType::Keyword(k) => Ok(k.to_string()),
+        MalType::Str(s) => Ok(s.to_string()),
+        _ => Err(MalErr::type_error("a string or keyword", value)),
+    }
+}
+
+// (sh cmd & args-then-opts): the command and its arguments are strings, followed by options
+// :in (a string fed to stdin), :env (a map of variables added to the inherited environment)
+// and :dir (the working directory)
+fn sh(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let split = args
+        .iter()
+        .position(|a| a.is_keyword())
+        .unwrap_or(args.len());
+    let (cmd, opts) = args.split_at(split);
+    let (program, cmd_args) = match cmd.split_first() {
+        Some((program, cmd_args)) => (text(program)?, cmd_args),
+        None => return Err(MalErr::expected("a command")),
+    };
+    if opts.len() % 2 != 0 {
+        return Err(MalErr::expected("a value for every sh option"));
+    }
+    let mut command = Command::new(program);
+    for a in cmd_args {
+        command.arg(text(a)?);
+    }
+    let mut input = None;
+    for opt in opts.chunks(2) {
+        match (name(&opt[0])?.as_str(), &opt[1]) {
+            ("in", v) => input = Some(v.pr_str(false)),
+            ("env", MalType::HashMap(vars, _)) => {
+                for (k, v) in vars.iter() {
+                    command.env(name(k)?, v.pr_str(false));
+                }
+            }
+            ("dir", v) => {
+                command.current_dir(text(v)?);
+            }
+            (_, _) => {
+                return Err(MalErr::FunctionErr(format!(
+                    "Invalid sh option {} {}",
+                    opt[0], opt[1]
+                )))
+            }
+        }
+    }
+    let io_err = |e| MalErr::IoError(Some(program.to_string()), e);
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(io_err)?;
+    // fed from another thread so a child filling its stdout pipe can't deadlock us
+    let writer = match (input, child.stdin.take()) {
+        (Some(input), Some(mut stdin)) => Some(std::thread::spawn(move || {
+            stdin.write_all(input.as_bytes())
+        })),
+        _ => None,
+    };
+    let output = child.wait_with_output().map_err(io_err)?;
+    if let Some(writer) = writer {
+        // a child that exits without reading its input is not an error
+        let _ = writer.join();
+    }
+    let mut result = BTreeMap::new();
+    result.insert(
+        types::keyword("exit"),
+        output
+            .status
+            .code()
+            .map(|c| MalType::Int(c as i64))
+            .unwrap_or(MalType::Nil),
+    );
+    result.insert(
+        types::keyword("out"),
+        MalType::Str(String::from_utf8_lossy(&output.stdout).into_owned()),
+    );
+    result.insert(
+        types::keyword("err"),
+        MalType::Str(String::from_utf8_lossy(&output.stderr).into_owned()),
+    );
+    Ok(hash_map(result))
+}
+
+// Names and values that aren't UTF-8 are read with U+FFFD in place of what isn't, rather than
+// making the whole environment unreadable
+fn lossy(s: &std::ffi::OsStr) -> String {
+    s.to_string_lossy().into_owned()
+}
+
+// (getenv name) is the variable's value or nil; (getenv) is a map of the whole environment
+fn getenv(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match args.first() {
+        None => Ok(hash_map(
+            std::env::vars_os()
+                .map(|(k, v)| (MalType::Str(lossy(&k)), MalType::Str(lossy(&v))))
+                .collect(),
+        )),
+        Some(var) => Ok(std::env::var_os(name(var)?)
+            .map(|v| MalType::Str(lossy(&v)))
+            .unwrap_or(MalType::Nil)),
+    }
+}
+
+// Setting a variable to nil removes it. Names and values that std::env would panic on, an empty
+// name or one with '=', or either with a NUL, are refused instead.
+fn setenv(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let var = name(&args[0])?;
+    if var.is_empty() || var.contains(['=', '\0']) {
+        return Err(MalErr::FunctionErr(format!(
+            "Invalid environment variable name: {:?}",
+            var
+        )));
+    }
+    match &args[1] {
+        MalType::Nil => std::env::remove_var(var),
+        value => {
+            let value = value.pr_str(false);
+            if value.contains('\0') {
+                return Err(MalErr::FunctionErr(format!(
+                    "Invalid value for environment variable {}: it contains a NUL",
+                    var
+                )));
+            }
+            std::env::set_var(var, value)
+        }
+    }
+    Ok(MalType::Nil)
+}
+
+thread_local! {
+    static ARGV: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Set what argv returns: the command line arguments after the file being run
+pub fn set_argv(args: &[String]) {
+    ARGV.with(|argv| *argv.borrow_mut() = args.to_vec());
+}
+
+fn argv(_: Vec<MalType>) -> Result<MalType, MalErr> {
+    let args = ARGV.with(|argv| argv.borrow().iter().cloned().map(MalType::Str).collect());
+    Ok(list!(args))
+}
+
+pub(super) fn register(ns: &mut HashMap<&'static str, MalType>) {
+    builtins! {ns;
+        "sh" "([cmd & args-and-opts])" [Str, Any] => sh,
+        "getenv" "([] [name])" [] => getenv,
+        "setenv" "([name value])" [] => setenv,
+        "argv" "([])" [] => argv,
+    }
+}
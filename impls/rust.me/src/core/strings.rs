@@ -0,0 +1,176 @@
+//! Printing to strings, symbols and keywords, and text manipulation.
+
+use super::{index, text};
+use crate::errors::MalErr;
+use crate::format;
+use crate::printer::pr_list;
+use crate::re;
+use crate::reader::read_str;
+use crate::types::{self, MalType};
+use crate::vector;
+
+use std::collections::HashMap;
+
+fn make_string(args: Vec<MalType>, print_readably: bool, join: &str) -> Result<MalType, MalErr> {
+    Ok(MalType::Str(pr_list(&args, "", "", print_readably, join)))
+}
+
+pub(super) fn read_string(
+    args: Vec<MalType>,
+    reader: fn(String) -> Result<MalType, MalErr>,
+) -> Result<MalType, MalErr> {
+    // a read error's span is into s, which whoever reports the error doesn't have
+    reader(text(&args[0])?.to_string()).map_err(|e| match e {
+        MalErr::ReadErr(message, _) => MalErr::ReadErr(message, None),
+        e => e,
+    })
+}
+
+// (symbol name) or (symbol ns name), where a nil ns makes a plain symbol
+fn symbol(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match args.as_slice() {
+        [MalType::Nil, name] => Ok(types::symbol(text(name)?)),
+        [ns, name] => Ok(types::symbol(&format!("{}/{}", text(ns)?, text(name)?))),
+        _ => Ok(types::symbol(text(&args[0])?)),
+    }
+}
+
+// The namespace and name of an identifier like ns/name. There is no namespace without a /
+// between two non-empty parts, so / on its own is just a name.
+fn split_ident(s: &str) -> (Option<&str>, &str) {
+    match s.find('/') {
+        Some(i) if i > 0 && i + 1 < s.len() => (Some(&s[..i]), &s[i + 1..]),
+        _ => (None, s),
+    }
+}
+
+// (name x) or (namespace x) of a symbol or keyword. A string is its own name and has no
+// namespace.
+fn ident_part(args: Vec<MalType>, namespace: bool) -> Result<MalType, MalErr> {
+    let (ns, name) = match &args[0] {
+        MalType::Keyword(k) => split_ident(k),
+        MalType::Str(s) => (None, s.as_str()),
+        MalType::Symbol(s) => split_ident(s),
+        x => return Err(MalErr::type_error("a symbol, keyword or string", x)),
+    };
+    Ok(match (namespace, ns) {
+        (true, Some(ns)) => MalType::Str(ns.to_string()),
+        (true, None) => MalType::Nil,
+        (false, _) => MalType::Str(name.to_string()),
+    })
+}
+
+fn keyword(value: &MalType) -> Result<MalType, MalErr> {
+    match value {
+        MalType::Keyword(_) => Ok(value.clone()),
+        MalType::Str(s) => Ok(types::keyword(s)),
+        _ => Err(MalErr::expected("a string")),
+    }
+}
+
+fn out_of_range(s: &str, char_index: usize) -> MalErr {
+    MalErr::IndexOutOfBounds {
+        index: char_index as i64,
+        of: "string",
+        len: s.chars().count(),
+    }
+}
+
+// Byte offset of the character at char_index, allowing the position just past the end
+fn byte_offset(s: &str, char_index: usize) -> Result<usize, MalErr> {
+    s.char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(s.len()))
+        .nth(char_index)
+        .ok_or_else(|| out_of_range(s, char_index))
+}
+
+fn subs(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (s, start) = (text(&args[0])?, index(&args[1])?);
+    let end = args.get(2).map(index).transpose()?;
+    let from = byte_offset(s, start)?;
+    let to = match end {
+        Some(end) if end < start => return Err(out_of_range(s, end)),
+        Some(end) => byte_offset(s, end)?,
+        None => s.len(),
+    };
+    Ok(MalType::Str(s[from..to].to_string()))
+}
+
+fn split(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (s, sep) = (text(&args[0])?, text(&args[1])?);
+    let parts: Vec<MalType> = if sep.is_empty() {
+        s.chars().map(|c| MalType::Str(c.to_string())).collect()
+    } else {
+        s.split(sep).map(|p| MalType::Str(p.to_string())).collect()
+    };
+    Ok(vector!(parts))
+}
+
+fn join(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (sep, coll) = match args.as_slice() {
+        [sep, coll] => (text(sep)?, coll),
+        _ => ("", &args[0]),
+    };
+    match coll {
+        MalType::List(l, _) | MalType::Vector(l, _) => {
+            Ok(MalType::Str(pr_list(l, "", "", false, sep)))
+        }
+        MalType::Nil => Ok(MalType::Str(String::new())),
+        _ => Err(MalErr::type_error("a list or vector", coll)),
+    }
+}
+
+fn replace(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (s, from, to) = (text(&args[0])?, text(&args[1])?, text(&args[2])?);
+    Ok(MalType::Str(s.replace(from, to)))
+}
+
+fn map_text(args: Vec<MalType>, op: fn(&str) -> String) -> Result<MalType, MalErr> {
+    Ok(MalType::Str(op(text(&args[0])?)))
+}
+
+fn test_text(args: Vec<MalType>, op: fn(&str, &str) -> bool) -> Result<MalType, MalErr> {
+    Ok(MalType::Bool(op(text(&args[0])?, text(&args[1])?)))
+}
+
+fn index_of(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (s, sub) = (text(&args[0])?, text(&args[1])?);
+    let start = args.get(2).map(index).transpose()?.unwrap_or(0);
+    let from = match byte_offset(s, start) {
+        Ok(from) => from,
+        Err(_) => return Ok(MalType::Nil),
+    };
+    Ok(match s[from..].find(sub) {
+        Some(i) => MalType::Int((start + s[from..from + i].chars().count()) as i64),
+        None => MalType::Nil,
+    })
+}
+
+pub(super) fn register(ns: &mut HashMap<&'static str, MalType>) {
+    builtins! {ns;
+        "pr-str" "([& xs])" [] => |a| make_string(a, true, " "),
+        "str" "([& xs])" [] => |a| make_string(a, false, ""),
+        "format" "([fmt & args])" [Str, Any] => format::format,
+        "read-string" "([s])" [Str] => |a| read_string(a, read_str),
+        "symbol" "([name] [ns name])" [] => symbol,
+        "keyword" "([name])" [] => |a| keyword(&a[0]),
+        "name" "([x])" [] => |a| ident_part(a, false),
+        "namespace" "([x])" [] => |a| ident_part(a, true),
+        "re-pattern" "([s])" [] => re::pattern,
+        "re-find" "([re s])" [] => re::find,
+        "re-matches" "([re s])" [] => re::matches,
+        "re-seq" "([re s])" [] => re::seq,
+        "re-replace" "([re s replacement])" [] => re::replace,
+        "subs" "([s start] [s start end])" [Str, Int] => subs,
+        "str/split" "([s sep])" [Str] => split,
+        "str/join" "([coll] [sep coll])" [] => join,
+        "str/replace" "([s match replacement])" [Str] => replace,
+        "trim" "([s])" [Str] => |a| map_text(a, |s| s.trim().to_string()),
+        "upper-case" "([s])" [Str] => |a| map_text(a, str::to_uppercase),
+        "lower-case" "([s])" [Str] => |a| map_text(a, str::to_lowercase),
+        "starts-with?" "([s prefix])" [Str] => |a| test_text(a, |s, p| s.starts_with(p)),
+        "ends-with?" "([s suffix])" [Str] => |a| test_text(a, |s, p| s.ends_with(p)),
+        "index-of" "([s sub] [s sub start])" [Str, Str, Int] => index_of,
+    }
+}
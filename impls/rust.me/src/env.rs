@@ -1,16 +1,103 @@
 use crate::errors::MalErr;
 use crate::list;
-use crate::types::MalType;
+use crate::types::{MalType, Sym};
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
 use std::rc::Rc;
 
-#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
+/// How many bindings an environment keeps in a list before moving them into a hash map
+const SMALL: usize = 8;
+
+/// The bindings of one environment. Most are a function call's few parameters, which are found
+/// quickest by scanning a short list, and only the larger ones, like the REPL's, are hashed.
+#[derive(Clone, Debug)]
+enum Frame {
+    Small(Vec<(Sym, MalType)>),
+    Large(HashMap<Sym, MalType>),
+}
+
+impl Frame {
+    fn get(&self, symbol: &Sym) -> Option<&MalType> {
+        match self {
+            Frame::Small(pairs) => pairs.iter().find(|(k, _)| k == symbol).map(|(_, v)| v),
+            Frame::Large(map) => map.get(symbol),
+        }
+    }
+
+    fn insert(&mut self, symbol: Sym, value: MalType) -> Option<MalType> {
+        match self {
+            Frame::Small(pairs) => {
+                if let Some((_, v)) = pairs.iter_mut().find(|(k, _)| *k == symbol) {
+                    return Some(std::mem::replace(v, value));
+                }
+                if pairs.len() < SMALL {
+                    pairs.push((symbol, value));
+                } else {
+                    let mut map: HashMap<Sym, MalType> = pairs.drain(..).collect();
+                    map.insert(symbol, value);
+                    *self = Frame::Large(map);
+                }
+                None
+            }
+            Frame::Large(map) => map.insert(symbol, value),
+        }
+    }
+
+    fn remove(&mut self, symbol: &Sym) -> Option<MalType> {
+        match self {
+            Frame::Small(pairs) => {
+                let i = pairs.iter().position(|(k, _)| k == symbol)?;
+                Some(pairs.swap_remove(i).1)
+            }
+            Frame::Large(map) => map.remove(symbol),
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&Sym, &MalType)> + '_> {
+        match self {
+            Frame::Small(pairs) => Box::new(pairs.iter().map(|(k, v)| (k, v))),
+            Frame::Large(map) => Box::new(map.iter()),
+        }
+    }
+
+    /// the bindings sorted by name, whichever way they are stored
+    fn sorted(&self) -> Vec<(&Sym, &MalType)> {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Env {
-    data: RefCell<BTreeMap<String, MalType>>,
+    data: RefCell<Frame>,
     pub outer: Option<Rc<Env>>,
 }
 
+// Environments are compared by identity, like builtins, so that closures can be map keys.
+// Comparing their bindings would never finish for a closure bound in the environment it closes
+// over, as every recursive function is.
+impl PartialEq for Env {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for Env {}
+
+impl PartialOrd for Env {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Env {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self as *const Env).cmp(&(other as *const Env))
+    }
+}
+
 impl Default for Env {
     fn default() -> Self {
         Self::new(None)
@@ -20,56 +107,144 @@ impl Default for Env {
 impl Env {
     pub fn new(outer: Option<Rc<Env>>) -> Self {
         Self {
-            data: RefCell::new(BTreeMap::new()),
-            outer: outer,
+            data: RefCell::new(Frame::Small(Vec::new())),
+            outer,
         }
     }
 
     /// takes a symbol key and a mal value and adds to the data structure
-    pub fn set(&self, symbol: String, value: MalType) -> Option<MalType> {
-        self.data.borrow_mut().insert(symbol, value)
-    }
-
-    /// takes a symbol key and if the current environment contains that key then return the environment.
-    /// If no key is found and outer is not nil then call find (recurse) on the outer environment.
-    fn find(&self, symbol: &str) -> Option<Self> {
-        if self.data.borrow().contains_key(symbol) {
-            return Some(self.clone());
-        } else {
-            match &self.outer {
-                Some(env) => env.find(symbol),
-                None => None,
-            }
+    pub fn set(&self, symbol: impl Into<Sym>, value: MalType) -> Option<MalType> {
+        self.data.borrow_mut().insert(symbol.into(), value)
+    }
+
+    /// removes a symbol from this environment (not from outer ones), returning its value if it was set
+    pub fn remove(&self, symbol: &Sym) -> Option<MalType> {
+        self.data.borrow_mut().remove(symbol)
+    }
+
+    /// takes a symbol key and looks it up in this environment, then in each outer one in turn,
+    /// returning the first value found. If no key is found up the outer chain, then throws/raises
+    /// a "not found" error.
+    pub fn get(&self, symbol: &Sym) -> Result<MalType, MalErr> {
+        self.lookup(symbol)
+            .ok_or_else(|| MalErr::SymbolNotFound(symbol.to_string(), self.suggestions(symbol)))
+    }
+
+    /// up to three symbols in scope that are a small edit away from symbol, closest first, to
+    /// suggest when symbol isn't bound
+    fn suggestions(&self, symbol: &str) -> Vec<String> {
+        let len = symbol.chars().count();
+        let limit = (len / 3).max(1);
+        let mut close: Vec<(usize, String)> = self
+            .symbols()
+            .into_iter()
+            // no name whose length is further off than the limit can be within it
+            .filter(|s| s.chars().count().abs_diff(len) <= limit)
+            .map(|s| (edit_distance(symbol, &s), s))
+            // replacing every character isn't a near miss, however short the name is
+            .filter(|(d, _)| *d <= limit && *d < len)
+            .collect();
+        close.sort();
+        close.into_iter().take(3).map(|(_, s)| s).collect()
+    }
+
+    /// like get, but returns None instead of building an error when the symbol isn't bound
+    pub fn lookup(&self, symbol: &Sym) -> Option<MalType> {
+        match self.data.borrow().get(symbol) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref().and_then(|env| env.lookup(symbol)),
         }
     }
 
-    /// takes a symbol key and uses the find method to locate the environment with the key, then returns the matching value.
-    /// If no key is found up the outer chain, then throws/raises a "not found" error.
-    pub fn get(&self, symbol: &str) -> Result<MalType, MalErr> {
-        match self.find(symbol) {
-            Some(env) => Ok(env.data.borrow().get(symbol).unwrap().clone()), // unwrap() is safe because find() checks for existence of key
-            None => Err(MalErr::SymbolNotFound(symbol.to_string())),
+    /// the symbols bound directly in this environment (not in outer ones) and their values, by name
+    #[cfg_attr(not(feature = "sync"), allow(dead_code))]
+    pub fn bindings(&self) -> Vec<(String, MalType)> {
+        self.data
+            .borrow()
+            .sorted()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    /// every symbol bound in this environment or an outer one, sorted and without repeats
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols = BTreeSet::new();
+        let mut env = Some(self);
+        while let Some(e) = env {
+            symbols.extend(e.data.borrow().iter().map(|(k, _)| k.to_string()));
+            env = e.outer.as_deref();
         }
+        symbols.into_iter().collect()
     }
 
     /// Bind (set) each element (symbol) of the binds list to the respective element of the exprs list.
-    pub fn bind(&self, binds: MalType, exprs: Vec<MalType>) -> Result<Self, MalErr> {
+    pub fn bind(&self, binds: MalType, exprs: Vec<MalType>) -> Result<(), MalErr> {
         match binds {
-            MalType::List(b, _) | MalType::Vector(b, _) => {
-                for (i, bind) in b.iter().enumerate() {
-                    match bind {
-                        MalType::Symbol(s) if s == "&" => {
-                            self.set(b[i + 1].to_string(), list!(exprs[i..].to_vec()));
-                            break;
-                        }
-                        _ => {
-                            self.set(bind.to_string(), exprs[i].clone());
+            MalType::List(ref b, _) | MalType::Vector(ref b, _) => {
+                let variadic = b
+                    .iter()
+                    .position(|bind| matches!(bind, MalType::Symbol(s) if s == "&"));
+                let required = variadic.unwrap_or(b.len());
+                if exprs.len() < required {
+                    let max = if variadic.is_some() {
+                        None
+                    } else {
+                        Some(required)
+                    };
+                    let name = format!("(fn* {})", binds);
+                    return Err(MalErr::arity(&name, (required, max), exprs.len()));
+                }
+                for (bind, expr) in b[..required].iter().zip(&exprs) {
+                    self.set(name(bind), expr.clone());
+                }
+                if let Some(i) = variadic {
+                    match b.get(i + 1) {
+                        Some(rest) => self.set(name(rest), list!(exprs[i..].to_vec())),
+                        None => {
+                            return Err(MalErr::MalFunctionErr(
+                                "& must be followed by a parameter".to_string(),
+                            ))
                         }
-                    }
+                    };
                 }
-                Ok(self.clone())
+                Ok(())
             }
             _ => Err(MalErr::Generic("binds is not a list or vector".to_string())),
         }
     }
 }
+
+/// The name a parameter binds, which is the symbol's own unless it is some other form
+fn name(bind: &MalType) -> Sym {
+    match bind {
+        MalType::Symbol(s) => s.clone(),
+        other => Sym::from(other.to_string()),
+    }
+}
+
+/// How many characters have to be inserted, deleted or replaced, or pairs of neighbouring ones
+/// swapped, to turn a into b (the optimal string alignment distance)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // d[i][j] is the distance from the first i characters of a to the first j of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
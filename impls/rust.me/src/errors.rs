@@ -1,33 +1,423 @@
-use crate::types::MalType;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::rc::Rc;
 
-#[derive(Debug)]
+use crate::syntax::line_col;
+use crate::types::{keyword, MalType};
+
+/// The ways evaluating mal can fail. Each kind of error has a short code that stays the same
+/// whatever its message says, which a catch* clause can select errors with, as in
+/// (catch* :arity-error e ...):
+///
+/// | code               | error                                              |
+/// |--------------------|----------------------------------------------------|
+/// | `read-error`       | ReadErr                                            |
+/// | `undefined-symbol` | SymbolNotFound                                     |
+/// | `syntax-error`     | InvalidLet, InvalidDo, InvalidForm, MalFunctionErr |
+/// | `arity-error`      | ArityError                                         |
+/// | `type-error`       | TypeError                                          |
+/// | `index-error`      | IndexOutOfBounds                                   |
+/// | `arithmetic-error` | ArithmeticError                                    |
+/// | `function-error`   | FunctionErr                                        |
+/// | `thrown`           | Throw                                              |
+/// | `no-match`         | NoMatch                                            |
+/// | `error`            | Generic                                            |
+/// | `io/error`         | IoError                                            |
+/// | `interrupted`      | Interrupted                                        |
+///
+/// An error with a location has the code of the error it wraps.
+#[derive(Debug, thiserror::Error)]
 pub enum MalErr {
-    // read
-    ReadErr(String),
+    // read, with where in the text read the token at fault is, if there is one
+    #[error("Read error: {0}")]
+    ReadErr(String, Option<Range<usize>>),
     // env
-    SymbolNotFound(String),
+    // with the names in scope closest to it, for a "did you mean"
+    #[error("'{}' not found{}", .0, did_you_mean(.1))]
+    SymbolNotFound(String, Vec<String>),
     // eval
+    #[error("Invalid let* construction: {0}")]
     InvalidLet(String),
+    #[error("Invalid do construction: {0}")]
     InvalidDo(String),
-    FunctionErr(String),
+    #[error("Invalid {0} construction: {1}")]
+    InvalidForm(String, String),
+    #[error("Invalid fn* construction: {0}")]
     MalFunctionErr(String),
+    // a builtin called with a number of arguments it doesn't take
+    #[error(
+        "Does not compute: Wrong number of arguments to {name}: expected {expected}, got {got}"
+    )]
+    ArityError {
+        name: String,
+        /// how many it takes, such as "2 or 3 arguments"
+        expected: String,
+        got: usize,
+    },
+    // a builtin given what it can't work with
+    #[error("Does not compute: Expected {expected}{}", got(.found))]
+    TypeError {
+        /// what it takes, such as "a string"
+        expected: String,
+        /// what it was given instead, printed, if that is worth saying
+        found: Option<String>,
+    },
+    #[error("Does not compute: Index {index} out of range for a {of} of {len}")]
+    IndexOutOfBounds {
+        index: i64,
+        /// what was indexed, such as "list" or "string"
+        of: &'static str,
+        len: usize,
+    },
+    #[error("Does not compute: {0}")]
+    ArithmeticError(&'static str),
+    // any other failure of a builtin
+    #[error("Does not compute: {0}")]
+    FunctionErr(String),
+    #[error("{}", .0.pr_str(false))]
     Throw(MalType),
+    // a case, condp or match with no clause for the value and no default
+    #[error("No matching clause: {0}")]
+    NoMatch(MalType),
+    #[error("Error: {0}")]
     Generic(String),
+    // a failed operation on a path, or on something with no path such as stdout
+    #[error("{}{}", on_path(.0), .1)]
+    IoError(Option<String>, #[source] std::io::Error),
+    #[error("Evaluation interrupted")]
+    Interrupted,
+    // an error from the form at the given place in a loaded file
+    #[error("{0}: {1}")]
+    At(Location, #[source] Box<MalErr>),
+    // not an error: a builtin asking its caller to apply a function in tail position
+    #[error("Unresolved tail call to {0}")]
+    TailCall(Box<MalType>, Vec<MalType>),
+}
+
+/// For an I/O error with no path to tell; one on a file is made with its path instead
+impl From<std::io::Error> for MalErr {
+    fn from(e: std::io::Error) -> Self {
+        MalErr::IoError(None, e)
+    }
 }
 
-impl std::fmt::Display for MalErr {
+/// Text that should have been a number, which only reading it can give
+impl From<std::num::ParseIntError> for MalErr {
+    fn from(e: std::num::ParseIntError) -> Self {
+        MalErr::ReadErr(format!("Invalid number: {}", e), None)
+    }
+}
+
+impl From<regex::Error> for MalErr {
+    fn from(e: regex::Error) -> Self {
+        MalErr::FunctionErr(format!("Invalid regex: {}", e))
+    }
+}
+
+fn on_path(path: &Option<String>) -> String {
+    match path {
+        Some(path) => format!("{}: ", path),
+        None => String::new(),
+    }
+}
+
+fn did_you_mean(names: &[String]) -> String {
+    let quoted: Vec<String> = names.iter().map(|n| format!("'{}'", n)).collect();
+    match quoted.as_slice() {
+        [] => String::new(),
+        [only] => format!("; did you mean {}?", only),
+        [init @ .., last] => format!("; did you mean {} or {}?", init.join(", "), last),
+    }
+}
+
+fn got(found: &Option<String>) -> String {
+    match found {
+        Some(found) => format!(", got {}", found),
+        None => String::new(),
+    }
+}
+
+/// Where a form starts in a loaded file, with the line and column counting from 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: Rc<str>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Location {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl MalErr {
+    /// A TypeError for found, which isn't what was expected
+    pub fn type_error(expected: impl Into<String>, found: &MalType) -> MalErr {
+        MalErr::TypeError {
+            expected: expected.into(),
+            found: Some(found.to_string()),
+        }
+    }
+
+    /// A TypeError for arguments that aren't what was expected, without saying what they were
+    pub fn expected(expected: impl Into<String>) -> MalErr {
+        MalErr::TypeError {
+            expected: expected.into(),
+            found: None,
+        }
+    }
+
+    /// An ArityError for name, which takes from min to max arguments (None for no limit), being
+    /// called with got of them
+    pub fn arity(name: &str, (min, max): (usize, Option<usize>), got: usize) -> MalErr {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        let expected = match max {
+            None => format!("at least {} argument{}", min, plural(min)),
+            Some(max) if max == min => format!("{} argument{}", min, plural(min)),
+            Some(max) if max == min + 1 => format!("{} or {} arguments", min, max),
+            Some(max) => format!("{} to {} arguments", min, max),
+        };
+        MalErr::ArityError {
+            name: name.to_string(),
+            expected,
+            got,
+        }
+    }
+
+    /// The short code for the kind of error this is, which stays the same whatever the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            MalErr::ReadErr(..) => "read-error",
+            MalErr::SymbolNotFound(..) => "undefined-symbol",
+            MalErr::InvalidLet(_)
+            | MalErr::InvalidDo(_)
+            | MalErr::InvalidForm(..)
+            | MalErr::MalFunctionErr(_) => "syntax-error",
+            MalErr::ArityError { .. } => "arity-error",
+            MalErr::TypeError { .. } => "type-error",
+            MalErr::IndexOutOfBounds { .. } => "index-error",
+            MalErr::ArithmeticError(_) => "arithmetic-error",
+            MalErr::FunctionErr(_) => "function-error",
+            MalErr::Throw(_) => "thrown",
+            MalErr::NoMatch(_) => "no-match",
+            MalErr::Generic(_) => "error",
+            MalErr::IoError(..) => "io/error",
+            MalErr::Interrupted => "interrupted",
+            MalErr::At(_, e) => e.code(),
+            MalErr::TailCall(..) => "tail-call",
+        }
+    }
+
+    /// The error as shown to people, with a thrown value printed readably, so that a string thrown
+    /// keeps its quotes and can be told from a message
+    pub fn message(&self) -> String {
+        match self {
+            MalErr::Throw(value) => value.pr_str(true),
+            MalErr::At(at, e) => format!("{}: {}", at, e.message()),
+            e => e.to_string(),
+        }
+    }
+
+    /// The value a catch* clause binds: whatever was thrown, a {:type :io/error :path :message}
+    /// map for I/O errors, with a nil :path if there was none, a {:type :no-match :value} map
+    /// when no clause matched, and the message for anything else. Errors from a loaded file
+    /// are caught as the error itself, without its location.
+    pub fn to_value(&self) -> MalType {
+        match self {
+            MalErr::Throw(mt) => mt.clone(),
+            MalErr::At(_, e) => e.to_value(),
+            // the suggestions are for whoever reads the message, not for code that catches it
+            MalErr::SymbolNotFound(name, _) => MalType::Str(format!("'{}' not found", name)),
+            MalErr::IoError(path, e) => MalType::HashMap(
+                Rc::new(BTreeMap::from([
+                    (keyword("type"), keyword("io/error")),
+                    (
+                        keyword("path"),
+                        path.clone().map_or(MalType::Nil, MalType::Str),
+                    ),
+                    (keyword("message"), MalType::Str(e.to_string())),
+                ])),
+                Rc::new(MalType::Nil),
+            ),
+            MalErr::NoMatch(value) => MalType::HashMap(
+                Rc::new(BTreeMap::from([
+                    (keyword("type"), keyword("no-match")),
+                    (keyword("value"), value.clone()),
+                ])),
+                Rc::new(MalType::Nil),
+            ),
+            _ => MalType::Str(self.to_string()),
+        }
+    }
+
+    /// The error as having happened at the form at, unless it already has a place of its own or
+    /// is one that isn't reported where it happened
+    pub fn at(self, at: Location) -> MalErr {
         match self {
-            MalErr::ReadErr(message) => write!(f, "Read error: {}", message),
-            MalErr::SymbolNotFound(symbol) => write!(f, "'{}' not found", symbol),
-            MalErr::InvalidLet(message) => write!(f, "Invalid let* construction: {}", message),
-            MalErr::InvalidDo(message) => write!(f, "Invalid do construction: {}", message),
-            MalErr::FunctionErr(message) => write!(f, "Does not compute: {}", message),
-            MalErr::MalFunctionErr(message) => write!(f, "Invalid fn* construction: {}", message),
-            MalErr::Throw(mt) => write!(f, "{}", mt.pr_str(false)),
-            MalErr::Generic(message) => write!(f, "Error: {}", message),
+            MalErr::At(..) | MalErr::Interrupted | MalErr::TailCall(..) => self,
+            e => MalErr::At(at, Box::new(e)),
         }
     }
+
+    /// The error as a structured record for tools, with the location it happened at, if it has
+    /// one, as its span rather than part of its message
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let (location, e) = match self {
+            MalErr::At(at, e) => (Some(at.clone()), &**e),
+            e => (None, e),
+        };
+        let bytes = match e {
+            MalErr::ReadErr(_, span) => span.clone(),
+            _ => None,
+        };
+        let (message, notes) = match e {
+            MalErr::SymbolNotFound(name, suggestions) => (
+                format!("'{}' not found", name),
+                suggestions
+                    .iter()
+                    .map(|s| format!("did you mean '{}'?", s))
+                    .collect(),
+            ),
+            e => (e.to_string(), Vec::new()),
+        };
+        Diagnostic {
+            severity: Severity::Error,
+            code: self.code(),
+            message,
+            span: (location.is_some() || bytes.is_some()).then_some(Span { location, bytes }),
+            notes,
+        }
+    }
+
+    /// Where a read error happened, shown as the line of source it is on with a caret under the
+    /// token at fault, or None if it isn't a read error or it has no span. source is the text
+    /// that was read; for an error from a loaded file, the file is read again instead.
+    pub fn diagnostic(&self, source: Option<&str>) -> Option<String> {
+        match self {
+            MalErr::ReadErr(_, Some(span)) => Some(render(source?, span.clone(), None)),
+            #[cfg(feature = "fs")]
+            MalErr::At(at, e) => match &**e {
+                MalErr::ReadErr(_, Some(span)) => {
+                    let source = std::fs::read_to_string(&*at.file).ok()?;
+                    Some(render(&source, span.clone(), Some(at)))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
 }
 
-impl std::error::Error for MalErr {}
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// Where a diagnostic points: the place in a loaded file of the form at fault, and for a read
+/// error, the bytes of the token at fault in the text that was read
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub location: Option<Location>,
+    pub bytes: Option<Range<usize>>,
+}
+
+/// An error as a record for tools such as editors and test harnesses to read, rather than a
+/// message for people. The message leaves out the location and the notes, which have fields of
+/// their own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    /// The diagnostic as a JSON object on one line, with null for a span or part of one that
+    /// isn't known, as in
+    /// {"severity":"error","code":"undefined-symbol","message":"'cuont' not found",
+    /// "span":{"file":"a.mal","line":2,"column":1,"start":null,"end":null},
+    /// "notes":["did you mean 'count'?"]}
+    pub fn to_json(&self) -> String {
+        let span = match &self.span {
+            None => "null".to_string(),
+            Some(span) => {
+                let location = span.location.as_ref();
+                let number = |n: Option<usize>| n.map_or("null".to_string(), |n| n.to_string());
+                format!(
+                    "{{\"file\":{},\"line\":{},\"column\":{},\"start\":{},\"end\":{}}}",
+                    location.map_or("null".to_string(), |l| json_string(&l.file)),
+                    number(location.map(|l| l.line)),
+                    number(location.map(|l| l.column)),
+                    number(span.bytes.as_ref().map(|b| b.start)),
+                    number(span.bytes.as_ref().map(|b| b.end)),
+                )
+            }
+        };
+        let notes: Vec<String> = self.notes.iter().map(|n| json_string(n)).collect();
+        format!(
+            "{{\"severity\":{},\"code\":{},\"message\":{},\"span\":{},\"notes\":[{}]}}",
+            json_string(self.severity.as_str()),
+            json_string(self.code),
+            json_string(&self.message),
+            span,
+            notes.join(",")
+        )
+    }
+}
+
+/// s as a JSON string, quoted and escaped
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The line of source span starts on, numbered, with carets under span, as rustc shows them
+fn render(source: &str, span: Range<usize>, at: Option<&Location>) -> String {
+    let (line, _) = line_col(source, span.start);
+    let start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let text = &source[start..end];
+    let gutter = " ".repeat(line.to_string().len());
+    // tabs are kept so the caret lines up under them
+    let indent: String = source[start..span.start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = source[span.start..span.end.min(end)].chars().count().max(1);
+    let mut out = String::new();
+    if let Some(at) = at {
+        out.push_str(&format!("{}--> {}\n", gutter, at));
+    }
+    out.push_str(&format!("{} |\n", gutter));
+    out.push_str(&format!("{} | {}\n", line, text.trim_end()));
+    out.push_str(&format!("{} | {}{}", gutter, indent, "^".repeat(width)));
+    out
+}
@@ -0,0 +1,861 @@
+//! The tree-walking evaluator, and reading, loading and printing around it.
+
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::env::Env;
+use crate::errors::{Location, MalErr};
+#[cfg(feature = "fs")]
+use crate::modules;
+use crate::special_forms::SpecialForm;
+use crate::symbol::Sym;
+#[cfg(feature = "fs")]
+use crate::syntax::line_col;
+use crate::types::{keyword, list_of, symbol, MalType};
+#[cfg(feature = "vm")]
+use crate::vm;
+use crate::{cancel, case_cache, locations, macro_cache, optimize, profile, reader, trace};
+use crate::{hashmap, list, vector};
+
+pub fn read(s: &str) -> Result<MalType, MalErr> {
+    reader::read_str(s.to_string())
+}
+
+fn qq_inner(l: &[MalType]) -> Result<MalType, MalErr> {
+    match l.first() {
+        // If ast is empty return it unchanged
+        None => Ok(list!(vec![])),
+        // If elt is a list starting with the "splice-unquote" symbol, return a list containing:
+        // the "concat" symbol, the second element of elt, then the result of processing the rest of ast recursively.
+        Some(MalType::List(elt, _)) if elt.first() == Some(&symbol("splice-unquote")) => {
+            Ok(list![symbol("concat"), unquoted(elt)?, qq_inner(&l[1..])?])
+        }
+        // Else return a list containing:
+        // the "cons" symbol, the result of calling quasiquote with elt as argument, then the result of processing the rest of ast.
+        Some(elt) => Ok(list![symbol("cons"), quasiquote(elt)?, qq_inner(&l[1..])?]),
+    }
+}
+
+fn quasiquote(ast: &MalType) -> Result<MalType, MalErr> {
+    match ast {
+        MalType::List(l, _) => match l.first() {
+            Some(MalType::Symbol(s)) if s == "unquote" => unquoted(l),
+            _ => qq_inner(l),
+        },
+        MalType::Vector(l, _) => Ok(list![symbol("vec"), qq_inner(l)?]),
+        // Rebuild the map with hash-map only when it contains unquoted keys or values
+        MalType::HashMap(hm, _) if has_unquote(ast) => {
+            let mut v = vec![symbol("hash-map")];
+            for (k, val) in hm.iter() {
+                v.push(quasiquote(k)?);
+                v.push(quasiquote(val)?);
+            }
+            Ok(list!(v))
+        }
+        MalType::HashMap(..) | MalType::Symbol(_) => Ok(list![symbol("quote"), ast.clone()]),
+        _ => Ok(ast.clone()),
+    }
+}
+
+/// The one argument of an (unquote x) or (splice-unquote x) form
+fn unquoted(l: &[MalType]) -> Result<MalType, MalErr> {
+    match l {
+        [_, x] => Ok(x.clone()),
+        _ => Err(MalErr::InvalidForm(
+            l[0].to_string(),
+            format!("expected 1 arguments, got {}", l.len() - 1),
+        )),
+    }
+}
+
+fn has_unquote(ast: &MalType) -> bool {
+    match ast {
+        MalType::List(l, _) => match l.first() {
+            Some(MalType::Symbol(s)) if s == "unquote" || s == "splice-unquote" => true,
+            _ => l.iter().any(has_unquote),
+        },
+        MalType::Vector(l, _) => l.iter().any(has_unquote),
+        MalType::HashMap(hm, _) => hm.iter().any(|(k, v)| has_unquote(k) || has_unquote(v)),
+        _ => false,
+    }
+}
+
+/// The macro a list form calls, if its head is a symbol bound to one
+fn macro_of(l: &[MalType], env: &Env) -> Option<MalType> {
+    match l.first() {
+        Some(MalType::Symbol(s)) => match env.lookup(s) {
+            Some(mac @ MalType::MalFunction { is_macro: true, .. }) => Some(mac),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn macroexpand(mut ast: MalType, env: Rc<Env>) -> Result<MalType, MalErr> {
+    while let MalType::List(l, _) = &ast {
+        let mac = match macro_of(l, &env) {
+            Some(mac) => mac,
+            None => break,
+        };
+        ast = match macro_cache::get(l, &mac) {
+            Some(expansion) => expansion,
+            None => {
+                let expansion = mac.apply(l[1..].to_vec())?;
+                macro_cache::insert(l, &mac, expansion.clone());
+                expansion
+            }
+        };
+    }
+    Ok(ast)
+}
+
+/// Reject match patterns with anything but a single pattern after `&`, anywhere inside them,
+/// which would otherwise be ignored
+fn check_pattern(pattern: &MalType) -> Result<(), MalErr> {
+    match pattern {
+        MalType::List(p, _) if p.first() == Some(&symbol("quote")) => Ok(()),
+        MalType::List(p, _) | MalType::Vector(p, _) => {
+            if let Some(i) = p.iter().position(|el| el == &symbol("&")) {
+                if p.len() > i + 2 {
+                    return Err(SpecialForm::Match.error(format!(
+                        "expected one pattern after & in {}",
+                        pattern.pr_str(true)
+                    )));
+                }
+            }
+            p.iter().try_for_each(check_pattern)
+        }
+        MalType::HashMap(p, _) => p.values().try_for_each(check_pattern),
+        _ => Ok(()),
+    }
+}
+
+/// Match a value against a match pattern, collecting the symbols bound along the way.
+/// `_` matches anything, other symbols bind, sequences match element-wise (with `&` for the rest),
+/// maps match when every key is present and its value matches, and everything else is compared literally.
+fn match_pattern(
+    pattern: &MalType,
+    value: &MalType,
+    bindings: &mut Vec<(String, MalType)>,
+) -> bool {
+    match pattern {
+        MalType::Symbol(s) if s == "_" => true,
+        MalType::Symbol(s) => {
+            bindings.push((s.to_string(), value.clone()));
+            true
+        }
+        MalType::List(p, _) if p.first() == Some(&symbol("quote")) => p.get(1) == Some(value),
+        MalType::List(p, _) | MalType::Vector(p, _) => match value {
+            MalType::List(v, _) | MalType::Vector(v, _) => {
+                match p.iter().position(|el| el == &symbol("&")) {
+                    Some(i) => {
+                        v.len() >= i
+                            && p[..i]
+                                .iter()
+                                .zip(v.iter())
+                                .all(|(p, v)| match_pattern(p, v, bindings))
+                            && match p.get(i + 1) {
+                                Some(rest) => match_pattern(rest, &list_of(v.slice(i..)), bindings),
+                                None => true,
+                            }
+                    }
+                    None => {
+                        p.len() == v.len()
+                            && p.iter()
+                                .zip(v.iter())
+                                .all(|(p, v)| match_pattern(p, v, bindings))
+                    }
+                }
+            }
+            _ => false,
+        },
+        MalType::HashMap(p, _) => match value {
+            MalType::HashMap(v, _) => p.iter().all(|(k, p)| match v.get(k) {
+                Some(v) => match_pattern(p, v, bindings),
+                None => false,
+            }),
+            _ => false,
+        },
+        _ => pattern == value,
+    }
+}
+
+/// Split a `(catch* sym body)` or `(catch* selector sym body)` clause into its parts
+fn catch_clause(clause: &MalType) -> Result<(Option<&MalType>, &MalType, &MalType), MalErr> {
+    if let MalType::List(c, _) = clause {
+        if c.first() == Some(&symbol("catch*")) {
+            match &c[1..] {
+                [sym @ MalType::Symbol(_), body] => return Ok((None, sym, body)),
+                [selector, sym @ MalType::Symbol(_), body] => {
+                    return Ok((Some(selector), sym, body))
+                }
+                _ => (),
+            }
+        }
+    }
+    Err(SpecialForm::Try.error(format!(
+        "expected a (catch* [selector] symbol body) clause, got {}",
+        clause
+    )))
+}
+
+/// Whether a catch* clause handles the error e, whose value is err. A keyword selector matches
+/// the code of the error, that keyword if it was thrown, or a map whose :type is that keyword;
+/// any other selector is evaluated and called as a predicate.
+fn catches(selector: &MalType, e: &MalErr, err: &MalType, env: &Rc<Env>) -> Result<bool, MalErr> {
+    match eval(selector.clone(), Rc::clone(env))? {
+        tag @ MalType::Keyword(_) => Ok(tag == keyword(e.code())
+            || match err {
+                MalType::HashMap(hm, _) => hm.get(&keyword("type")) == Some(&tag),
+                _ => *err == tag,
+            }),
+        pred @ (MalType::Function(..) | MalType::MalFunction { .. }) => Ok(!matches!(
+            pred.apply(vec![err.clone()])?,
+            MalType::Nil | MalType::Bool(false)
+        )),
+        other => Err(SpecialForm::Try.error(format!(
+            "expected a keyword or predicate to select errors, got {}",
+            other
+        ))),
+    }
+}
+
+thread_local! {
+    // looked up on every pass through eval, so made once rather than interned each time
+    static DEBUG_EVAL: Sym = Sym::new("DEBUG-EVAL");
+}
+
+/// Evaluate ast in env. An error is reported as happening at the innermost form being evaluated
+/// that was read from a file, if there is one.
+pub fn eval(ast: MalType, env: Rc<Env>) -> Result<MalType, MalErr> {
+    let mut at = None;
+    eval_at(ast, env, &mut at).map_err(|e| match at {
+        Some(at) => e.at(at),
+        None => e,
+    })
+}
+
+/// eval, keeping at up to date with where the form being evaluated was read from
+fn eval_at(
+    mut ast: MalType,
+    mut env: Rc<Env>,
+    at: &mut Option<Location>,
+) -> Result<MalType, MalErr> {
+    let res: Result<MalType, MalErr>;
+
+    let _depth = trace::Depth::enter();
+    let mut profile_frame = profile::Frame::default();
+
+    'eval: loop {
+        cancel::check()?;
+        if let Some(filter) = DEBUG_EVAL.with(|name| env.lookup(name)) {
+            trace::trace(&ast, &filter);
+        }
+        if let MalType::List(l, _) = &ast {
+            *at = locations::get(l);
+        }
+        ast = macroexpand(ast, Rc::clone(&env))?;
+        // an expansion is reported where the macro call was, unless it was read from a file
+        if let MalType::List(l, _) = &ast {
+            if let Some(expanded) = locations::get(l) {
+                *at = Some(expanded);
+            }
+        }
+        match ast {
+            MalType::List(..) => (), // do nothing, continue with with rest of switch
+            _ => return eval_ast(&ast, &env),
+        }
+        res = match ast.clone() {
+            MalType::List(l, _) => {
+                if l.is_empty() {
+                    return Ok(ast);
+                }
+                let form = SpecialForm::resolve(&l[0]);
+                if let Some(sf) = form {
+                    sf.check(&l)?;
+                }
+                match form {
+                    Some(SpecialForm::Def) => {
+                        let result = eval(l[2].clone(), Rc::clone(&env))?;
+                        env.set(l[1].to_string(), result.clone());
+                        return Ok(result);
+                    }
+                    Some(SpecialForm::DefMacro) => {
+                        let result = eval(l[2].clone(), Rc::clone(&env))?;
+                        match result {
+                            MalType::MalFunction {
+                                eval,
+                                params,
+                                ast,
+                                env,
+                                ..
+                            } => {
+                                let new_macro = MalType::MalFunction {
+                                    eval,
+                                    params,
+                                    ast,
+                                    env: Rc::clone(&env),
+                                    is_macro: true,
+                                    meta: Rc::new(MalType::Nil),
+                                };
+                                env.set(l[1].to_string(), new_macro.clone());
+                                Ok(new_macro)
+                            }
+                            _ => Err(MalErr::Generic(
+                                "cannot set non-function as a macro".to_string(),
+                            )),
+                        }
+                    }
+                    // letrec* binds every name (to nil) before evaluating any initializer, so
+                    // initializers never see an outer binding of a name defined later in the list
+                    Some(SpecialForm::Let | SpecialForm::LetRec) => {
+                        let let_env = Rc::new(Env::new(Some(Rc::clone(&env))));
+                        match &l[1] {
+                            MalType::List(binding_list, _) | MalType::Vector(binding_list, _) => {
+                                if binding_list.len() % 2 != 0 {
+                                    return Err(MalErr::InvalidLet(
+                                        "Odd number of parameters in the binding list".to_string(),
+                                    ));
+                                }
+                                if let Some(name) = binding_list
+                                    .iter()
+                                    .step_by(2)
+                                    .find(|b| !matches!(b, MalType::Symbol(_)))
+                                {
+                                    return Err(MalErr::InvalidLet(format!(
+                                        "expected a symbol to bind, got {}",
+                                        name.pr_str(true)
+                                    )));
+                                }
+                                if form == Some(SpecialForm::LetRec) {
+                                    for w in binding_list.chunks(2) {
+                                        let_env.set(w[0].to_string(), MalType::Nil);
+                                    }
+                                }
+                                for w in binding_list.chunks(2) {
+                                    let_env.set(
+                                        w[0].to_string(),
+                                        eval(w[1].clone(), Rc::clone(&let_env))?,
+                                    );
+                                }
+                            }
+                            _ => {
+                                return Err(MalErr::InvalidLet(
+                                    "let* expects a list or vector as the first parameter"
+                                        .to_string(),
+                                ))
+                            }
+                        };
+                        ast = l[2].clone();
+                        env = let_env;
+                        continue;
+                    }
+                    Some(SpecialForm::Do) if l.len() == 1 => Ok(MalType::Nil),
+                    Some(SpecialForm::Do) => {
+                        for form in &l[1..l.len() - 1] {
+                            eval(form.clone(), Rc::clone(&env))?;
+                        }
+                        ast = l[l.len() - 1].clone();
+                        continue;
+                    }
+                    Some(SpecialForm::If) => match eval(l[1].clone(), Rc::clone(&env))? {
+                        MalType::Nil | MalType::Bool(false) => {
+                            ast = l
+                                .get(3)
+                                .map_or(MalType::Nil, |else_branch| else_branch.clone());
+                            continue;
+                        }
+                        _ => {
+                            ast = l[2].clone();
+                            continue;
+                        }
+                    },
+                    // Rewrite (while cond body...) into (if cond (do body... (while cond body...)))
+                    // so that every iteration goes through the TCO loop instead of recursing
+                    Some(SpecialForm::While) => {
+                        let mut body = vec![symbol("do")];
+                        body.extend_from_slice(&l[2..]);
+                        body.push(ast.clone());
+                        ast = list![symbol("if"), l[1].clone(), list!(body)];
+                        continue;
+                    }
+                    Some(SpecialForm::Case) => {
+                        let value = eval(l[1].clone(), Rc::clone(&env))?;
+                        let clauses = &l[2..];
+                        match case_cache::dispatch(&l).get(&value) {
+                            Some(&branch) => ast = l[branch].clone(),
+                            None if clauses.len() % 2 == 1 => {
+                                ast = clauses[clauses.len() - 1].clone()
+                            }
+                            None => return Err(MalErr::NoMatch(value)),
+                        }
+                        continue;
+                    }
+                    Some(SpecialForm::Condp) => {
+                        let pred = eval(l[1].clone(), Rc::clone(&env))?;
+                        let value = eval(l[2].clone(), Rc::clone(&env))?;
+                        let clauses = &l[3..];
+                        let mut branch = None;
+                        for c in clauses.chunks_exact(2) {
+                            let test = eval(c[0].clone(), Rc::clone(&env))?;
+                            match pred.apply(vec![test, value.clone()])? {
+                                MalType::Nil | MalType::Bool(false) => (),
+                                _ => {
+                                    branch = Some(c[1].clone());
+                                    break;
+                                }
+                            }
+                        }
+                        match branch {
+                            Some(b) => ast = b,
+                            None if clauses.len() % 2 == 1 => {
+                                ast = clauses[clauses.len() - 1].clone()
+                            }
+                            None => return Err(MalErr::NoMatch(value)),
+                        }
+                        continue;
+                    }
+                    Some(sf @ (SpecialForm::DoTimes | SpecialForm::DoSeq)) => {
+                        let (binding, coll_form) = match &l[1] {
+                            MalType::Vector(b, _) if b.len() == 2 => match &b[0] {
+                                MalType::Symbol(s) => (s.clone(), &b[1]),
+                                _ => return Err(sf.error("expected a symbol to bind".to_string())),
+                            },
+                            _ => {
+                                return Err(sf.error(
+                                    "expected a binding vector of a symbol and an expression"
+                                        .to_string(),
+                                ))
+                            }
+                        };
+                        let run = |item: MalType| -> Result<(), MalErr> {
+                            let loop_env = Rc::new(Env::new(Some(Rc::clone(&env))));
+                            loop_env.set(binding.clone(), item);
+                            for form in &l[2..] {
+                                eval(form.clone(), Rc::clone(&loop_env))?;
+                            }
+                            Ok(())
+                        };
+                        match (sf, eval(coll_form.clone(), Rc::clone(&env))?) {
+                            (SpecialForm::DoTimes, MalType::Int(n)) => {
+                                for i in 0..n {
+                                    run(MalType::Int(i))?;
+                                }
+                            }
+                            (SpecialForm::DoSeq, MalType::List(c, _) | MalType::Vector(c, _)) => {
+                                for item in c.iter() {
+                                    run(item.clone())?;
+                                }
+                            }
+                            (SpecialForm::DoSeq, MalType::Nil) => {}
+                            (SpecialForm::DoTimes, _) => {
+                                return Err(sf.error("expected an integer count".to_string()))
+                            }
+                            _ => return Err(sf.error("expected a list or vector".to_string())),
+                        }
+                        Ok(MalType::Nil)
+                    }
+                    // Temporarily rebind globals for the extent of the body, restoring them even on error
+                    Some(sf @ SpecialForm::WithRedefs) => {
+                        let bindings = match &l[1] {
+                            MalType::List(b, _) | MalType::Vector(b, _) if b.len() % 2 == 0 => b,
+                            _ => {
+                                return Err(sf.error(
+                                    "expected a binding vector of symbol/value pairs".to_string(),
+                                ))
+                            }
+                        };
+                        let mut root = Rc::clone(&env);
+                        while let Some(ref e) = Rc::clone(&root).outer {
+                            root = Rc::clone(e);
+                        }
+                        let mut redefs = Vec::new();
+                        for w in bindings.chunks(2) {
+                            redefs.push((
+                                Sym::from(w[0].to_string()),
+                                eval(w[1].clone(), Rc::clone(&env))?,
+                            ));
+                        }
+                        let saved: Vec<(Sym, Option<MalType>)> = redefs
+                            .into_iter()
+                            .map(|(symbol, value)| (symbol.clone(), root.set(symbol, value)))
+                            .collect();
+                        let result = l[2..]
+                            .iter()
+                            .try_fold(MalType::Nil, |_, f| eval(f.clone(), Rc::clone(&env)));
+                        for (symbol, previous) in saved.into_iter().rev() {
+                            match previous {
+                                Some(value) => root.set(symbol, value),
+                                None => root.remove(&symbol),
+                            };
+                        }
+                        result
+                    }
+                    // (match expr pattern body pattern :when guard body ...)
+                    Some(sf @ SpecialForm::Match) => {
+                        let value = eval(l[1].clone(), Rc::clone(&env))?;
+                        let mut clauses = l[2..].iter();
+                        let mut branch = None;
+                        while let Some(pattern) = clauses.next() {
+                            let (guard, body) = match clauses.next() {
+                                Some(when) if when == &keyword("when") => {
+                                    (clauses.next(), clauses.next())
+                                }
+                                body => (None, body),
+                            };
+                            let body = match body {
+                                Some(b) => b,
+                                None => {
+                                    return Err(
+                                        sf.error("expected a body for every pattern".to_string())
+                                    )
+                                }
+                            };
+                            check_pattern(pattern)?;
+                            let mut bindings = Vec::new();
+                            if !match_pattern(pattern, &value, &mut bindings) {
+                                continue;
+                            }
+                            let match_env = Rc::new(Env::new(Some(Rc::clone(&env))));
+                            for (symbol, v) in bindings {
+                                match_env.set(symbol, v);
+                            }
+                            if let Some(g) = guard {
+                                match eval(g.clone(), Rc::clone(&match_env))? {
+                                    MalType::Nil | MalType::Bool(false) => continue,
+                                    _ => (),
+                                }
+                            }
+                            branch = Some((body.clone(), match_env));
+                            break;
+                        }
+                        match branch {
+                            Some((b, e)) => {
+                                ast = b;
+                                env = e;
+                                continue;
+                            }
+                            None => return Err(MalErr::NoMatch(value)),
+                        }
+                    }
+                    Some(SpecialForm::Assert) => match eval(l[1].clone(), Rc::clone(&env))? {
+                        value @ (MalType::Nil | MalType::Bool(false)) => {
+                            let mut info = vec![
+                                keyword("type"),
+                                keyword("assertion-error"),
+                                keyword("form"),
+                                l[1].clone(),
+                                keyword("value"),
+                                value,
+                            ];
+                            if let Some(message) = l.get(2) {
+                                info.push(keyword("message"));
+                                info.push(eval(message.clone(), Rc::clone(&env))?);
+                            }
+                            Err(MalErr::Throw(hashmap!(info)?))
+                        }
+                        _ => Ok(MalType::Nil),
+                    },
+                    Some(SpecialForm::Fn) => match &l[1..] {
+                        [params @ (MalType::List(..) | MalType::Vector(..)), body] => {
+                            return Ok(MalType::MalFunction {
+                                eval,
+                                params: Rc::new(params.clone()),
+                                ast: Rc::new(body.clone()),
+                                env,
+                                is_macro: false,
+                                meta: Rc::new(MalType::Nil),
+                            });
+                        }
+                        _ => Err(MalErr::MalFunctionErr(
+                            "fn* expects two parameters".to_string(),
+                        )),
+                    },
+                    Some(SpecialForm::Eval) => {
+                        ast = eval(l[1].clone(), Rc::clone(&env))?;
+                        while let Some(ref e) = Rc::clone(&env).outer {
+                            env = Rc::clone(e);
+                        }
+                        ast = optimize::optimize(ast, &env);
+                        continue;
+                    }
+                    Some(SpecialForm::LoadFile) => {
+                        let path = eval(l[1].clone(), Rc::clone(&env))?;
+                        while let Some(ref e) = Rc::clone(&env).outer {
+                            env = Rc::clone(e);
+                        }
+                        load_file(&path, &env)
+                    }
+                    Some(SpecialForm::Require) => {
+                        let args = l[1..]
+                            .iter()
+                            .map(|arg| eval(arg.clone(), Rc::clone(&env)))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        while let Some(ref e) = Rc::clone(&env).outer {
+                            env = Rc::clone(e);
+                        }
+                        require(&args, &env)
+                    }
+                    Some(SpecialForm::Quote) => Ok(l[1].clone()),
+                    Some(SpecialForm::Quasiquote) => {
+                        ast = quasiquote(&l[1])?;
+                        continue;
+                    }
+                    Some(SpecialForm::QuasiquoteExpand) => quasiquote(&l[1]),
+                    Some(SpecialForm::MacroExpand) => macroexpand(l[1].clone(), env),
+                    Some(SpecialForm::Try) => {
+                        let clauses = l[2..]
+                            .iter()
+                            .map(catch_clause)
+                            .collect::<Result<Vec<_>, _>>()?;
+                        match eval(l[1].clone(), Rc::clone(&env)) {
+                            // cancellation must reach the top level, so it is never caught
+                            Err(MalErr::Interrupted) => Err(MalErr::Interrupted),
+                            // clauses are tried in order and the first one selecting the error
+                            // handles it; when none does, the error propagates unchanged
+                            Err(e) if !clauses.is_empty() => {
+                                let err = e.to_value();
+                                let mut handler = None;
+                                for (selector, sym, body) in clauses {
+                                    let selected = match selector {
+                                        Some(selector) => catches(selector, &e, &err, &env)?,
+                                        None => true,
+                                    };
+                                    if selected {
+                                        handler = Some((sym, body));
+                                        break;
+                                    }
+                                }
+                                match handler {
+                                    Some((sym, body)) => {
+                                        let catch_env = Rc::new(Env::new(Some(Rc::clone(&env))));
+                                        catch_env.bind(list!(vec![sym.clone()]), vec![err])?;
+                                        eval(body.clone(), catch_env)
+                                    }
+                                    None => Err(e),
+                                }
+                            }
+                            res => res,
+                        }
+                    }
+                    _ => {
+                        let mut f = eval(l[0].clone(), Rc::clone(&env))?;
+                        let mut args = l[1..]
+                            .iter()
+                            .map(|arg| eval(arg.clone(), Rc::clone(&env)))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        let profiling = profile::enabled();
+                        // Builtins like apply hand their final call back as a TailCall,
+                        // so calling a mal function through them doesn't grow the stack
+                        loop {
+                            match f {
+                                MalType::Function(func, _) => {
+                                    let started = profiling.then(Instant::now);
+                                    let res = func(args);
+                                    if let Some(started) = started {
+                                        profile::record(&profile::name(&l[0]), started.elapsed());
+                                    }
+                                    match res {
+                                        Err(MalErr::TailCall(g, a)) => {
+                                            f = *g;
+                                            args = a;
+                                        }
+                                        res => break res,
+                                    }
+                                }
+                                MalType::MalFunction {
+                                    params,
+                                    ast: mfast,
+                                    env: mfenv,
+                                    ..
+                                } => {
+                                    if profiling {
+                                        profile_frame.enter(profile::name(&l[0]));
+                                    }
+                                    let fn_env = Rc::new(Env::new(Some(mfenv)));
+                                    fn_env.bind((*params).clone(), args)?;
+                                    ast = (*mfast).clone();
+                                    env = fn_env;
+                                    continue 'eval;
+                                }
+                                // keywords, maps and vectors in call position
+                                _ => break f.apply(args),
+                            }
+                        }
+                    }
+                }
+            }
+            _ => eval_ast(&ast, &env),
+        };
+
+        break;
+    }
+
+    res
+}
+
+pub fn print(ast: MalType) -> String {
+    ast.pr_str(true)
+}
+
+/// Evaluate with the bytecode VM when it was enabled with --vm, otherwise with the tree-walker
+pub fn evaluate(ast: MalType, env: Rc<Env>) -> Result<MalType, MalErr> {
+    #[cfg(feature = "vm")]
+    if vm::enabled() {
+        return vm::eval(ast, env, eval);
+    }
+    eval(ast, env)
+}
+
+/// rep without the print, for callers that want the value
+pub fn read_eval(s: &str, env: &Rc<Env>) -> Result<MalType, MalErr> {
+    cancel::reset();
+    let r = optimize::optimize(read(s)?, env);
+    evaluate(r, Rc::clone(env))
+}
+
+/// Read, evaluate and print s. A value thrown by it comes back as it was thrown, not as a
+/// message.
+///
+/// ```
+/// use mal::errors::MalErr;
+///
+/// let env = mal::repl_env();
+/// match mal::rep("(throw {:code 42 :tags [:a]})", &env) {
+///     Err(MalErr::Throw(value)) => assert_eq!(value.pr_str(true), "{:code 42 :tags [:a]}"),
+///     other => panic!("expected a thrown map, got {:?}", other),
+/// }
+/// ```
+pub fn rep(s: &str, env: &Rc<Env>) -> Result<String, MalErr> {
+    let e = read_eval(s, env)?;
+    let p = print(e);
+    Ok(p)
+}
+
+/// Read, evaluate and print the forms of s in turn, as rep would each on its own. Reading stops
+/// at the first form that can't be read.
+pub fn rep_forms<'a>(
+    s: &'a str,
+    env: &'a Rc<Env>,
+) -> impl Iterator<Item = Result<String, MalErr>> + 'a {
+    reader::read_forms(s, None).map(move |(_, form)| {
+        cancel::reset();
+        let e = evaluate(optimize::optimize(form?, env), Rc::clone(env))?;
+        Ok(print(e))
+    })
+}
+
+/// Evaluate the forms of a file in turn, with *file* bound to its path while they run. A
+/// relative path is resolved against the directory of the file doing the loading, if any, and
+/// errors are reported with the path, line and column of the form they came from.
+#[cfg(feature = "fs")]
+pub fn load_file(path: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
+    let path = match path {
+        MalType::Str(p) => Path::new(p),
+        _ => {
+            return Err(SpecialForm::LoadFile.error(format!("expected a path, got {}", path)));
+        }
+    };
+    let including = env.lookup(&Sym::new("*file*")).unwrap_or(MalType::Nil);
+    let path = match &including {
+        MalType::Str(f) if path.is_relative() => {
+            Path::new(f).parent().unwrap_or(Path::new("")).join(path)
+        }
+        _ => path.to_path_buf(),
+    };
+    let name = path.to_string_lossy().into_owned();
+    let source =
+        std::fs::read_to_string(&path).map_err(|e| MalErr::IoError(Some(name.clone()), e))?;
+
+    env.set("*file*".to_string(), MalType::Str(name.clone()));
+    let mut res = Ok(MalType::Nil);
+    let file: Rc<str> = Rc::from(name.as_str());
+    for ((line, column), form) in reader::read_forms(&source, Some(&name)) {
+        res = form.and_then(|form| evaluate(optimize::optimize(form, env), Rc::clone(env)));
+        if let Err(e) = res {
+            // a read error is reported at the token at fault rather than where the form starts
+            let (line, column) = match &e {
+                MalErr::ReadErr(_, Some(span)) => line_col(&source, span.start),
+                _ => (line, column),
+            };
+            res = Err(e.at(Location {
+                file: Rc::clone(&file),
+                line,
+                column,
+            }));
+            break;
+        }
+    }
+    env.set("*file*".to_string(), including);
+    res.map(|_| MalType::Nil)
+}
+
+/// (require name) loads a module from MAL_PATH unless it has been loaded already, and
+/// (require name :reload) loads it again regardless
+#[cfg(feature = "fs")]
+fn require(args: &[MalType], env: &Rc<Env>) -> Result<MalType, MalErr> {
+    let name: &str = match &args[0] {
+        MalType::Symbol(name) => name,
+        MalType::Str(s) => s,
+        name => {
+            return Err(SpecialForm::Require.error(format!("expected a module name, got {}", name)))
+        }
+    };
+    match args.get(1) {
+        None => (),
+        Some(opt) if *opt == keyword("reload") => modules::forget(name),
+        Some(opt) => {
+            return Err(SpecialForm::Require.error(format!("unknown option {}", opt)));
+        }
+    }
+    if !modules::start_loading(name) {
+        return Ok(MalType::Nil);
+    }
+    let res = modules::find(name)
+        .and_then(|path| load_file(&MalType::Str(path.to_string_lossy().into_owned()), env));
+    if res.is_err() {
+        modules::forget(name);
+    }
+    res
+}
+
+#[cfg(not(feature = "fs"))]
+fn require(_: &[MalType], _: &Rc<Env>) -> Result<MalType, MalErr> {
+    Err(SpecialForm::Require.error("mal was built without the \"fs\" feature".to_string()))
+}
+
+#[cfg(not(feature = "fs"))]
+pub fn load_file(_: &MalType, _: &Rc<Env>) -> Result<MalType, MalErr> {
+    Err(SpecialForm::LoadFile.error("mal was built without the \"fs\" feature".to_string()))
+}
+
+fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
+    match ast {
+        MalType::Symbol(s) => env.get(s),
+        MalType::List(l, _) => {
+            let mut results = Vec::new();
+            for ast in l.iter() {
+                results.push(eval(ast.clone(), Rc::clone(env))?);
+            }
+            Ok(list!(results))
+        }
+        MalType::Vector(l, _) => {
+            let mut results = Vec::new();
+            for ast in l.iter() {
+                results.push(eval(ast.clone(), Rc::clone(env))?);
+            }
+            Ok(vector!(results))
+        }
+        MalType::HashMap(hm, _) => {
+            let mut results = Vec::new();
+            for (k, v) in hm.iter() {
+                results.push(k.clone());
+                results.push(eval(v.clone(), Rc::clone(env))?);
+            }
+            hashmap!(results)
+        }
+        _ => Ok(ast.clone()),
+    }
+}
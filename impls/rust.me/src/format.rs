@@ -0,0 +1,163 @@
+//! The `format` builtin: printf-style directives, following Java's (and so Clojure's) format.
+//!
+//! A directive is `%[flags][width][.precision]conversion`, where the conversion is one of
+//!
+//! - `s`: the argument as str prints it, `S` as pr-str does; a precision truncates it
+//! - `d`: a decimal integer, `x`/`X` hexadecimal, `o` octal and `b` binary
+//! - `%`: a literal %, and `n` a newline, neither taking an argument
+//!
+//! and the flags are `-` (pad on the right), `0` (pad numbers with zeros after the sign) and `+`
+//! (always give decimals a sign). The width is a minimum number of characters. Widths and
+//! precisions are at most 65536, so a typo can't ask for gigabytes of padding. Arguments left
+//! over once the directives are used up are ignored.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::errors::MalErr;
+use crate::types::MalType;
+
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+const MAX_WIDTH: usize = 65536;
+
+fn number(chars: &mut Peekable<Chars>) -> Result<Option<usize>, MalErr> {
+    let mut n = None;
+    while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+        let m = n.unwrap_or(0) * 10 + d as usize;
+        if m > MAX_WIDTH {
+            return Err(MalErr::FunctionErr(format!(
+                "Format width or precision over {}",
+                MAX_WIDTH
+            )));
+        }
+        n = Some(m);
+        chars.next();
+    }
+    Ok(n)
+}
+
+fn spec(chars: &mut Peekable<Chars>) -> Result<Spec, MalErr> {
+    let mut spec = Spec::default();
+    while let Some(c) = chars.peek() {
+        match c {
+            '-' => spec.left = true,
+            '0' => spec.zero = true,
+            '+' => spec.plus = true,
+            _ => break,
+        }
+        chars.next();
+    }
+    spec.width = number(chars)?;
+    if chars.next_if_eq(&'.').is_some() {
+        spec.precision =
+            Some(number(chars)?.ok_or_else(|| MalErr::expected("a number after . in format"))?);
+    }
+    Ok(spec)
+}
+
+fn pad(s: String, spec: &Spec) -> String {
+    let len = s.chars().count();
+    match spec.width {
+        Some(width) if width > len => {
+            let fill = " ".repeat(width - len);
+            if spec.left {
+                s + &fill
+            } else {
+                fill + &s
+            }
+        }
+        _ => s,
+    }
+}
+
+fn integer(value: &MalType, conversion: char, spec: &Spec) -> Result<String, MalErr> {
+    let i = match value {
+        MalType::Int(i) => *i,
+        _ => {
+            return Err(MalErr::type_error(
+                format!("a number for %{}", conversion),
+                value,
+            ))
+        }
+    };
+    if spec.precision.is_some() {
+        return Err(MalErr::FunctionErr(format!(
+            "%{} doesn't take a precision",
+            conversion
+        )));
+    }
+    // only decimals are signed; the other bases show the bits of negative numbers, as Java does
+    let (sign, digits) = match conversion {
+        'd' if i < 0 => ("-", i.unsigned_abs().to_string()),
+        'd' if spec.plus => ("+", i.to_string()),
+        'd' => ("", i.to_string()),
+        'x' => ("", format!("{:x}", i)),
+        'X' => ("", format!("{:X}", i)),
+        'o' => ("", format!("{:o}", i)),
+        _ => ("", format!("{:b}", i)),
+    };
+    match spec.width {
+        Some(width) if spec.zero && !spec.left && width > sign.len() + digits.len() => Ok(format!(
+            "{}{}{}",
+            sign,
+            "0".repeat(width - sign.len() - digits.len()),
+            digits
+        )),
+        _ => Ok(pad(format!("{}{}", sign, digits), spec)),
+    }
+}
+
+/// (format fmt & args)
+pub fn format(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let fmt = match args.first() {
+        Some(MalType::Str(s)) => s,
+        _ => return Err(MalErr::expected("a format string")),
+    };
+    let mut args = args[1..].iter();
+    let mut out = String::new();
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let spec = spec(&mut chars)?;
+        let conversion = chars
+            .next()
+            .ok_or_else(|| MalErr::FunctionErr("Format ends in the middle of a %".to_string()))?;
+        let mut arg = || {
+            args.next().ok_or_else(|| {
+                MalErr::FunctionErr(format!("Not enough arguments for %{}", conversion))
+            })
+        };
+        let s = match conversion {
+            '%' => pad("%".to_string(), &spec),
+            'n' => "\n".to_string(),
+            's' | 'S' => {
+                let s = arg()?.pr_str(conversion == 'S');
+                let s = match spec.precision {
+                    Some(n) => s.chars().take(n).collect(),
+                    None => s,
+                };
+                pad(s, &spec)
+            }
+            'd' | 'x' | 'X' | 'o' | 'b' => integer(arg()?, conversion, &spec)?,
+            _ => {
+                return Err(MalErr::FunctionErr(format!(
+                    "Unknown format directive %{}",
+                    conversion
+                )))
+            }
+        };
+        out.push_str(&s);
+    }
+    Ok(MalType::Str(out))
+}
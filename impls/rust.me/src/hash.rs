@@ -0,0 +1,109 @@
+//! Value hashing for the `hash` builtin.
+//!
+//! Hashes are 64-bit FNV-1a over a tagged encoding of the value, so they are the same across
+//! runs, platforms and Rust versions (unlike std's DefaultHasher). They agree with `=`: lists
+//! and vectors with equal elements hash alike, and a map's entries are combined without regard
+//! to order.
+
+use crate::errors::MalErr;
+use crate::types::MalType;
+
+const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const PRIME: u64 = 0x100000001b3;
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(PRIME);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+}
+
+pub fn hash(value: &MalType) -> Result<i64, MalErr> {
+    let mut h = Fnv::new();
+    write(value, &mut h)?;
+    Ok(h.0 as i64)
+}
+
+fn write(value: &MalType, h: &mut Fnv) -> Result<(), MalErr> {
+    match value {
+        MalType::Nil => h.write(&[0]),
+        MalType::Bool(b) => h.write(&[1, *b as u8]),
+        MalType::Int(i) => {
+            h.write(&[2]);
+            h.write_u64(*i as u64);
+        }
+        MalType::Str(s) => {
+            h.write(&[3]);
+            h.write_u64(s.len() as u64);
+            h.write(s.as_bytes());
+        }
+        MalType::Keyword(k) => {
+            h.write(&[9]);
+            h.write_u64(k.len() as u64);
+            h.write(k.as_bytes());
+        }
+        MalType::Symbol(s) => {
+            h.write(&[4]);
+            h.write_u64(s.len() as u64);
+            h.write(s.as_bytes());
+        }
+        MalType::List(l, _) | MalType::Vector(l, _) => {
+            h.write(&[5]);
+            h.write_u64(l.len() as u64);
+            for el in l.iter() {
+                write(el, h)?;
+            }
+        }
+        MalType::Bytes(b) => {
+            h.write(&[7]);
+            h.write_u64(b.len() as u64);
+            h.write(b);
+        }
+        MalType::Pattern(p) => {
+            h.write(&[8]);
+            h.write_u64(p.0.as_str().len() as u64);
+            h.write(p.0.as_str().as_bytes());
+        }
+        MalType::HashMap(hm, _) => {
+            let mut entries: u64 = 0;
+            for (k, v) in hm.iter() {
+                let mut entry = Fnv::new();
+                write(k, &mut entry)?;
+                write(v, &mut entry)?;
+                entries = entries.wrapping_add(entry.0);
+            }
+            h.write(&[6]);
+            h.write_u64(hm.len() as u64);
+            h.write_u64(entries);
+        }
+        MalType::Function(..)
+        | MalType::MalFunction { .. }
+        | MalType::Atom(_)
+        | MalType::Reduced(_) => {
+            return Err(MalErr::FunctionErr(format!(
+                "Cannot hash {}",
+                value.pr_str(true)
+            )))
+        }
+        #[cfg(feature = "sync")]
+        MalType::Thread(_) | MalType::Channel(_) => {
+            return Err(MalErr::FunctionErr(format!(
+                "Cannot hash {}",
+                value.pr_str(true)
+            )))
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,97 @@
+//! Blocking HTTP client builtins (cargo feature `http`).
+//!
+//! Responses are `{:status :headers :body}` maps. Every status code, including 4xx and 5xx, is
+//! a response rather than an error; only failing to get a response at all (bad URL, DNS,
+//! connection, TLS) is reported, as an I/O error on the URL. Bodies that are valid UTF-8 are
+//! strings and anything else is bytes.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::rc::Rc;
+
+use crate::errors::MalErr;
+use crate::types::{keyword, MalType};
+
+fn hash_map(hm: BTreeMap<MalType, MalType>) -> MalType {
+    MalType::HashMap(Rc::new(hm), Rc::new(MalType::Nil))
+}
+
+fn string(value: &MalType, what: &str) -> Result<String, MalErr> {
+    match value {
+        MalType::Keyword(k) => Ok(k.to_string()),
+        MalType::Str(s) => Ok(s.to_string()),
+        _ => Err(MalErr::type_error(format!("a string {}", what), value)),
+    }
+}
+
+fn response(url: &str, res: Result<ureq::Response, ureq::Error>) -> Result<MalType, MalErr> {
+    let res = match res {
+        Ok(res) | Err(ureq::Error::Status(_, res)) => res,
+        Err(e) => return Err(MalErr::IoError(Some(url.to_string()), io::Error::other(e))),
+    };
+    let status = MalType::Int(res.status() as i64);
+    let headers = res
+        .headers_names()
+        .into_iter()
+        .filter_map(|name| {
+            let value = res.header(&name)?.to_string();
+            Some((MalType::Str(name), MalType::Str(value)))
+        })
+        .collect();
+    let mut body = Vec::new();
+    res.into_reader()
+        .read_to_end(&mut body)
+        .map_err(|e| MalErr::IoError(Some(url.to_string()), e))?;
+    let body = match String::from_utf8(body) {
+        Ok(s) => MalType::Str(s),
+        Err(e) => MalType::Bytes(Rc::new(e.into_bytes())),
+    };
+    Ok(hash_map(BTreeMap::from([
+        (keyword("status"), status),
+        (keyword("headers"), hash_map(headers)),
+        (keyword("body"), body),
+    ])))
+}
+
+pub fn get(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match args.as_slice() {
+        [url] => {
+            let url = string(url, "URL")?;
+            response(&url, ureq::get(&url).call())
+        }
+        _ => Err(MalErr::expected("a URL")),
+    }
+}
+
+/// (http-request {:method :url :headers :body}), where only :url is required. The method is a
+/// string or keyword (GET by default) and the body a string or bytes.
+pub fn request(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let opts = match args.as_slice() {
+        [MalType::HashMap(opts, _)] => opts,
+        _ => return Err(MalErr::expected("a {:method :url :headers :body} map")),
+    };
+    let url = match opts.get(&keyword("url")) {
+        Some(url) => string(url, "URL")?,
+        None => return Err(MalErr::expected("a :url")),
+    };
+    let method = match opts.get(&keyword("method")) {
+        Some(method) => string(method, "method")?.to_uppercase(),
+        None => "GET".to_string(),
+    };
+    let mut req = ureq::request(&method, &url);
+    match opts.get(&keyword("headers")) {
+        Some(MalType::HashMap(headers, _)) => {
+            for (k, v) in headers.iter() {
+                req = req.set(&string(k, "header name")?, &string(v, "header value")?);
+            }
+        }
+        Some(MalType::Nil) | None => (),
+        Some(other) => return Err(MalErr::type_error("a map of :headers", other)),
+    }
+    let res = match opts.get(&keyword("body")) {
+        Some(MalType::Bytes(b)) => req.send_bytes(b),
+        Some(MalType::Nil) | None => req.call(),
+        Some(body) => req.send_string(&string(body, "or bytes body")?),
+    };
+    response(&url, res)
+}
@@ -0,0 +1,382 @@
+//! Where the REPL reads its lines from: rustyline's line editor, with history, tab completion of
+//! the names in the REPL environment, hints at the parameters of the function just named, and the
+//! line colored as the reader sees it with the bracket paired with the one at the cursor marked,
+//! or plain lines from stdin when mal is built without the "readline" feature.
+
+#[cfg(feature = "readline")]
+use std::borrow::Cow;
+#[cfg(feature = "readline")]
+use std::path::PathBuf;
+use std::rc::Rc;
+
+#[cfg(feature = "readline")]
+use rustyline::completion::Completer;
+#[cfg(feature = "readline")]
+use rustyline::error::ReadlineError;
+#[cfg(feature = "readline")]
+use rustyline::highlight::Highlighter;
+#[cfg(feature = "readline")]
+use rustyline::hint::{Hint, Hinter};
+#[cfg(feature = "readline")]
+use rustyline::history::DefaultHistory;
+#[cfg(feature = "readline")]
+use rustyline::validate::Validator;
+#[cfg(feature = "readline")]
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+#[cfg(not(feature = "readline"))]
+use std::io::Write;
+
+use mal::env::Env;
+#[cfg(feature = "readline")]
+use mal::symbol::Sym;
+#[cfg(feature = "readline")]
+use mal::types::{keyword, MalType};
+
+/// Lines of history kept unless MAL_HISTORY_SIZE says otherwise
+#[cfg(feature = "readline")]
+const HISTORY_SIZE: usize = 1000;
+
+#[cfg(feature = "readline")]
+const STRING: &str = "\x1b[32m";
+#[cfg(feature = "readline")]
+const NUMBER: &str = "\x1b[36m";
+#[cfg(feature = "readline")]
+const KEYWORD: &str = "\x1b[35m";
+#[cfg(feature = "readline")]
+const SPECIAL_FORM: &str = "\x1b[1;33m";
+#[cfg(feature = "readline")]
+const MATCHED: &str = "\x1b[1;34m";
+#[cfg(feature = "readline")]
+const MISMATCHED: &str = "\x1b[1;31m";
+#[cfg(feature = "readline")]
+const DIM: &str = "\x1b[2m";
+#[cfg(feature = "readline")]
+const RESET: &str = "\x1b[0m";
+
+#[cfg(feature = "readline")]
+#[derive(Default)]
+struct ReplHelper {
+    /// where completions come from, once there is an environment
+    env: Option<Rc<Env>>,
+}
+
+/// Where the symbol before pos starts, which is wherever the reader would have ended the token
+/// before it
+#[cfg(feature = "readline")]
+fn symbol_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .rfind(|c: char| c.is_whitespace() || "()[]{}'`~^@\",;".contains(c))
+        .map_or(0, |i| i + 1)
+}
+
+/// Completes the symbol before the cursor with the special forms and the names bound in env
+#[cfg(feature = "readline")]
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = symbol_start(line, pos);
+        let word = &line[start..pos];
+        let mut names: Vec<String> = mal::special_forms()
+            .map(str::to_string)
+            .chain(self.env.iter().flat_map(|env| env.symbols()))
+            .filter(|name| name.starts_with(word))
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok((start, names))
+    }
+}
+
+/// The bracket paired with the one just before the cursor, or else the one under it, and
+/// whether the two are of the same kind. Brackets in strings and comments don't count.
+#[cfg(feature = "readline")]
+fn paired_bracket(line: &str, pos: usize) -> Option<(usize, bool)> {
+    let brackets: Vec<(usize, u8)> = mal::reader::spans(line)
+        .map(|token| (token.start, line.as_bytes()[token.start]))
+        .filter(|&(_, b)| b"()[]{}".contains(&b))
+        .collect();
+    let at = brackets
+        .iter()
+        .find(|&&(i, b)| i + 1 == pos && b")]}".contains(&b))
+        .or_else(|| brackets.iter().find(|&&(i, _)| i == pos))?
+        .0;
+    let mut open = Vec::new();
+    for &(i, b) in &brackets {
+        if b"([{".contains(&b) {
+            open.push((i, b));
+        } else if let Some((o, opener)) = open.pop() {
+            let same = matches!((opener, b), (b'(', b')') | (b'[', b']') | (b'{', b'}'));
+            if i == at {
+                return Some((o, same));
+            } else if o == at {
+                return Some((i, same));
+            }
+        }
+    }
+    None
+}
+
+/// The color of a token, going by the value the reader makes of it
+#[cfg(feature = "readline")]
+fn color(token: &str) -> Option<&'static str> {
+    match MalType::try_from(token.to_string()) {
+        Ok(MalType::Int(_)) => Some(NUMBER),
+        Ok(MalType::Keyword(_)) => Some(KEYWORD),
+        // including one that isn't closed yet
+        Ok(MalType::Str(_)) | Err(_) => Some(STRING),
+        Ok(MalType::Symbol(s)) if mal::special_forms().any(|form| *s == *form) => {
+            Some(SPECIAL_FORM)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(feature = "readline")]
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        let paired = paired_bracket(line, pos);
+        let mut colored = String::with_capacity(line.len());
+        let mut end = 0;
+        for token in mal::reader::spans(line) {
+            let text = &line[token.clone()];
+            let color = match paired {
+                Some((i, same)) if i == token.start => {
+                    Some(if same { MATCHED } else { MISMATCHED })
+                }
+                _ => color(text),
+            };
+            colored.push_str(&line[end..token.start]);
+            match color {
+                Some(color) => colored.push_str(&format!("{}{}{}", color, text, RESET)),
+                None => colored.push_str(text),
+            }
+            end = token.end;
+        }
+        colored.push_str(&line[end..]);
+        Cow::Owned(colored)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("{}{}{}", DIM, hint, RESET))
+    }
+
+    fn highlight_char(&self, _: &str, _: usize) -> bool {
+        true
+    }
+}
+
+/// The parameters of a function, and the first line of its docstring, shown after its name
+#[cfg(feature = "readline")]
+struct ArglistHint(String);
+
+#[cfg(feature = "readline")]
+impl Hint for ArglistHint {
+    fn display(&self) -> &str {
+        &self.0
+    }
+
+    // it's there to read, not to be typed in
+    fn completion(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Hints at the arglists and docstring of the function whose name was just typed
+#[cfg(feature = "readline")]
+impl Hinter for ReplHelper {
+    type Hint = ArglistHint;
+
+    fn hint(&self, line: &str, pos: usize, _: &Context<'_>) -> Option<ArglistHint> {
+        if pos < line.len() {
+            return None;
+        }
+        let env = self.env.as_ref()?;
+        let name = &line[symbol_start(line, pos)..pos];
+        let f = match env.lookup(&Sym::new(name)) {
+            Some(f @ (MalType::Function(..) | MalType::MalFunction { .. })) => f,
+            _ => return None,
+        };
+        let mut hint = String::new();
+        if let Ok(MalType::List(arglists, _)) =
+            env.lookup(&Sym::new("arglists"))?.apply(vec![f.clone()])
+        {
+            for params in arglists.iter() {
+                hint.push(' ');
+                hint.push_str(&params.pr_str(true));
+            }
+        }
+        if let Ok(MalType::HashMap(meta, _)) = f.get_meta() {
+            if let Some(MalType::Str(doc)) = meta.get(&keyword("doc")) {
+                hint.push_str("  ; ");
+                hint.push_str(doc.lines().next().unwrap_or(""));
+            }
+        }
+        (!hint.is_empty()).then_some(ArglistHint(hint))
+    }
+}
+
+#[cfg(feature = "readline")]
+impl Validator for ReplHelper {}
+
+#[cfg(feature = "readline")]
+impl Helper for ReplHelper {}
+
+/// Where history is kept: MAL_HISTORY if it is set, where an empty path keeps none, or else
+/// mal/history in the XDG data directory
+#[cfg(feature = "readline")]
+fn history_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("MAL_HISTORY") {
+        return (!path.is_empty()).then(|| PathBuf::from(path));
+    }
+    let data = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(data.join("mal/history"))
+}
+
+/// How many lines of history to keep, from MAL_HISTORY_SIZE
+#[cfg(feature = "readline")]
+fn history_size() -> usize {
+    match std::env::var("MAL_HISTORY_SIZE") {
+        Ok(size) => size.parse().unwrap_or_else(|_| {
+            eprintln!(
+                "Ignoring MAL_HISTORY_SIZE={}, which isn't a number of lines",
+                size
+            );
+            HISTORY_SIZE
+        }),
+        Err(_) => HISTORY_SIZE,
+    }
+}
+
+#[cfg(feature = "readline")]
+pub struct Input {
+    editor: Editor<ReplHelper, DefaultHistory>,
+    history: Option<PathBuf>,
+    /// text to start the next line with, for fixing a line that didn't read
+    retry: Option<String>,
+}
+
+#[cfg(feature = "readline")]
+impl Input {
+    pub fn open() -> rustyline::Result<Self> {
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .max_history_size(history_size())?
+            .history_ignore_dups(true)?
+            .build();
+        let mut editor = Editor::with_config(config)?;
+        editor.set_helper(Some(ReplHelper::default()));
+        let history = history_path();
+        if let Some(path) = &history {
+            if editor.load_history(path).is_err() {
+                println!("No previous history.");
+            }
+        }
+        Ok(Input {
+            editor,
+            history,
+            retry: None,
+        })
+    }
+
+    /// Complete names from env when tab is pressed
+    // not every step has an environment to complete from
+    #[allow(dead_code)]
+    pub fn complete_from(&mut self, env: &Rc<Env>) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.env = Some(Rc::clone(env));
+        }
+    }
+
+    /// Start the next line with line, with the cursor at its end, so it can be fixed in place
+    // only a step that reports where a read error is knows to offer the line again
+    #[allow(dead_code)]
+    pub fn retry(&mut self, line: &str) {
+        self.retry = Some(line.to_string());
+    }
+
+    /// The next line, or None once the user is done
+    pub fn line(&mut self, prompt: &str) -> Option<String> {
+        let read = match self.retry.take() {
+            Some(initial) => self.editor.readline_with_initial(prompt, (&initial, "")),
+            None => self.editor.readline(prompt),
+        };
+        match read {
+            Ok(line) => {
+                // history is a convenience, so failing to add to it isn't worth stopping for
+                let _ = self.editor.add_history_entry(&line);
+                Some(line)
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("CTRL-C");
+                None
+            }
+            Err(ReadlineError::Eof) => {
+                println!("CTRL-D");
+                None
+            }
+            Err(err) => {
+                println!("Error: {:?}", err);
+                None
+            }
+        }
+    }
+
+    pub fn close(mut self) {
+        let path = match &self.history {
+            Some(path) => path,
+            None => return,
+        };
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Err(e) = self.editor.save_history(path) {
+            eprintln!("Error saving history to {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(not(feature = "readline"))]
+pub struct Input;
+
+#[cfg(not(feature = "readline"))]
+impl Input {
+    pub fn open() -> std::io::Result<Self> {
+        Ok(Input)
+    }
+
+    #[allow(dead_code)]
+    pub fn complete_from(&mut self, _: &Rc<Env>) {}
+
+    /// Plain stdin has no way to start a line with text, so the line has to be typed again
+    #[allow(dead_code)]
+    pub fn retry(&mut self, _: &str) {}
+
+    /// The next line, or None at the end of stdin
+    pub fn line(&mut self, prompt: &str) -> Option<String> {
+        print!("{}", prompt);
+        std::io::stdout().flush().ok()?;
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) => {
+                println!("CTRL-D");
+                None
+            }
+            Ok(_) => Some(line.trim_end_matches(['\n', '\r']).to_string()),
+            Err(err) => {
+                println!("Error: {:?}", err);
+                None
+            }
+        }
+    }
+
+    pub fn close(self) {}
+}
@@ -0,0 +1,105 @@
+//! Parameter lists of functions, for the `arglists` and `arity-of` builtins.
+//!
+//! A function's arglists are a list of parameter vectors, one per way of calling it, kept under
+//! `:arglists` in its metadata as in Clojure. Builtins are declared when they are registered in
+//! core, off to the side so that their metadata stays nil; a MalFunction without any falls back
+//! to its own parameters.
+
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::errors::MalErr;
+use crate::reader::read_str;
+use crate::types::{keyword, symbol, MalType};
+use crate::{list, vector};
+
+type Builtin = fn(Vec<MalType>) -> Result<MalType, MalErr>;
+
+lazy_static! {
+    // keyed by address, which builtins keep across with-meta and into other threads
+    static ref BUILTINS: Mutex<HashMap<usize, &'static str>> = Mutex::new(HashMap::new());
+}
+
+/// Record the arglists of a builtin, such as "([x] [x y])"
+pub fn declare(f: Builtin, arglists: &'static str) {
+    BUILTINS.lock().unwrap().insert(f as usize, arglists);
+}
+
+fn hash_map(hm: BTreeMap<MalType, MalType>) -> MalType {
+    MalType::HashMap(Rc::new(hm), Rc::new(MalType::Nil))
+}
+
+fn declared(f: &MalType) -> Result<MalType, MalErr> {
+    let meta = match f {
+        MalType::Function(_, meta) | MalType::MalFunction { meta, .. } => meta,
+        _ => return Err(MalErr::type_error("a function", f)),
+    };
+    if let MalType::HashMap(hm, _) = &**meta {
+        if let Some(arglists) = hm.get(&keyword("arglists")) {
+            return Ok(arglists.clone());
+        }
+    }
+    let builtin = match f {
+        MalType::Function(f, _) => BUILTINS.lock().unwrap().get(&(*f as usize)).copied(),
+        _ => None,
+    };
+    match builtin {
+        Some(arglists) => read_str(arglists.to_string()),
+        None => Ok(MalType::Nil),
+    }
+}
+
+/// (arglists f) is the list of f's parameter vectors, or nil for an undeclared builtin
+pub fn arglists(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let f = match args.as_slice() {
+        [f] => f,
+        _ => return Err(MalErr::expected("a function")),
+    };
+    match (declared(f)?, f) {
+        (MalType::Nil, MalType::MalFunction { params, .. }) => match &**params {
+            MalType::List(l, _) | MalType::Vector(l, _) => Ok(list!(vec![vector!(l.to_vec())])),
+            _ => Ok(MalType::Nil),
+        },
+        (arglists, _) => Ok(arglists),
+    }
+}
+
+/// (arity-of f) is {:min n :max m} over all of f's arglists, where :max is nil if f takes any
+/// number of arguments, or nil if f's arglists are unknown
+pub fn arity_of(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let arglists = match arglists(args)? {
+        MalType::List(l, _) | MalType::Vector(l, _) => l,
+        _ => return Ok(MalType::Nil),
+    };
+    if arglists.is_empty() {
+        return Ok(MalType::Nil);
+    }
+    let mut min = usize::MAX;
+    let mut max = Some(0);
+    for params in arglists.iter() {
+        let params = match params {
+            MalType::List(l, _) | MalType::Vector(l, _) => l,
+            _ => {
+                return Err(MalErr::type_error(
+                    "a parameter vector in :arglists",
+                    params,
+                ))
+            }
+        };
+        let variadic = params.iter().position(|p| p == &symbol("&"));
+        let required = variadic.unwrap_or(params.len());
+        min = min.min(required);
+        max = match variadic {
+            Some(_) => None,
+            None => max.map(|m: usize| m.max(required)),
+        };
+    }
+    let int = |n: usize| MalType::Int(n as i64);
+    Ok(hash_map(BTreeMap::from([
+        (keyword("min"), int(min)),
+        (keyword("max"), max.map_or(MalType::Nil, int)),
+    ])))
+}
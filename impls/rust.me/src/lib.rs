@@ -0,0 +1,120 @@
+//! A mal interpreter, usable as a library.
+//!
+//! [`repl_env`] makes a root environment holding the core builtins, [`load_prelude`] adds the
+//! functions and macros written in mal, and [`rep`] reads, evaluates and prints a string in it.
+//! The stepA_mal binary is the REPL and script runner built on top.
+//!
+//! ```
+//! let env = mal::repl_env();
+//! mal::rep("(def! sq (fn* (x) (* x x)))", &env).unwrap();
+//! assert_eq!(mal::rep("(sq 7)", &env).unwrap(), "49");
+//! ```
+
+// MalType is used as a BTreeMap key even though atoms are interior-mutable
+#![allow(clippy::mutable_key_type)]
+
+use std::rc::Rc;
+
+pub mod cancel;
+mod case_cache;
+pub mod core;
+pub mod env;
+pub mod errors;
+mod eval;
+mod format;
+mod hash;
+#[cfg(feature = "http")]
+mod http;
+mod introspect;
+mod locations;
+mod macro_cache;
+#[cfg(feature = "fs")]
+mod modules;
+mod optimize;
+pub mod printer;
+mod profile;
+mod re;
+pub mod reader;
+pub mod seq;
+mod special_forms;
+pub mod symbol;
+pub mod syntax;
+#[cfg(feature = "sync")]
+mod thread;
+mod trace;
+pub mod types;
+#[cfg(feature = "vm")]
+pub mod vm;
+
+pub use eval::{eval, evaluate, load_file, print, read, read_eval, rep, rep_forms};
+
+use env::Env;
+use errors::MalErr;
+use types::MalType;
+
+/// The functions of the prelude, which only need def! and fn* to define
+pub const PRELUDE: &str = include_str!("prelude.mal");
+/// The macros of the prelude, for interpreters that expand macros
+pub const PRELUDE_MACROS: &str = include_str!("prelude_macros.mal");
+
+/// A root environment with the core builtins, *host-language* and *file*, and functions standing
+/// in for the special forms that are also useful as values
+pub fn repl_env() -> Rc<Env> {
+    let env = Rc::new(Env::default());
+    for (symbol, value) in core::ns() {
+        env.set(symbol.to_string(), value);
+    }
+    env.set(
+        "*host-language*".to_string(),
+        MalType::Str("rust".to_string()),
+    );
+    // the file being loaded, nil at the REPL
+    env.set("*file*".to_string(), MalType::Nil);
+
+    #[cfg(feature = "sync")]
+    let _ = rep(
+        "(defmacro! future (fn* (& body) `(future-call (fn* () (do ~@body)))))",
+        &env,
+    );
+    // eval is also a special form; this binding is what (map eval forms) and friends see
+    let _ = rep("(def! eval (fn* (form) (eval form)))", &env);
+    // and so is load-file, which needs the root environment to load into
+    let _ = rep("(def! load-file (fn* (f) (load-file f)))", &env);
+    let _ = rep(
+        "(def! require (fn* (name & opts) (if (empty? opts) (require name) (require name (first opts)))))",
+        &env,
+    );
+    env
+}
+
+/// The names of the special forms, which eval handles itself rather than finding in an
+/// environment
+pub fn special_forms() -> impl Iterator<Item = &'static str> {
+    special_forms::SpecialForm::ALL
+        .iter()
+        .map(|form| form.name())
+}
+
+/// Check that a list headed by a special form has the arguments it needs, so that they can be
+/// indexed into. Any other list passes.
+pub fn check_form(l: &[MalType]) -> Result<(), MalErr> {
+    match l.first().and_then(special_forms::SpecialForm::resolve) {
+        Some(form) => form.check(l),
+        None => Ok(()),
+    }
+}
+
+/// Define the functions and macros of the prelude in env
+pub fn load_prelude(env: &Rc<Env>) -> Result<(), MalErr> {
+    for source in [PRELUDE, PRELUDE_MACROS] {
+        rep(&format!("(do {}\nnil)", source), env)?;
+    }
+    Ok(())
+}
+
+/// Forget which modules require has loaded, so that it loads them again into the next
+/// environment that requires them
+#[cfg(feature = "fs")]
+pub fn forget_modules() {
+    modules::forget_all();
+}
@@ -0,0 +1,59 @@
+//! Where the lists read from a file start in it, so an error can say which form it came from.
+//!
+//! Like the macro cache, entries are keyed by the address of a list and only used while that list
+//! is still alive. The optimizer rebuilds lists, so it moves their entries onto the new ones.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::errors::Location;
+use crate::seq::{Seq, SeqKey, WeakSeq};
+use crate::types::MalType;
+
+struct Table {
+    entries: HashMap<SeqKey, (WeakSeq, Location)>,
+    /// how many entries there can be before the dead ones are dropped
+    limit: usize,
+}
+
+thread_local! {
+    static TABLE: RefCell<Table> = RefCell::new(Table {
+        entries: HashMap::new(),
+        limit: 4096,
+    });
+}
+
+/// Where the list l was read from, if it came from a file
+pub fn get(l: &Seq) -> Option<Location> {
+    TABLE.with(|t| {
+        let t = t.borrow();
+        if t.entries.is_empty() {
+            return None;
+        }
+        t.entries
+            .get(&l.key())
+            .filter(|(list, _)| list.upgrade().is_some_and(|live| Seq::ptr_eq(&live, l)))
+            .map(|(_, at)| at.clone())
+    })
+}
+
+pub fn set(l: &Seq, at: Location) {
+    TABLE.with(|t| {
+        let mut t = t.borrow_mut();
+        if t.entries.len() >= t.limit {
+            t.entries.retain(|_, (list, _)| list.strong_count() > 0);
+            t.limit = (t.entries.len() * 2).max(4096);
+        }
+        t.entries.insert(l.key(), (l.downgrade(), at));
+    });
+}
+
+/// to, given the place from was read from when both are lists
+pub fn copy(from: &MalType, to: MalType) -> MalType {
+    if let (MalType::List(from, _), MalType::List(l, _)) = (from, &to) {
+        if let Some(at) = get(from) {
+            set(l, at);
+        }
+    }
+    to
+}
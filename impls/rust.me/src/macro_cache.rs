@@ -0,0 +1,70 @@
+//! Expansions of macro call sites, so a macro call inside a loop or function body is expanded
+//! once rather than on every pass through eval.
+//!
+//! Entries are keyed by the address of the call form's list and remember which macro produced
+//! them. An entry is only used while the form is still alive and its head still resolves to that
+//! same macro, so redefining the macro (or shadowing it locally) expands the form afresh.
+//! Expansion is treated as a pure function of the form: a macro with side effects runs them
+//! once per call site.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::seq::{Seq, SeqKey, WeakSeq};
+use crate::types::MalType;
+
+struct Entry {
+    form: WeakSeq,
+    macro_body: Rc<MalType>,
+    expansion: MalType,
+}
+
+struct Cache {
+    entries: HashMap<SeqKey, Entry>,
+    /// how many entries there can be before the dead ones are dropped
+    limit: usize,
+}
+
+thread_local! {
+    static CACHE: RefCell<Cache> = RefCell::new(Cache {
+        entries: HashMap::new(),
+        limit: 4096,
+    });
+}
+
+/// The cached expansion of the call form `l`, if it was produced by the macro `mac`
+pub fn get(l: &Seq, mac: &MalType) -> Option<MalType> {
+    let MalType::MalFunction { ast, .. } = mac else {
+        return None;
+    };
+    CACHE.with(|c| {
+        c.borrow()
+            .entries
+            .get(&l.key())
+            .filter(|e| e.form.upgrade().is_some_and(|live| Seq::ptr_eq(&live, l)))
+            .filter(|e| Rc::ptr_eq(&e.macro_body, ast))
+            .map(|e| e.expansion.clone())
+    })
+}
+
+pub fn insert(l: &Seq, mac: &MalType, expansion: MalType) {
+    let MalType::MalFunction { ast, .. } = mac else {
+        return;
+    };
+    CACHE.with(|c| {
+        let mut cache = c.borrow_mut();
+        if cache.entries.len() >= cache.limit {
+            cache.entries.retain(|_, e| e.form.strong_count() > 0);
+            cache.limit = (cache.entries.len() * 2).max(4096);
+        }
+        cache.entries.insert(
+            l.key(),
+            Entry {
+                form: l.downgrade(),
+                macro_body: Rc::clone(ast),
+                expansion,
+            },
+        );
+    });
+}
@@ -0,0 +1,188 @@
+//! mal-fmt: re-indents mal source and normalizes the whitespace in it.
+//!
+//! Line breaks stay where they are written, apart from runs of blank lines, which become one, and
+//! closing brackets, which go at the end of the line before them. Everything else about the layout
+//! is decided here: one space between forms on a line, none inside brackets, and each line
+//! indented by where the form it is in starts. The elements of a vector or map line up after its
+//! bracket, and the arguments of a call line up with its first argument when that is on the same
+//! line as the function, or else one past the bracket. Special forms and macros with a body, and
+//! anything named def... or with-..., indent their body by two instead.
+
+use std::io::{Read, Write};
+
+use mal::syntax::{self, Kind, Node};
+
+const USAGE: &str = "\
+Usage: mal-fmt [options] [file...]
+
+Formats each file in place, or stdin to stdout when there are none.
+
+Options:
+  --check     Change nothing, but list the files that aren't formatted and fail if there are any
+  -h, --help  Show this help";
+
+/// The macros of the prelude that take a body
+const BODY_MACROS: [&str; 4] = ["cond", "when", "unless", "comment"];
+
+/// Whether a list headed by name indents the lines after its first by two
+fn has_body(name: &str) -> bool {
+    mal::special_forms().any(|form| form == name)
+        || BODY_MACROS.contains(&name)
+        || name.starts_with("def")
+        || name.starts_with("with-")
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    out: String,
+}
+
+impl Formatter<'_> {
+    /// The column the next character goes in, counting from 0
+    fn column(&self) -> usize {
+        let line_start = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line_start..].chars().count()
+    }
+
+    /// Put the space between two nodes: a space if they are on the same line, or else a line
+    /// break, and a blank line as well if there was one, then indent spaces
+    fn space(&mut self, before: &Node, after: &Node, indent: usize) {
+        let breaks = self.source[before.span.end..after.span.start]
+            .matches('\n')
+            .count();
+        if breaks == 0 && !before.is_comment() {
+            self.out.push(' ');
+        } else {
+            self.out.push_str(if breaks > 1 { "\n\n" } else { "\n" });
+            self.out.push_str(&" ".repeat(indent));
+        }
+    }
+
+    /// Put nodes one after another, with indent for any that start a line
+    fn nodes(&mut self, nodes: &[Node], mut indent: usize, align_second: bool) {
+        for (i, node) in nodes.iter().enumerate() {
+            if i > 0 {
+                self.space(&nodes[i - 1], node, indent);
+                if i == 1 && align_second && self.out.ends_with(' ') {
+                    indent = self.column();
+                }
+            }
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        match &node.kind {
+            Kind::Atom(text) => self.out.push_str(text),
+            Kind::Comment(text) => self.out.push_str(text.trim_end()),
+            Kind::Macro(prefix, items) => {
+                self.out.push_str(prefix);
+                let indent = self.column();
+                self.nodes(items, indent, false);
+            }
+            Kind::Seq(open, items) => {
+                let column = self.column();
+                self.out.push(*open);
+                let (indent, align_second) = match (open, items.first().map(|n| &n.kind)) {
+                    ('(', Some(Kind::Atom(head))) if has_body(head) => (column + 2, false),
+                    ('(', _) => (column + 1, true),
+                    _ => (column + 1, false),
+                };
+                self.nodes(items, indent, align_second);
+                // a comment runs to the end of its line, so the bracket can't follow it there
+                if items.last().is_some_and(Node::is_comment) {
+                    self.out.push('\n');
+                    self.out.push_str(&" ".repeat(indent));
+                }
+                self.out.push(match open {
+                    '(' => ')',
+                    '[' => ']',
+                    _ => '}',
+                });
+            }
+        }
+    }
+}
+
+/// source formatted, or why it can't be read
+fn format(source: &str) -> Result<String, String> {
+    let nodes = syntax::parse(source).map_err(|e| {
+        let (line, column) = syntax::line_col(source, e.at);
+        format!("{}:{}: {}", line, column, e)
+    })?;
+    let mut formatter = Formatter {
+        source,
+        out: String::new(),
+    };
+    formatter.nodes(&nodes, 0, false);
+    if !formatter.out.is_empty() {
+        formatter.out.push('\n');
+    }
+    Ok(formatter.out)
+}
+
+/// Format the file at path in place, or with check just see whether it would change. Returns
+/// whether it was formatted already.
+fn format_file(path: &str, check: bool) -> Result<bool, String> {
+    let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let formatted = format(&source).map_err(|e| format!("{}:{}", path, e))?;
+    if formatted == source {
+        return Ok(true);
+    }
+    if check {
+        println!("{}", path);
+    } else {
+        std::fs::write(path, formatted).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(false)
+}
+
+fn main() {
+    let mut check = false;
+    let mut files = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("Error: Unknown option {}\n\n{}", arg, USAGE);
+                std::process::exit(2);
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    if files.is_empty() {
+        let mut source = String::new();
+        let formatted = std::io::stdin()
+            .read_to_string(&mut source)
+            .map_err(|e| format!("stdin: {}", e))
+            .and_then(|_| format(&source).map_err(|e| format!("stdin:{}", e)));
+        match formatted {
+            Ok(formatted) if check => std::process::exit(if formatted == source { 0 } else { 1 }),
+            Ok(formatted) => {
+                let _ = std::io::stdout().write_all(formatted.as_bytes());
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let mut ok = true;
+    for file in &files {
+        match format_file(file, check) {
+            Ok(formatted) => ok &= formatted || !check,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ok = false;
+            }
+        }
+    }
+    std::process::exit(if ok { 0 } else { 1 });
+}
@@ -0,0 +1,305 @@
+//! mal-lint: finds likely mistakes in mal files without running them.
+//!
+//! It reports forms that aren't balanced, calls to what looks like a special form but isn't one,
+//! such as `let` for `let*`, calls to core functions with a number of arguments they never take,
+//! and let* bindings that nothing uses. Quoted and quasiquoted forms are data, or code yet to be
+//! made, so they aren't checked. A name the file binds itself anywhere, with def!, let*, fn* and
+//! the like, may not mean what it does in core, so calls to it aren't checked either.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+
+use mal::symbol::Sym;
+use mal::syntax::{self, Kind, Node};
+use mal::types::{keyword, MalType};
+
+const USAGE: &str = "\
+Usage: mal-lint [file...]
+
+Checks mal files, or stdin when there are none, without running them, and reports what looks
+wrong in them. Fails if anything was found.
+
+Options:
+  -h, --help  Show this help";
+
+/// The special forms that bind the names in their second element, all of them, or with pairs,
+/// those in even positions
+const BINDING_FORMS: [(&str, bool); 6] = [
+    ("fn*", false),
+    ("catch*", false),
+    ("let*", true),
+    ("letrec*", true),
+    ("dotimes", true),
+    ("doseq", true),
+];
+
+/// The name node is, if it is a symbol
+fn symbol<'a>(node: &Node<'a>) -> Option<&'a str> {
+    match node.kind {
+        Kind::Atom(text) => match MalType::try_from(text.to_string()) {
+            Ok(MalType::Symbol(_)) => Some(text),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The forms in nodes, leaving out the comments
+fn forms<'n, 'a>(nodes: &'n [Node<'a>]) -> Vec<&'n Node<'a>> {
+    nodes.iter().filter(|node| !node.is_comment()).collect()
+}
+
+/// Whether the symbol name appears anywhere in node
+fn mentions(node: &Node, name: &str) -> bool {
+    match &node.kind {
+        Kind::Atom(text) => *text == name,
+        Kind::Comment(_) => false,
+        Kind::Macro(_, items) | Kind::Seq(_, items) => items.iter().any(|n| mentions(n, name)),
+    }
+}
+
+/// Add the names node binds, wherever it binds them, to bound
+fn collect_bound<'a>(node: &Node<'a>, bound: &mut HashSet<&'a str>) {
+    let items = match &node.kind {
+        Kind::Macro(_, items) | Kind::Seq(_, items) => items,
+        _ => return,
+    };
+    if let Kind::Seq('(', _) = node.kind {
+        let list = forms(items);
+        match list.first().and_then(|head| symbol(head)) {
+            Some("def!" | "defmacro!") => bound.extend(list.get(1).and_then(|n| symbol(n))),
+            Some(head) => {
+                let binding = BINDING_FORMS.iter().find(|(form, _)| *form == head);
+                if let (Some((_, pairs)), Some(target)) = (binding, list.get(1)) {
+                    match &target.kind {
+                        Kind::Seq(_, names) => {
+                            let step = if *pairs { 2 } else { 1 };
+                            bound.extend(forms(names).into_iter().step_by(step).filter_map(symbol));
+                        }
+                        _ => bound.extend(symbol(target)),
+                    }
+                }
+            }
+            None => (),
+        }
+    }
+    for item in items {
+        collect_bound(item, bound);
+    }
+}
+
+/// "1 argument", "2 or 3 arguments" and so on, as core says it when a builtin is called wrongly
+fn expected((min, max): (usize, Option<usize>)) -> String {
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    match max {
+        None => format!("at least {} argument{}", min, plural(min)),
+        Some(max) if max == min => format!("{} argument{}", min, plural(min)),
+        Some(max) if max == min + 1 => format!("{} or {} arguments", min, max),
+        Some(max) => format!("{} to {} arguments", min, max),
+    }
+}
+
+struct Linter<'a> {
+    /// the names of the special forms, core and the prelude
+    known: HashSet<String>,
+    /// the fewest and most arguments each core builtin takes
+    arities: HashMap<&'static str, (usize, Option<usize>)>,
+    /// the names the file binds somewhere
+    bound: HashSet<&'a str>,
+    /// what was found, by where in the source
+    findings: Vec<(usize, String)>,
+}
+
+impl<'a> Linter<'a> {
+    fn new() -> Self {
+        let env = mal::repl_env();
+        if let Err(e) = mal::load_prelude(&env) {
+            eprintln!("Error loading prelude: {}", e);
+        }
+        let arity_of = env.get(&Sym::new("arity-of")).unwrap();
+        let arities = mal::core::ns()
+            .into_iter()
+            .filter_map(|(name, f)| match arity_of.apply(vec![f]) {
+                Ok(MalType::HashMap(arity, _)) => {
+                    let int = |key| match arity.get(&keyword(key)) {
+                        Some(MalType::Int(n)) => Some(*n as usize),
+                        _ => None,
+                    };
+                    Some((name, (int("min")?, int("max"))))
+                }
+                _ => None,
+            })
+            .collect();
+        Linter {
+            known: mal::special_forms()
+                // which is part of try*, not a special form of its own
+                .chain(["catch*"])
+                .map(str::to_string)
+                .chain(env.symbols())
+                .collect(),
+            arities,
+            bound: HashSet::new(),
+            findings: Vec::new(),
+        }
+    }
+
+    fn report(&mut self, node: &Node, message: String) {
+        self.findings.push((node.span.start, message));
+    }
+
+    fn check(&mut self, node: &Node<'a>) {
+        match &node.kind {
+            Kind::Macro("'" | "`", _) => (),
+            Kind::Seq('(', items) => {
+                let list = forms(items);
+                match list.first().and_then(|head| symbol(head)) {
+                    Some("quote" | "quasiquote") => (),
+                    // the steps of a threading macro are calls with an argument still to come
+                    Some("->" | "->>") => {
+                        for step in items {
+                            match &step.kind {
+                                Kind::Seq('(', args) => self.check_all(args),
+                                _ => self.check(step),
+                            }
+                        }
+                    }
+                    Some(head) => {
+                        self.call(head, &list);
+                        self.check_all(items);
+                    }
+                    None => self.check_all(items),
+                }
+            }
+            Kind::Macro(_, items) | Kind::Seq(_, items) => self.check_all(items),
+            _ => (),
+        }
+    }
+
+    fn check_all(&mut self, nodes: &[Node<'a>]) {
+        for node in nodes {
+            self.check(node);
+        }
+    }
+
+    /// Check a call of head with the forms of the list, head included
+    fn call(&mut self, head: &str, list: &[&Node<'a>]) {
+        if head == "let*" {
+            self.unused_bindings(list);
+        }
+        if self.bound.contains(head) {
+            return;
+        }
+        if !self.known.contains(head) {
+            let meant = [format!("{}*", head), format!("{}!", head)]
+                .into_iter()
+                .find(|name| mal::special_forms().any(|form| form == name));
+            match meant {
+                Some(meant) => self.report(
+                    list[0],
+                    format!("Unknown special form {}; did you mean {}?", head, meant),
+                ),
+                None if head.ends_with('*') => {
+                    self.report(list[0], format!("Unknown special form {}", head))
+                }
+                None => (),
+            }
+        }
+        if let Some(&(min, max)) = self.arities.get(head) {
+            let n = list.len() - 1;
+            if n < min || max.is_some_and(|max| n > max) {
+                self.report(
+                    list[0],
+                    format!(
+                        "Wrong number of arguments to {}: expected {}, got {}",
+                        head,
+                        expected((min, max)),
+                        n
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Report the names a let* binds that neither its body nor any of its values use
+    fn unused_bindings(&mut self, list: &[&Node<'a>]) {
+        let bindings = match list.get(1).map(|n| &n.kind) {
+            Some(Kind::Seq(_, bindings)) => forms(bindings),
+            _ => return,
+        };
+        let body = &list[2..];
+        for name in bindings.iter().step_by(2) {
+            let Some(s) = symbol(name) else { continue };
+            if s.starts_with('_') {
+                continue;
+            }
+            let used = bindings.iter().skip(1).step_by(2).any(|n| mentions(n, s))
+                || body.iter().any(|n| mentions(n, s));
+            if !used {
+                self.report(name, format!("{} is bound by let* but never used", s));
+            }
+        }
+    }
+}
+
+/// Lint source, printing what is found with name and where it is. Returns whether there was
+/// nothing to report.
+fn lint(name: &str, source: &str) -> bool {
+    let nodes = match syntax::parse(source) {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            let (line, column) = syntax::line_col(source, e.at);
+            println!("{}:{}:{}: {}", name, line, column, e);
+            return false;
+        }
+    };
+    let mut linter = Linter::new();
+    for node in &nodes {
+        collect_bound(node, &mut linter.bound);
+    }
+    for node in &nodes {
+        linter.check(node);
+    }
+    linter.findings.sort();
+    for (at, message) in &linter.findings {
+        let (line, column) = syntax::line_col(source, *at);
+        println!("{}:{}:{}: {}", name, line, column, message);
+    }
+    linter.findings.is_empty()
+}
+
+fn main() {
+    let mut files = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("Error: Unknown option {}\n\n{}", arg, USAGE);
+                std::process::exit(2);
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let mut ok = true;
+    if files.is_empty() {
+        let mut source = String::new();
+        if let Err(e) = std::io::stdin().read_to_string(&mut source) {
+            eprintln!("Error: stdin: {}", e);
+            std::process::exit(1);
+        }
+        ok = lint("stdin", &source);
+    }
+    for file in &files {
+        match std::fs::read_to_string(file) {
+            Ok(source) => ok &= lint(file, &source),
+            Err(e) => {
+                eprintln!("Error: {}: {}", file, e);
+                ok = false;
+            }
+        }
+    }
+    std::process::exit(if ok { 0 } else { 1 });
+}
@@ -0,0 +1,249 @@
+//! mal-test: runs test files in the format of the upstream suite against the interpreter itself,
+//! without the Python harness or a terminal.
+//!
+//! Each line of a test file that isn't a comment is a form, evaluated as the REPL would. The lines
+//! after it starting with `;/` are regexes for what it prints, and a line starting with `;=>` is
+//! the value it should print last. `;>>> soft=True` makes the failures after it soft, and the tests
+//! after `;>>> deferrable=True` and `;>>> optional=True` can be left out.
+
+use std::rc::Rc;
+
+use regex::Regex;
+
+use mal::env::Env;
+use mal::types::MalType;
+use mal::{list, print, read_eval};
+
+const USAGE: &str = "\
+Usage: mal-test [options] file...
+
+Runs mal test files, each in a fresh environment, and reports how many of their tests pass.
+
+Options:
+  --no-deferrable  Stop each file at its deferrable tests
+  --no-optional    Stop each file at its optional tests
+  --hard           Count soft failures as failures
+  --no-prelude     Don't define the functions and macros of the prelude
+  -h, --help       Show this help";
+
+struct Options {
+    deferrable: bool,
+    optional: bool,
+    hard: bool,
+    prelude: bool,
+}
+
+/// A form and what the REPL should show for it
+struct Test {
+    line: usize,
+    form: String,
+    /// a regex for the output, from the `;/` lines
+    out: String,
+    /// the value printed last, with neither it nor out meaning the test is just run
+    ret: String,
+    soft: bool,
+}
+
+enum Entry {
+    Test(Test),
+    Deferrable,
+    Optional,
+}
+
+/// The tests of a file, read the way runtest.py reads them
+fn parse(source: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut soft = false;
+    let mut lines = source.split('\n').enumerate().peekable();
+    while let Some((i, line)) = lines.next() {
+        if line.trim().is_empty() || line.starts_with(";;") {
+            continue;
+        }
+        if let Some(settings) = line.strip_prefix(";>>> ") {
+            for setting in settings.split([';', ',']) {
+                match setting.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+                    Some(("soft", value)) => soft = value == "True",
+                    Some(("deferrable", "True")) => entries.push(Entry::Deferrable),
+                    Some(("optional", "True")) => entries.push(Entry::Optional),
+                    _ => (),
+                }
+            }
+            continue;
+        }
+        if line.starts_with(';') {
+            return Err(format!("Unexpected comment at line {}: {}", i + 1, line));
+        }
+        let mut test = Test {
+            line: i + 1,
+            form: line.to_string(),
+            out: String::new(),
+            ret: String::new(),
+            soft,
+        };
+        while let Some((_, next)) = lines.peek() {
+            if let Some(ret) = next.strip_prefix(";=>") {
+                test.ret = ret.to_string();
+                test.line = lines.next().unwrap().0 + 1;
+                break;
+            } else if let Some(out) = next.strip_prefix(";/") {
+                test.out.push_str(out);
+                test.out.push('\n');
+                test.line = lines.next().unwrap().0 + 1;
+            } else {
+                break;
+            }
+        }
+        // the output only ends in a newline when a value follows it
+        if test.ret.is_empty() && test.out.ends_with('\n') {
+            test.out.pop();
+        }
+        entries.push(Entry::Test(test));
+    }
+    Ok(entries)
+}
+
+/// Whether what the REPL showed for test is what it expects, or an error if its expectation
+/// isn't a regex the regex crate understands
+fn passes(test: &Test, shown: &str) -> Result<bool, regex::Error> {
+    if test.out.is_empty() && test.ret.is_empty() {
+        return Ok(true);
+    }
+    let expected = Regex::new(&format!("(?s).*\n{}{}", test.out, regex::escape(&test.ret)))?;
+    Ok(expected.is_match(&format!("{}\n{}", test.form, shown)))
+}
+
+/// A fresh environment like the one stepA_mal runs files in
+fn test_env(options: &Options) -> Rc<Env> {
+    let env = mal::repl_env();
+    env.set("*ARGV*".to_string(), list!(vec![]));
+    if options.prelude {
+        if let Err(e) = mal::load_prelude(&env) {
+            eprintln!("Error loading prelude: {}", e);
+        }
+    }
+    env
+}
+
+#[derive(Default)]
+struct Counts {
+    passed: usize,
+    failed: usize,
+    soft_failed: usize,
+    skipped: usize,
+}
+
+/// Run the tests of path, printing the ones that fail
+fn run_file(path: &str, options: &Options) -> Result<Counts, String> {
+    let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let entries = parse(&source).map_err(|e| format!("{}: {}", path, e))?;
+    let tests: Vec<&Test> = entries
+        .iter()
+        .take_while(|entry| match entry {
+            Entry::Deferrable => options.deferrable,
+            Entry::Optional => options.optional,
+            Entry::Test(_) => true,
+        })
+        .filter_map(|entry| match entry {
+            Entry::Test(test) => Some(test),
+            _ => None,
+        })
+        .collect();
+    let env = test_env(options);
+    let mut counts = Counts::default();
+    let mut i = 0;
+    while i < tests.len() {
+        // the forms after this one are what the REPL would be sent next, so they are what
+        // readline reads
+        let input: Vec<String> = tests[i + 1..].iter().map(|t| t.form.clone()).collect();
+        let (result, mut shown, read) =
+            mal::core::redirect(&input, || read_eval(&tests[i].form, &env));
+        match result {
+            Ok(val) => shown.push_str(&print(val)),
+            Err(e) => shown.push_str(&format!("Error: {}", e.message())),
+        }
+        // a test whose form was read as input shows nothing of its own, and the last one read
+        // shows what the evaluation goes on to print
+        for test in &tests[i..i + read] {
+            check(test, "", options, &mut counts);
+        }
+        check(tests[i + read], &shown, options, &mut counts);
+        i += read + 1;
+    }
+    Ok(counts)
+}
+
+/// Count whether test passes, printing it if it doesn't
+fn check(test: &Test, shown: &str, options: &Options, counts: &mut Counts) {
+    match passes(test, shown) {
+        Ok(true) => counts.passed += 1,
+        Ok(false) => {
+            let kind = if test.soft && !options.hard {
+                counts.soft_failed += 1;
+                "SOFT FAIL"
+            } else {
+                counts.failed += 1;
+                "FAIL"
+            };
+            println!("{} (line {}): {}", kind, test.line, test.form);
+            println!("    Expected : {:?}", format!("{}{}", test.out, test.ret));
+            println!("    Got      : {:?}", shown);
+        }
+        Err(e) => {
+            counts.skipped += 1;
+            println!("SKIP (line {}): {}", test.line, test.form);
+            println!(
+                "    The expected output isn't a regex mal-test can run: {}",
+                e
+            );
+        }
+    }
+}
+
+fn main() {
+    let mut options = Options {
+        deferrable: true,
+        optional: true,
+        hard: false,
+        prelude: true,
+    };
+    let mut files = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--no-deferrable" => options.deferrable = false,
+            "--no-optional" => options.optional = false,
+            "--hard" => options.hard = true,
+            "--no-prelude" => options.prelude = false,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("Error: Unknown option {}\n\n{}", arg, USAGE);
+                std::process::exit(2);
+            }
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        eprintln!("Error: No test files given\n\n{}", USAGE);
+        std::process::exit(2);
+    }
+
+    let mut ok = true;
+    for file in &files {
+        match run_file(file, &options) {
+            Ok(counts) => {
+                println!(
+                    "{}: {} passed, {} failed, {} soft failures, {} skipped",
+                    file, counts.passed, counts.failed, counts.soft_failed, counts.skipped
+                );
+                ok &= counts.failed == 0;
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ok = false;
+            }
+        }
+    }
+    std::process::exit(if ok { 0 } else { 1 });
+}
@@ -0,0 +1,60 @@
+//! Finding and keeping track of the modules loaded with `require`.
+//!
+//! A module is named by a symbol or string such as `util.strings`, which is the file
+//! `util/strings.mal` in one of the directories of MAL_PATH (separated as PATH is, and the
+//! current directory when it is unset). Each module is loaded at most once per session.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::{self, PathBuf};
+
+use crate::errors::MalErr;
+
+thread_local! {
+    static LOADED: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
+
+/// The directories searched for modules, in order
+fn search_path() -> Vec<PathBuf> {
+    match std::env::var_os("MAL_PATH") {
+        Some(dirs) => std::env::split_paths(&dirs).collect(),
+        None => vec![PathBuf::from(".")],
+    }
+}
+
+/// The absolute path of the first file found for the module
+pub fn find(name: &str) -> Result<PathBuf, MalErr> {
+    let file = format!("{}.mal", name.replace('.', "/"));
+    let dirs = search_path();
+    dirs.iter()
+        .map(|dir| dir.join(&file))
+        .find(|candidate| candidate.is_file())
+        .map(|found| path::absolute(&found).unwrap_or(found))
+        .ok_or_else(|| {
+            MalErr::Generic(format!(
+                "Could not find module {} as {} in MAL_PATH ({})",
+                name,
+                file,
+                dirs.iter()
+                    .map(|d| d.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })
+}
+
+/// Record that a module is loaded, returning false if it already was. Modules are recorded
+/// before they finish loading, so a module that requires itself in a cycle isn't loaded again.
+pub fn start_loading(name: &str) -> bool {
+    LOADED.with(|l| l.borrow_mut().insert(name.to_string()))
+}
+
+/// Forget a module, so that the next require loads it again
+pub fn forget(name: &str) {
+    LOADED.with(|l| l.borrow_mut().remove(name));
+}
+
+/// Forget every module, so that require loads each of them again
+pub fn forget_all() {
+    LOADED.with(|l| l.borrow_mut().clear());
+}
@@ -0,0 +1,259 @@
+//! Pre-eval pass run on every top-level form before it is evaluated.
+//!
+//! - Vector and hash-map literals made only of constants are wrapped in `quote`, so evaluating
+//!   them shares the reader's Rc instead of rebuilding the collection on every pass.
+//! - Calls to the core arithmetic and comparison builtins with integer literal arguments are
+//!   folded into their result, as long as the operator still refers to the builtin and is never
+//!   rebound inside the form.
+//!
+//! Quoted data, forms whose arguments are patterns (case, match) and assert, which reports its
+//! unevaluated form, are left alone, as are calls that may be to a macro: any whose head isn't
+//! bound to a function when the form is optimized, or may be rebound within it, as a defmacro!
+//! earlier in the same form would.
+
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+
+use lazy_static::lazy_static;
+
+use crate::core;
+use crate::env::Env;
+use crate::errors::MalErr;
+use crate::special_forms::SpecialForm;
+use crate::symbol::Sym;
+use crate::types::{symbol, MalType};
+use crate::{list, locations};
+
+const FOLDABLE: [&str; 9] = ["+", "-", "*", "/", "=", "<", "<=", ">", ">="];
+
+type Builtin = fn(Vec<MalType>) -> Result<MalType, MalErr>;
+
+lazy_static! {
+    /// The core builtins of the FOLDABLE names, made once rather than with every form optimized
+    static ref BUILTINS: HashMap<&'static str, Builtin> = core::ns()
+        .into_iter()
+        .filter_map(|(name, f)| match f {
+            MalType::Function(f, _) if FOLDABLE.contains(&name) => Some((name, f)),
+            _ => None,
+        })
+        .collect();
+}
+
+struct Optimizer<'a> {
+    env: &'a Env,
+    // symbols used anywhere other than in call position, which may therefore be rebound
+    rebound: BTreeSet<String>,
+}
+
+pub fn optimize(ast: MalType, env: &Env) -> MalType {
+    let mut rebound = BTreeSet::new();
+    collect_non_head_symbols(&ast, &mut rebound);
+    let optimizer = Optimizer { env, rebound };
+    optimizer.expr(ast)
+}
+
+/// Collect the symbols that ast may bind or rebind: every symbol but the head of a call. The
+/// parameters and bindings of special forms, and the patterns of match, are not calls, so all of
+/// their symbols are collected, heads included.
+fn collect_non_head_symbols(ast: &MalType, symbols: &mut BTreeSet<String>) {
+    match ast {
+        MalType::List(l, _) => {
+            let form = l.first().and_then(SpecialForm::resolve);
+            let binds = |i: usize| match form {
+                Some(
+                    SpecialForm::Fn
+                    | SpecialForm::Let
+                    | SpecialForm::LetRec
+                    | SpecialForm::DoTimes
+                    | SpecialForm::DoSeq,
+                ) => i == 1,
+                Some(SpecialForm::Match) => i >= 2 && i.is_multiple_of(2),
+                _ => false,
+            };
+            for (i, el) in l.iter().enumerate() {
+                if binds(i) {
+                    collect_symbols(el, symbols);
+                } else if i > 0 || !matches!(el, MalType::Symbol(_)) {
+                    collect_non_head_symbols(el, symbols);
+                }
+            }
+        }
+        MalType::Vector(l, _) => l
+            .iter()
+            .for_each(|el| collect_non_head_symbols(el, symbols)),
+        MalType::HashMap(hm, _) => hm.iter().for_each(|(k, v)| {
+            collect_non_head_symbols(k, symbols);
+            collect_non_head_symbols(v, symbols);
+        }),
+        _ => collect_symbols(ast, symbols),
+    }
+}
+
+/// Collect every symbol in ast, wherever it is
+fn collect_symbols(ast: &MalType, symbols: &mut BTreeSet<String>) {
+    match ast {
+        MalType::Symbol(s) => {
+            symbols.insert(s.to_string());
+        }
+        MalType::List(l, _) | MalType::Vector(l, _) => {
+            l.iter().for_each(|el| collect_symbols(el, symbols))
+        }
+        MalType::HashMap(hm, _) => hm.iter().for_each(|(k, v)| {
+            collect_symbols(k, symbols);
+            collect_symbols(v, symbols);
+        }),
+        _ => (),
+    }
+}
+
+/// Self-evaluating values: scalars, and collections containing only scalars
+fn is_constant(ast: &MalType) -> bool {
+    match ast {
+        MalType::Nil
+        | MalType::Bool(_)
+        | MalType::Int(_)
+        | MalType::Str(_)
+        | MalType::Keyword(_) => true,
+        MalType::Vector(l, _) => l.iter().all(is_constant),
+        MalType::HashMap(hm, _) => hm.iter().all(|(k, v)| is_constant(k) && is_constant(v)),
+        _ => false,
+    }
+}
+
+impl Optimizer<'_> {
+    /// Optimize a form in evaluated position
+    fn expr(&self, ast: MalType) -> MalType {
+        match ast {
+            MalType::Vector(..) | MalType::HashMap(..) if is_constant(&ast) => {
+                list![symbol("quote"), ast]
+            }
+            MalType::Vector(..) | MalType::HashMap(..) => self.elements(ast),
+            MalType::List(ref l, _) if !l.is_empty() => self.list(ast.clone(), l),
+            _ => ast,
+        }
+    }
+
+    /// Optimize the elements of a collection without hoisting the collection itself
+    fn elements(&self, ast: MalType) -> MalType {
+        match ast {
+            MalType::Vector(l, meta) => {
+                MalType::Vector(l.iter().map(|el| self.expr(el.clone())).collect(), meta)
+            }
+            MalType::HashMap(hm, meta) => MalType::HashMap(
+                Rc::new(
+                    hm.iter()
+                        .map(|(k, v)| (k.clone(), self.expr(v.clone())))
+                        .collect(),
+                ),
+                meta,
+            ),
+            MalType::List(ref l, ref meta) => locations::copy(
+                &ast,
+                MalType::List(
+                    l.iter().map(|el| self.expr(el.clone())).collect(),
+                    Rc::clone(meta),
+                ),
+            ),
+            _ => ast,
+        }
+    }
+
+    fn list(&self, ast: MalType, l: &[MalType]) -> MalType {
+        match SpecialForm::resolve(&l[0]) {
+            Some(SpecialForm::Quote) => match l {
+                [_, value @ (MalType::Nil | MalType::Bool(_) | MalType::Int(_) | MalType::Str(_))] => {
+                    value.clone()
+                }
+                _ => ast,
+            },
+            Some(
+                SpecialForm::Quasiquote
+                | SpecialForm::QuasiquoteExpand
+                | SpecialForm::MacroExpand
+                | SpecialForm::Case
+                | SpecialForm::Match
+                | SpecialForm::Assert,
+            ) => ast,
+            // binding vectors and parameter lists must keep their shape
+            Some(_) => {
+                let mut v = vec![l[0].clone()];
+                v.extend(l[1..].iter().map(|el| match el {
+                    MalType::Vector(..) | MalType::HashMap(..) => self.elements(el.clone()),
+                    _ => self.expr(el.clone()),
+                }));
+                locations::copy(&ast, list!(v))
+            }
+            None if !self.calls_function(&l[0]) => ast,
+            None => {
+                let call: Vec<MalType> = l.iter().map(|el| self.expr(el.clone())).collect();
+                self.fold(&call)
+                    .unwrap_or_else(|| locations::copy(&ast, list!(call)))
+            }
+        }
+    }
+
+    /// Whether a call with this head is sure to be a function call, and not a macro call, when it
+    /// is evaluated. A head that isn't a symbol is evaluated, so it can't name a macro.
+    fn calls_function(&self, head: &MalType) -> bool {
+        match head {
+            MalType::Symbol(s) => {
+                !self.rebound.contains(s.as_str())
+                    && matches!(
+                        self.env.lookup(s),
+                        Some(
+                            MalType::Function(..)
+                                | MalType::MalFunction {
+                                    is_macro: false,
+                                    ..
+                                }
+                        )
+                    )
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether the symbol is still bound to the core builtin of the same name
+    fn is_builtin(&self, op: &Sym) -> bool {
+        match (self.env.lookup(op), BUILTINS.get(op.as_str())) {
+            (Some(MalType::Function(f, _)), Some(g)) => std::ptr::fn_addr_eq(f, *g),
+            _ => false,
+        }
+    }
+
+    fn fold(&self, call: &[MalType]) -> Option<MalType> {
+        let (sym, op) = match &call[0] {
+            MalType::Symbol(s) if FOLDABLE.contains(&s.as_str()) => (s, s.as_str()),
+            _ => return None,
+        };
+        let args = call[1..]
+            .iter()
+            .map(|a| match a {
+                MalType::Int(i) => Some(*i),
+                _ => None,
+            })
+            .collect::<Option<Vec<i64>>>()?;
+        if self.rebound.contains(op) || !self.is_builtin(sym) {
+            return None;
+        }
+        match (op, args.as_slice()) {
+            ("+" | "-" | "*" | "/", [first, rest @ ..]) if !rest.is_empty() => {
+                let int = rest.iter().try_fold(*first, |acc, x| match op {
+                    "+" => acc.checked_add(*x),
+                    "-" => acc.checked_sub(*x),
+                    "*" => acc.checked_mul(*x),
+                    _ => acc.checked_div(*x),
+                })?;
+                Some(MalType::Int(int))
+            }
+            ("=" | "<" | "<=" | ">" | ">=", [a, b]) => Some(MalType::Bool(match op {
+                "=" => a == b,
+                "<" => a < b,
+                "<=" => a <= b,
+                ">" => a > b,
+                _ => a >= b,
+            })),
+            _ => None,
+        }
+    }
+}
@@ -2,7 +2,7 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use std::ops::Deref;
 
-use crate::{core::KEYWORD_PREFIX, types::MalType};
+use crate::types::MalType;
 use itertools::Itertools;
 
 lazy_static! {
@@ -11,7 +11,7 @@ lazy_static! {
 // The reverse of reader::read_str_transform
 fn pr_str_transform(s: &str) -> String {
     let t = ESCAPE_RE
-        .replace_all(&s, |caps: &regex::Captures| {
+        .replace_all(s, |caps: &regex::Captures| {
             format!("\\{}", if &caps[1] == "\n" { "n" } else { &caps[1] })
         })
         .to_string();
@@ -19,27 +19,26 @@ fn pr_str_transform(s: &str) -> String {
 }
 
 impl MalType {
-    pub fn pr_str(self: &Self, print_readably: bool) -> String {
+    pub fn pr_str(&self, print_readably: bool) -> String {
         match self {
             MalType::Nil => "nil".to_string(),
             MalType::Bool(b) => b.to_string(),
             MalType::Int(i) => i.to_string(),
             MalType::Str(s) => {
-                if s.starts_with(KEYWORD_PREFIX) {
-                    format!(":{}", &s[2..])
-                } else if print_readably {
+                if print_readably {
                     pr_str_transform(s)
                 } else {
                     s.to_string()
                 }
             }
+            MalType::Keyword(k) => format!(":{}", k),
             MalType::Symbol(s) => s.to_string(),
             MalType::List(l, _) => pr_list(l.deref(), "(", ")", print_readably, " "),
             MalType::Vector(l, _) => pr_list(l.deref(), "[", "]", print_readably, " "),
             MalType::HashMap(hm, _) => pr_list(
                 &hm.iter()
                     .flat_map(|(k, v)| vec![k.clone(), v.clone()])
-                    .collect(),
+                    .collect::<Vec<_>>(),
                 "{",
                 "}",
                 print_readably,
@@ -47,13 +46,25 @@ impl MalType {
             ),
             MalType::Function(f, _) => format!("#<fn {:?}>", f),
             MalType::MalFunction { .. } => "#<function>".to_string(),
-            MalType::Atom(a) => format!("(atom {})", a.borrow().to_string()),
+            MalType::Atom(a) => format!("(atom {})", a.borrow()),
+            MalType::Reduced(v) => format!("(reduced {})", v),
+            MalType::Bytes(b) => format!("(bytes [{}])", b.iter().join(" ")),
+            MalType::Pattern(p) if print_readably => {
+                format!("(re-pattern {})", pr_str_transform(p.0.as_str()))
+            }
+            MalType::Pattern(p) => p.0.as_str().to_string(),
+            #[cfg(feature = "sync")]
+            MalType::Thread(h) if h.future => "#<future>".to_string(),
+            #[cfg(feature = "sync")]
+            MalType::Thread(_) => "#<thread>".to_string(),
+            #[cfg(feature = "sync")]
+            MalType::Channel(_) => "#<channel>".to_string(),
         }
     }
 }
 
 pub fn pr_list(
-    seq: &Vec<MalType>,
+    seq: &[MalType],
     open: &str,
     close: &str,
     print_readably: bool,
@@ -0,0 +1,95 @@
+//! Call counts and wall time per function, collected by eval between (profile-start) and
+//! (profile-stop) and printed by (profile-report). Functions are named after the symbol they
+//! were called through. Time is inclusive: it covers everything a call did until it returned
+//! or tail-called into another function.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::types::MalType;
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static STATS: RefCell<HashMap<String, (u64, Duration)>> = RefCell::new(HashMap::new());
+}
+
+pub fn enabled() -> bool {
+    ENABLED.with(|e| e.get())
+}
+
+pub fn start() {
+    STATS.with(|s| s.borrow_mut().clear());
+    ENABLED.with(|e| e.set(true));
+}
+
+pub fn stop() {
+    ENABLED.with(|e| e.set(false));
+}
+
+/// The name a call is reported under
+pub fn name(head: &MalType) -> String {
+    match head {
+        MalType::Symbol(s) => s.to_string(),
+        _ => "<anonymous>".to_string(),
+    }
+}
+
+pub fn record(name: &str, elapsed: Duration) {
+    STATS.with(|s| {
+        let mut stats = s.borrow_mut();
+        let entry = stats.entry(name.to_string()).or_default();
+        entry.0 += 1;
+        entry.1 += elapsed;
+    });
+}
+
+/// The mal function currently running in an eval frame; its time is recorded when the
+/// frame tail-calls another function or returns
+#[derive(Default)]
+pub struct Frame(Option<(String, Instant)>);
+
+impl Frame {
+    pub fn enter(&mut self, name: String) {
+        self.finish();
+        self.0 = Some((name, Instant::now()));
+    }
+
+    fn finish(&mut self) {
+        if let Some((name, started)) = self.0.take() {
+            record(&name, started.elapsed());
+        }
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Format the collected statistics, slowest functions first
+pub fn report() -> String {
+    let mut rows: Vec<(String, u64, Duration)> = STATS.with(|s| {
+        s.borrow()
+            .iter()
+            .map(|(name, (calls, total))| (name.clone(), *calls, *total))
+            .collect()
+    });
+    rows.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    let width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0).max(8);
+    let mut out = format!(
+        "{:<width$} {:>10} {:>12} {:>12}",
+        "function", "calls", "total ms", "avg us"
+    );
+    for (name, calls, total) in rows {
+        out.push_str(&format!(
+            "\n{:<width$} {:>10} {:>12.3} {:>12.3}",
+            name,
+            calls,
+            total.as_secs_f64() * 1e3,
+            total.as_secs_f64() * 1e6 / calls as f64
+        ));
+    }
+    out
+}
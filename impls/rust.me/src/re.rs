@@ -0,0 +1,125 @@
+//! Regular expression builtins, using the regex crate's syntax.
+//!
+//! Every function takes either a pattern made by `re-pattern` or a string, which is compiled on
+//! each call. A match is returned as the matched string when the pattern has no groups, and
+//! otherwise as a vector of the whole match followed by each group (nil for groups that took
+//! no part in the match).
+
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use regex::{Captures, Regex};
+
+use crate::errors::MalErr;
+use crate::types::MalType;
+use crate::{list, vector};
+
+/// A compiled regex. Patterns are equal and ordered by their source text.
+#[derive(Debug)]
+pub struct Pattern(pub Regex);
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+impl Eq for Pattern {}
+impl PartialOrd for Pattern {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Pattern {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.as_str().cmp(other.0.as_str())
+    }
+}
+
+fn compile(source: &str) -> Result<Regex, MalErr> {
+    Ok(Regex::new(source)?)
+}
+
+fn regex(value: &MalType) -> Result<Rc<Pattern>, MalErr> {
+    match value {
+        MalType::Pattern(p) => Ok(Rc::clone(p)),
+        MalType::Str(s) => Ok(Rc::new(Pattern(compile(s)?))),
+        _ => Err(MalErr::type_error("a regex or string", value)),
+    }
+}
+
+fn text(value: &MalType) -> Result<&str, MalErr> {
+    match value {
+        MalType::Str(s) => Ok(s),
+        _ => Err(MalErr::type_error("a string", value)),
+    }
+}
+
+/// The regex and string of a (re-* re s) call
+fn args(args: &[MalType]) -> Result<(Rc<Pattern>, &str), MalErr> {
+    Ok((regex(&args[0])?, text(&args[1])?))
+}
+
+fn groups(caps: &Captures) -> MalType {
+    if caps.len() == 1 {
+        return MalType::Str(caps[0].to_string());
+    }
+    vector!(caps
+        .iter()
+        .map(|m| m.map_or(MalType::Nil, |m| MalType::Str(m.as_str().to_string())))
+        .collect())
+}
+
+/// (re-pattern s) compiles s; a pattern is returned as is
+pub fn pattern(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    regex(&args[0]).map(MalType::Pattern)
+}
+
+/// (re-find re s) is the first match of re in s, or nil
+pub fn find(a: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (re, s) = args(&a)?;
+    Ok(re.0.captures(s).map_or(MalType::Nil, |caps| groups(&caps)))
+}
+
+/// (re-matches re s) is the match of re against the whole of s, or nil
+pub fn matches(a: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (re, s) = args(&a)?;
+    // anchoring the pattern, rather than checking the span of a match, makes alternatives
+    // such as a|ab try every way of matching the whole string
+    let whole = compile(&format!(r"\A(?:{})\z", re.0.as_str()))?;
+    Ok(whole.captures(s).map_or(MalType::Nil, |caps| groups(&caps)))
+}
+
+/// (re-seq re s) is a list of the successive matches of re in s
+pub fn seq(a: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (re, s) = args(&a)?;
+    Ok(list!(re
+        .0
+        .captures_iter(s)
+        .map(|caps| groups(&caps))
+        .collect()))
+}
+
+/// (re-replace re s replacement) replaces every match of re in s. The replacement is either a
+/// string, in which $1 or ${name} stand for groups, or a function called with each match (as
+/// re-find returns it) that returns the string to put in its place.
+pub fn replace(a: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (re, s) = args(&a)?;
+    let replacement = &a[2];
+    match replacement {
+        MalType::Function(..) | MalType::MalFunction { .. } => {
+            let mut out = String::new();
+            let mut last = 0;
+            for caps in re.0.captures_iter(s) {
+                let m = caps.get(0).unwrap();
+                out.push_str(&s[last..m.start()]);
+                out.push_str(text(&replacement.apply(vec![groups(&caps)])?)?);
+                last = m.end();
+            }
+            out.push_str(&s[last..]);
+            Ok(MalType::Str(out))
+        }
+        _ => Ok(MalType::Str(
+            re.0.replace_all(s, text(replacement)?).into_owned(),
+        )),
+    }
+}
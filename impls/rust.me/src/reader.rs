@@ -1,24 +1,60 @@
-use crate::core::KEYWORD_PREFIX;
-use crate::errors::MalErr;
-use crate::types::MalType;
+use crate::errors::{Location, MalErr};
+use crate::locations;
+use crate::types::{keyword, symbol, MalType};
 use crate::{hashmap, list, vector};
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::ops::Range;
+use std::rc::Rc;
 
 type Token = String;
+/// the line and column of a token, both counting from 1
+type Position = (usize, usize);
 
-pub struct Reader {
+struct Reader {
     tokens: Vec<Token>,
+    /// where each token is in the text read
+    spans: Vec<Range<usize>>,
+    positions: Vec<Position>,
     position: usize,
+    /// the file being read, when reading one
+    file: Option<Rc<str>>,
 }
 
 impl Reader {
-    /// create a new reader instance with tokens
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self {
-            tokens,
+    /// create a new reader instance with the tokens of s, leaving out empty ones when skip_empty
+    fn new(s: &str, skip_empty: bool) -> Self {
+        let mut reader = Self {
+            tokens: Vec::new(),
+            spans: Vec::new(),
+            positions: Vec::new(),
             position: 0,
+            file: None,
+        };
+        for (span, position, token) in tokens(s) {
+            if !(skip_empty && token.is_empty()) {
+                reader.tokens.push(token);
+                reader.spans.push(span);
+                reader.positions.push(position);
+            }
         }
+        reader
+    }
+
+    /// where the token at the current position is, when reading a file
+    fn location(&self) -> Option<Location> {
+        let file = self.file.as_ref()?;
+        let &(line, column) = self.positions.get(self.position)?;
+        Some(Location {
+            file: Rc::clone(file),
+            line,
+            column,
+        })
+    }
+
+    /// a read error at the token at position
+    fn error(&self, message: &str, position: usize) -> MalErr {
+        MalErr::ReadErr(message.to_string(), self.spans.get(position).cloned())
     }
 
     /// returns the token at the current position and increments the position
@@ -33,36 +69,95 @@ impl Reader {
         Ok(self
             .tokens
             .get(self.position)
-            .ok_or(MalErr::ReadErr("Reader position out of bounds".to_string()))?
+            .ok_or(MalErr::ReadErr(
+                "Reader position out of bounds".to_string(),
+                None,
+            ))?
             .to_string())
     }
 }
 
-/// This function will call tokenize and then create a new Reader object instance with the tokens.
+/// This function will tokenize s and create a new Reader object instance with the tokens.
 /// Then it will call read_form with the Reader instance.
 pub fn read_str(s: String) -> Result<MalType, MalErr> {
-    let mut reader = Reader::new(tokenize(s));
+    let mut reader = Reader::new(&s, false);
     read_form(&mut reader)
 }
 
 lazy_static! {
+    // #! starts a comment as ; does, so that a script can begin with a shebang line
     static ref RE: Regex =
-        Regex::new(r#"[\s,]*(~@|[\[\]{}()'`~^@]|"(?:\\.|[^\\"])*"?|;.*|[^\s\[\]{}('"`,;)]*)"#)
+        Regex::new(r#"[\s,]*(~@|[\[\]{}()'`~^@]|"(?:\\.|[^\\"])*"?|(?:;|#!).*|[^\s\[\]{}('"`,;)]*)"#)
             .unwrap();
 }
-/// This function will take a single string and return an array/list of all the tokens (strings) in it.
-fn tokenize(s: String) -> Vec<Token> {
-    let tokens: Vec<String> = RE
-        .captures_iter(&s.trim())
-        .filter_map(|caps| {
-            if caps[1].starts_with(";") {
-                None
-            } else {
-                Some(String::from(&caps[1]))
-            }
-        })
-        .collect();
-    tokens
+
+fn is_comment(token: &str) -> bool {
+    token.starts_with(';') || token.starts_with("#!")
+}
+
+/// The tokens of s, each with where it is in s and the line and column (both counting from 1) it
+/// starts at, skipping comments
+fn tokens(s: &str) -> impl Iterator<Item = (Range<usize>, Position, Token)> + '_ {
+    let trimmed = s.trim();
+    let lead = s.len() - s.trim_start().len();
+    let (mut line, mut line_start, mut seen) = (1, 0, 0);
+    RE.captures_iter(trimmed).filter_map(move |caps| {
+        let token = caps.get(1).unwrap();
+        let at = lead + token.start();
+        let between = &s[seen..at];
+        line += between.matches('\n').count();
+        if let Some(i) = between.rfind('\n') {
+            line_start = seen + i + 1;
+        }
+        seen = at;
+        if is_comment(token.as_str()) {
+            None
+        } else {
+            let column = s[line_start..at].chars().count() + 1;
+            Some((
+                at..lead + token.end(),
+                (line, column),
+                String::from(token.as_str()),
+            ))
+        }
+    })
+}
+
+/// Where each token of s is in it, comments included, for highlighting a line as it's typed
+pub fn spans(s: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    RE.captures_iter(s)
+        .filter_map(|caps| caps.get(1))
+        .map(|token| token.range())
+        .filter(|range| !range.is_empty())
+}
+
+/// The forms of a whole source file, read one at a time so that each can be evaluated before
+/// the next is read. Each comes with the line and column it starts at, and reading stops at the
+/// first error.
+pub struct Forms {
+    reader: Reader,
+}
+
+impl Iterator for Forms {
+    type Item = (Position, Result<MalType, MalErr>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = &mut self.reader;
+        let position = *reader.positions.get(reader.position)?;
+        let form = read_form(reader);
+        if form.is_err() {
+            reader.position = reader.tokens.len();
+        }
+        Some((position, form))
+    }
+}
+
+/// Read the forms of s in turn, as load-file does. Given the name of the file s is from, the lists
+/// read remember where they are in it, for reporting errors.
+pub fn read_forms(s: &str, file: Option<&str>) -> Forms {
+    let mut reader = Reader::new(s, true);
+    reader.file = file.map(Rc::from);
+    Forms { reader }
 }
 
 /// This function will peek at the first token in the Reader object and switch on the first character of that token.
@@ -72,54 +167,35 @@ fn tokenize(s: String) -> Vec<Token> {
 fn read_form(reader: &mut Reader) -> Result<MalType, MalErr> {
     match reader.peek()?.as_str() {
         "(" => read_list(reader, ")"),
-        ")" => return Err(MalErr::ReadErr("Unexpected ')'".to_string())),
+        ")" => Err(reader.error("Unexpected ')'", reader.position)),
         "[" => read_list(reader, "]"),
-        "]" => return Err(MalErr::ReadErr("Unexpected ']'".to_string())),
+        "]" => Err(reader.error("Unexpected ']'", reader.position)),
         "{" => read_list(reader, "}"),
-        "}" => return Err(MalErr::ReadErr("Unexpected '}'".to_string())),
+        "}" => Err(reader.error("Unexpected '}'", reader.position)),
         "@" => {
             reader.next()?;
-            Ok(list!(
-                MalType::Symbol("deref".to_string()),
-                read_form(reader)?
-            ))
+            Ok(list!(symbol("deref"), read_form(reader)?))
         }
         "'" => {
             reader.next()?;
-            Ok(list!(
-                MalType::Symbol("quote".to_string()),
-                read_form(reader)?
-            ))
+            Ok(list!(symbol("quote"), read_form(reader)?))
         }
         "`" => {
             reader.next()?;
-            Ok(list!(
-                MalType::Symbol("quasiquote".to_string()),
-                read_form(reader)?
-            ))
+            Ok(list!(symbol("quasiquote"), read_form(reader)?))
         }
         "~" => {
             reader.next()?;
-            Ok(list!(
-                MalType::Symbol("unquote".to_string()),
-                read_form(reader)?
-            ))
+            Ok(list!(symbol("unquote"), read_form(reader)?))
         }
         "~@" => {
             reader.next()?;
-            Ok(list!(
-                MalType::Symbol("splice-unquote".to_string()),
-                read_form(reader)?
-            ))
+            Ok(list!(symbol("splice-unquote"), read_form(reader)?))
         }
         "^" => {
             reader.next()?;
             let meta = read_form(reader)?;
-            Ok(list![
-                MalType::Symbol("with-meta".to_string()),
-                read_form(reader)?,
-                meta
-            ])
+            Ok(list![symbol("with-meta"), read_form(reader)?, meta])
         }
         _ => read_atom(reader),
     }
@@ -130,14 +206,17 @@ fn read_form(reader: &mut Reader) -> Result<MalType, MalErr> {
 /// It accumulates the results into a List type.
 fn read_list(reader: &mut Reader, end: &str) -> Result<MalType, MalErr> {
     let mut list: Vec<MalType> = vec![];
+    let at = reader.location();
+    let opening = reader.position;
 
     // skip opening brace
     reader.next()?;
 
     loop {
+        // reported at the bracket that is never closed
         let token = match reader.peek() {
             Ok(t) => t,
-            _ => return Err(MalErr::ReadErr("Unexpected EOF".to_string())),
+            _ => return Err(reader.error("Unexpected EOF", opening)),
         };
         if token == end {
             break;
@@ -150,10 +229,16 @@ fn read_list(reader: &mut Reader, end: &str) -> Result<MalType, MalErr> {
     reader.next()?;
 
     match end {
-        ")" => Ok(list!(list)),
+        ")" => {
+            let list = list!(list);
+            if let (MalType::List(l, _), Some(at)) = (&list, at) {
+                locations::set(l, at);
+            }
+            Ok(list)
+        }
         "]" => Ok(vector!(list)),
         "}" => hashmap!(list),
-        _ => Err(MalErr::ReadErr("Unknown end value".to_string())),
+        _ => Err(MalErr::ReadErr("Unknown end value".to_string(), None)),
     }
 }
 
@@ -161,7 +246,10 @@ fn read_list(reader: &mut Reader, end: &str) -> Result<MalType, MalErr> {
 /// Initially, you can just implement numbers (integers) and symbols.
 fn read_atom(reader: &mut Reader) -> Result<MalType, MalErr> {
     let token = reader.next()?;
-    MalType::try_from(token)
+    MalType::try_from(token).map_err(|e| match e {
+        MalErr::ReadErr(message, None) => reader.error(&message, reader.position - 1),
+        e => e,
+    })
 }
 
 lazy_static! {
@@ -178,15 +266,15 @@ impl TryFrom<Token> for MalType {
             "false" => Ok(MalType::Bool(false)),
             _ => {
                 if INT_RE.is_match(&token) {
-                    Ok(MalType::Int(token.parse().unwrap()))
+                    Ok(MalType::Int(token.parse()?))
                 } else if STR_RE.is_match(&token) {
                     Ok(MalType::Str(read_str_transform(&token)))
                 } else if token.starts_with('"') {
-                    Err(MalErr::ReadErr("unbalanced string".to_string()))
-                } else if token.starts_with(':') {
-                    Ok(MalType::Str(format!("{}{}", KEYWORD_PREFIX, &token[1..])))
+                    Err(MalErr::ReadErr("unbalanced string".to_string(), None))
+                } else if let Some(kw) = token.strip_prefix(':') {
+                    Ok(keyword(kw))
                 } else {
-                    Ok(MalType::Symbol(token))
+                    Ok(symbol(&token))
                 }
             }
         }
@@ -203,8 +291,8 @@ fn read_str_transform(s: &str) -> String {
     // a backslash followed by "n" is translated into a newline,
     // and a backslash followed by another backslash is translated into a single backslash
     UNESCAPE_RE
-        .replace_all(&t, |caps: &regex::Captures| {
-            format!("{}", if &caps[1] == "n" { "\n" } else { &caps[1] })
+        .replace_all(t, |caps: &regex::Captures| {
+            (if &caps[1] == "n" { "\n" } else { &caps[1] }).to_string()
         })
         .to_string()
 }
@@ -0,0 +1,172 @@
+//! The elements of a list or vector.
+//!
+//! A Seq is a view of a range of a shared slice, so the rest of a list, or any part of one, is
+//! another view of the same elements rather than a copy of them. Building a Seq from a Vec moves
+//! the elements into the shared slice once; after that, cloning, taking the rest and slicing
+//! never clone an element. Adding to the front or back, as cons and conj do, still has to copy.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Bound, Deref, RangeBounds};
+use std::rc::{Rc, Weak};
+
+use crate::types::MalType;
+
+#[derive(Clone)]
+pub struct Seq {
+    items: Rc<[MalType]>,
+    start: usize,
+    end: usize,
+}
+
+/// What identifies a Seq while it is alive: where its shared slice is, and the range of it seen
+pub type SeqKey = (*const MalType, usize, usize);
+
+/// A Seq that doesn't keep its elements alive, as an Rc has a Weak
+pub struct WeakSeq {
+    items: Weak<[MalType]>,
+    start: usize,
+    end: usize,
+}
+
+impl Seq {
+    pub fn as_slice(&self) -> &[MalType] {
+        &self.items[self.start..self.end]
+    }
+
+    /// All but the first element, or nothing if there are none
+    pub fn rest(&self) -> Seq {
+        Seq {
+            items: Rc::clone(&self.items),
+            start: (self.start + 1).min(self.end),
+            end: self.end,
+        }
+    }
+
+    /// The elements in range, which, as in indexing a slice, must be within this one
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Seq {
+        let start = match range.start_bound() {
+            Bound::Included(&i) => i,
+            Bound::Excluded(&i) => i + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&i) => i + 1,
+            Bound::Excluded(&i) => i,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(
+            start <= end && end <= self.len(),
+            "range {}..{} out of a seq of {}",
+            start,
+            end,
+            self.len()
+        );
+        Seq {
+            items: Rc::clone(&self.items),
+            start: self.start + start,
+            end: self.start + end,
+        }
+    }
+
+    /// Whether a and b are views of the same range of the same elements, not just equal ones
+    pub fn ptr_eq(a: &Seq, b: &Seq) -> bool {
+        Rc::ptr_eq(&a.items, &b.items) && a.start == b.start && a.end == b.end
+    }
+
+    pub fn key(&self) -> SeqKey {
+        (
+            Rc::as_ptr(&self.items) as *const MalType,
+            self.start,
+            self.end,
+        )
+    }
+
+    pub fn downgrade(&self) -> WeakSeq {
+        WeakSeq {
+            items: Rc::downgrade(&self.items),
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
+impl WeakSeq {
+    pub fn upgrade(&self) -> Option<Seq> {
+        Some(Seq {
+            items: self.items.upgrade()?,
+            start: self.start,
+            end: self.end,
+        })
+    }
+
+    pub fn strong_count(&self) -> usize {
+        self.items.strong_count()
+    }
+}
+
+impl Default for Seq {
+    fn default() -> Self {
+        Seq::from(Vec::new())
+    }
+}
+
+impl From<Vec<MalType>> for Seq {
+    fn from(v: Vec<MalType>) -> Self {
+        let end = v.len();
+        Seq {
+            items: Rc::from(v),
+            start: 0,
+            end,
+        }
+    }
+}
+
+impl FromIterator<MalType> for Seq {
+    fn from_iter<I: IntoIterator<Item = MalType>>(iter: I) -> Self {
+        Seq::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl Deref for Seq {
+    type Target = [MalType];
+
+    fn deref(&self) -> &[MalType] {
+        self.as_slice()
+    }
+}
+
+impl<'a> IntoIterator for &'a Seq {
+    type Item = &'a MalType;
+    type IntoIter = std::slice::Iter<'a, MalType>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for Seq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for Seq {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for Seq {}
+
+impl PartialOrd for Seq {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Seq {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_slice().cmp(other.as_slice())
+    }
+}
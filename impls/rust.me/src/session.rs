@@ -0,0 +1,132 @@
+//! Saving the definitions made at the REPL as mal source, for `:save` and `:restore`.
+//!
+//! Each definition is written as a def! (or defmacro!) of a form that evaluates to its value
+//! again. Data is written out much as it prints. A function is written as the fn* it was made
+//! from, which only reproduces it when it was made at the top level and so closes over the REPL
+//! environment itself, and a builtin defined under another name is written as its name in core.
+//! Anything else, such as a closure over local bindings or a thread, is left out with a warning.
+
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use itertools::Itertools;
+use mal::env::Env;
+use mal::errors::MalErr;
+use mal::types::MalType;
+
+/// Whether ast can be printed and read back as the same form
+fn readable(ast: &MalType) -> bool {
+    match ast {
+        MalType::Nil
+        | MalType::Bool(_)
+        | MalType::Int(_)
+        | MalType::Str(_)
+        | MalType::Keyword(_)
+        | MalType::Symbol(_) => true,
+        MalType::List(l, _) | MalType::Vector(l, _) => l.iter().all(readable),
+        MalType::HashMap(hm, _) => hm.iter().all(|(k, v)| readable(k) && readable(v)),
+        _ => false,
+    }
+}
+
+struct Saver<'a> {
+    env: &'a Rc<Env>,
+    /// the builtins of core by name, in order
+    builtins: Vec<(&'static str, MalType)>,
+}
+
+impl Saver<'_> {
+    /// A form that evaluates to value, or why there isn't one
+    fn source(&self, value: &MalType) -> Result<String, String> {
+        let source = match value {
+            MalType::Nil
+            | MalType::Bool(_)
+            | MalType::Int(_)
+            | MalType::Str(_)
+            | MalType::Keyword(_)
+            | MalType::Bytes(_)
+            | MalType::Pattern(_) => value.pr_str(true),
+            MalType::Symbol(s) => format!("(quote {})", s),
+            MalType::List(l, _) => format!("(list{})", self.sources(l.iter())?),
+            MalType::Vector(l, _) => format!("[{}]", self.sources(l.iter())?.trim_start()),
+            MalType::HashMap(hm, _) => format!(
+                "{{{}}}",
+                self.sources(hm.iter().flat_map(|(k, v)| [k, v]))?
+                    .trim_start()
+            ),
+            MalType::Atom(a) => format!("(atom {})", self.source(&a.borrow())?),
+            MalType::Function(f, _) => self
+                .builtins
+                .iter()
+                .find(|(_, builtin)| match builtin {
+                    MalType::Function(g, _) => *g as usize == *f as usize,
+                    _ => false,
+                })
+                .map(|(name, _)| name.to_string())
+                .ok_or("it is a builtin without a name in core")?,
+            MalType::MalFunction {
+                params, ast, env, ..
+            } => {
+                if !Rc::ptr_eq(env, self.env) {
+                    return Err("it closes over local bindings".to_string());
+                }
+                if !readable(params) || !readable(ast) {
+                    return Err("its body holds values that can't be read back".to_string());
+                }
+                format!("(fn* {} {})", params.pr_str(true), ast.pr_str(true))
+            }
+            other => return Err(format!("{} can't be written as mal", other.pr_str(true))),
+        };
+        match value.get_meta() {
+            Ok(MalType::Nil) | Err(_) => Ok(source),
+            Ok(meta) => Ok(format!("(with-meta {} {})", source, self.source(&meta)?)),
+        }
+    }
+
+    /// The sources of values, each after a space
+    fn sources<'v>(&self, values: impl Iterator<Item = &'v MalType>) -> Result<String, String> {
+        Ok(values
+            .map(|v| self.source(v).map(|s| format!(" {}", s)))
+            .collect::<Result<Vec<_>, _>>()?
+            .concat())
+    }
+
+    /// The definition of name as value
+    fn definition(&self, name: &str, value: &MalType) -> Result<String, String> {
+        match value {
+            MalType::MalFunction { is_macro: true, .. } => {
+                Ok(format!("(defmacro! {} {})", name, self.source(value)?))
+            }
+            _ => Ok(format!("(def! {} {})", name, self.source(value)?)),
+        }
+    }
+}
+
+/// Write the definitions made in env since the session started to path, warning about any that
+/// can't be written
+pub fn save(env: &Rc<Env>, initial: &HashSet<String>, path: &str) -> Result<(), MalErr> {
+    let saver = Saver {
+        env,
+        builtins: mal::core::ns()
+            .into_iter()
+            .sorted_by_key(|(name, _)| *name)
+            .collect(),
+    };
+    let definitions = env
+        .bindings()
+        .iter()
+        .filter(|(name, _)| !initial.contains(name))
+        .filter_map(|(name, value)| match saver.definition(name, value) {
+            Ok(definition) => Some(definition),
+            Err(reason) => {
+                eprintln!("Warning: not saving {}: {}", name, reason);
+                None
+            }
+        })
+        .join("\n");
+    std::fs::write(
+        path,
+        format!(";; mal session written by :save\n{}\n", definitions),
+    )
+    .map_err(|e| MalErr::IoError(Some(path.to_string()), e))
+}
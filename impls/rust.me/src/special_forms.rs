@@ -0,0 +1,173 @@
+use crate::errors::MalErr;
+use crate::types::MalType;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpecialForm {
+    Def,
+    DefMacro,
+    Let,
+    LetRec,
+    Do,
+    If,
+    While,
+    Case,
+    Condp,
+    DoTimes,
+    DoSeq,
+    WithRedefs,
+    Match,
+    Assert,
+    Fn,
+    Eval,
+    LoadFile,
+    Require,
+    Quote,
+    Quasiquote,
+    QuasiquoteExpand,
+    MacroExpand,
+    Try,
+}
+
+impl SpecialForm {
+    pub const ALL: [SpecialForm; 23] = [
+        SpecialForm::Def,
+        SpecialForm::DefMacro,
+        SpecialForm::Let,
+        SpecialForm::LetRec,
+        SpecialForm::Do,
+        SpecialForm::If,
+        SpecialForm::While,
+        SpecialForm::Case,
+        SpecialForm::Condp,
+        SpecialForm::DoTimes,
+        SpecialForm::DoSeq,
+        SpecialForm::WithRedefs,
+        SpecialForm::Match,
+        SpecialForm::Assert,
+        SpecialForm::Fn,
+        SpecialForm::Eval,
+        SpecialForm::LoadFile,
+        SpecialForm::Require,
+        SpecialForm::Quote,
+        SpecialForm::Quasiquote,
+        SpecialForm::QuasiquoteExpand,
+        SpecialForm::MacroExpand,
+        SpecialForm::Try,
+    ];
+
+    /// Resolve the head of a list to a special form, without allocating
+    pub fn resolve(head: &MalType) -> Option<Self> {
+        let name = match head {
+            MalType::Symbol(s) => s.as_str(),
+            _ => return None,
+        };
+        let form = match name {
+            "def!" => SpecialForm::Def,
+            "defmacro!" => SpecialForm::DefMacro,
+            "let*" => SpecialForm::Let,
+            "letrec*" => SpecialForm::LetRec,
+            "do" => SpecialForm::Do,
+            "if" => SpecialForm::If,
+            "while" => SpecialForm::While,
+            "case" => SpecialForm::Case,
+            "condp" => SpecialForm::Condp,
+            "dotimes" => SpecialForm::DoTimes,
+            "doseq" => SpecialForm::DoSeq,
+            "with-redefs" => SpecialForm::WithRedefs,
+            "match" => SpecialForm::Match,
+            "assert" => SpecialForm::Assert,
+            "fn*" => SpecialForm::Fn,
+            "eval" => SpecialForm::Eval,
+            "load-file" => SpecialForm::LoadFile,
+            "require" => SpecialForm::Require,
+            "quote" => SpecialForm::Quote,
+            "quasiquote" => SpecialForm::Quasiquote,
+            "quasiquoteexpand" => SpecialForm::QuasiquoteExpand,
+            "macroexpand" => SpecialForm::MacroExpand,
+            "try*" => SpecialForm::Try,
+            _ => return None,
+        };
+        Some(form)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpecialForm::Def => "def!",
+            SpecialForm::DefMacro => "defmacro!",
+            SpecialForm::Let => "let*",
+            SpecialForm::LetRec => "letrec*",
+            SpecialForm::Do => "do",
+            SpecialForm::If => "if",
+            SpecialForm::While => "while",
+            SpecialForm::Case => "case",
+            SpecialForm::Condp => "condp",
+            SpecialForm::DoTimes => "dotimes",
+            SpecialForm::DoSeq => "doseq",
+            SpecialForm::WithRedefs => "with-redefs",
+            SpecialForm::Match => "match",
+            SpecialForm::Assert => "assert",
+            SpecialForm::Fn => "fn*",
+            SpecialForm::Eval => "eval",
+            SpecialForm::LoadFile => "load-file",
+            SpecialForm::Require => "require",
+            SpecialForm::Quote => "quote",
+            SpecialForm::Quasiquote => "quasiquote",
+            SpecialForm::QuasiquoteExpand => "quasiquoteexpand",
+            SpecialForm::MacroExpand => "macroexpand",
+            SpecialForm::Try => "try*",
+        }
+    }
+
+    /// Argument count bounds, excluding the form's own symbol
+    fn arity(&self) -> (usize, Option<usize>) {
+        match self {
+            SpecialForm::Def
+            | SpecialForm::DefMacro
+            | SpecialForm::Let
+            | SpecialForm::LetRec
+            | SpecialForm::Fn => (2, Some(2)),
+            SpecialForm::Eval
+            | SpecialForm::LoadFile
+            | SpecialForm::Quote
+            | SpecialForm::Quasiquote
+            | SpecialForm::QuasiquoteExpand
+            | SpecialForm::MacroExpand => (1, Some(1)),
+            SpecialForm::If => (2, Some(3)),
+            SpecialForm::Require => (1, Some(2)),
+            SpecialForm::Assert => (1, Some(2)),
+            SpecialForm::Do => (0, None),
+            SpecialForm::Try => (1, None),
+            SpecialForm::While
+            | SpecialForm::Case
+            | SpecialForm::Match
+            | SpecialForm::DoTimes
+            | SpecialForm::DoSeq
+            | SpecialForm::WithRedefs => (1, None),
+            SpecialForm::Condp => (2, None),
+        }
+    }
+
+    /// Validate the shape of the form before eval indexes into it
+    pub fn check(&self, l: &[MalType]) -> Result<(), MalErr> {
+        let (min, max) = self.arity();
+        let got = l.len() - 1;
+        if got < min || max.is_some_and(|max| got > max) {
+            let expected = match max {
+                Some(max) if max == min => format!("{}", min),
+                Some(max) => format!("{} to {}", min, max),
+                None => format!("at least {}", min),
+            };
+            return Err(self.error(format!("expected {} arguments, got {}", expected, got)));
+        }
+        match self {
+            SpecialForm::Def | SpecialForm::DefMacro if !matches!(l[1], MalType::Symbol(_)) => {
+                Err(self.error(format!("expected a symbol to define, got {}", l[1])))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn error(&self, message: String) -> MalErr {
+        MalErr::InvalidForm(self.name().to_string(), message)
+    }
+}
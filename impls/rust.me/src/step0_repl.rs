@@ -1,5 +1,5 @@
-use rustyline::error::ReadlineError;
-use rustyline::{DefaultEditor, Result};
+mod input;
+use input::Input;
 
 fn read(s: String) -> String {
     s
@@ -16,38 +16,21 @@ fn print(s: String) -> String {
 fn rep(s: String) -> String {
     let r = read(s);
     let e = eval(r);
-    let p = print(e);
-    p
-}
 
-fn main() -> Result<()> {
-    let mut rl = DefaultEditor::new()?;
-    if rl.load_history("history.txt").is_err() {
-        println!("No previous history.");
-    }
+    print(e)
+}
 
-    loop {
-        let readline = rl.readline("user> ");
-        match readline {
-            Ok(line) => {
-                rl.add_history_entry(line.as_str()).unwrap();
-                println!("{}", &rep(line));
-            }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
-            }
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break;
-            }
+fn main() {
+    let mut input = match Input::open() {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
         }
-    }
+    };
 
-    rl.save_history("history.txt").unwrap();
-    Ok(())
+    while let Some(line) = input.line("user> ") {
+        println!("{}", &rep(line));
+    }
+    input.close();
 }
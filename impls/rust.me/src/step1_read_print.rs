@@ -1,11 +1,9 @@
-use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
-mod errors;
-use crate::errors::MalErr;
-mod printer;
-mod reader;
-mod types;
-use types::MalType;
+use mal::errors::MalErr;
+use mal::reader;
+use mal::types::MalType;
+
+mod input;
+use input::Input;
 
 fn read(s: String) -> Result<MalType, MalErr> {
     reader::read_str(s)
@@ -16,7 +14,7 @@ fn eval(mt: MalType) -> MalType {
 }
 
 fn print(mt: MalType) -> String {
-    mt.pr_str()
+    mt.pr_str(true)
 }
 
 fn rep(s: String) -> Result<String, MalErr> {
@@ -26,38 +24,21 @@ fn rep(s: String) -> Result<String, MalErr> {
     Ok(p)
 }
 
-fn main() -> rustyline::Result<()> {
-    let mut rl = DefaultEditor::new()?;
-    if rl.load_history("history.txt").is_err() {
-        println!("No previous history.");
-    }
+fn main() {
+    let mut input = match Input::open() {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    loop {
-        let readline = rl.readline("user> ");
-        match readline {
-            Ok(line) => {
-                rl.add_history_entry(line.as_str()).unwrap();
-                let output = rep(line);
-                match output {
-                    Ok(val) => println!("{}", val),
-                    Err(e) => eprintln!("Error: {}", e),
-                }
-            }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
-            }
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break;
-            }
+    while let Some(line) = input.line("user> ") {
+        let output = rep(line);
+        match output {
+            Ok(val) => println!("{}", val),
+            Err(e) => eprintln!("Error: {}", e),
         }
     }
-
-    rl.save_history("history.txt").unwrap();
-    Ok(())
+    input.close();
 }
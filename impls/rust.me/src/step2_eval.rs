@@ -1,13 +1,13 @@
+#![allow(clippy::mutable_key_type)]
+
 use std::collections::HashMap;
 
-use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
-mod errors;
-use crate::errors::MalErr;
-mod printer;
-mod reader;
-mod types;
-use types::MalType;
+use mal::errors::MalErr;
+use mal::types::MalType;
+use mal::{core, hashmap, list, reader, vector};
+
+mod input;
+use input::Input;
 
 fn read(s: String) -> Result<MalType, MalErr> {
     reader::read_str(s)
@@ -16,51 +16,50 @@ fn read(s: String) -> Result<MalType, MalErr> {
 fn eval(ast: MalType, repl_env: &ReplEnv) -> Result<MalType, MalErr> {
     match ast.clone() {
         MalType::List(l, _) => {
-            if l.len() == 0 {
+            if l.is_empty() {
                 return Ok(ast);
             }
-            match eval_ast(&ast, &repl_env)? {
-                MalType::List(ref el, _) => match el.split_first() {
-                    Some((f, args)) => f.apply(args.to_vec()),
-                    _ => Err(MalErr::Generic("Something bad happened".to_string())),
-                },
-                _ => Err(MalErr::Generic("Expected a list".to_string())),
-            }
+            let f = eval(l[0].clone(), repl_env)?;
+            let args = l[1..]
+                .iter()
+                .map(|arg| eval(arg.clone(), repl_env))
+                .collect::<Result<Vec<_>, _>>()?;
+            f.apply(args)
         }
         _ => eval_ast(&ast, repl_env),
     }
 }
 
 fn print(ast: MalType) -> String {
-    ast.pr_str()
+    ast.pr_str(true)
 }
 
 fn rep(s: String, repl_env: &ReplEnv) -> Result<String, MalErr> {
     let r = read(s)?;
-    let e = eval(r, &repl_env)?;
+    let e = eval(r, repl_env)?;
     let p = print(e);
     Ok(p)
 }
 
-type ReplEnv = HashMap<&'static str, fn(Vec<MalType>) -> MalType>;
+type ReplEnv = HashMap<&'static str, MalType>;
 
 fn eval_ast(ast: &MalType, repl_env: &ReplEnv) -> Result<MalType, MalErr> {
     match ast {
         MalType::Symbol(s) => match repl_env.get(s.as_str()) {
-            Some(f) => Ok(MalType::Function(*f)),
-            None => Err(MalErr::SymbolNotFound("Invalid symbol {e:}".to_string())),
+            Some(f) => Ok(f.clone()),
+            None => Err(MalErr::SymbolNotFound(s.to_string(), vec![])),
         },
         MalType::List(l, _) => {
             let mut results = Vec::new();
             for ast in l.iter() {
-                results.push(eval(ast.clone(), &repl_env)?);
+                results.push(eval(ast.clone(), repl_env)?);
             }
             Ok(list!(results))
         }
         MalType::Vector(l, _) => {
             let mut results = Vec::new();
             for ast in l.iter() {
-                results.push(eval(ast.clone(), &repl_env)?);
+                results.push(eval(ast.clone(), repl_env)?);
             }
             Ok(vector!(results))
         }
@@ -68,7 +67,7 @@ fn eval_ast(ast: &MalType, repl_env: &ReplEnv) -> Result<MalType, MalErr> {
             let mut results = Vec::new();
             for (k, v) in hm.iter() {
                 results.push(k.clone());
-                results.push(eval(v.clone(), &repl_env)?);
+                results.push(eval(v.clone(), repl_env)?);
             }
             hashmap!(results)
         }
@@ -76,60 +75,26 @@ fn eval_ast(ast: &MalType, repl_env: &ReplEnv) -> Result<MalType, MalErr> {
     }
 }
 
-fn main() -> rustyline::Result<()> {
-    let mut rl = DefaultEditor::new()?;
-    if rl.load_history("history.txt").is_err() {
-        println!("No previous history.");
-    }
+fn main() {
+    let mut input = match Input::open() {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let mut repl_env: ReplEnv = ReplEnv::new();
-    repl_env.insert("+", |a| {
-        a.iter()
-            .skip(1)
-            .fold(a[0].clone(), |acc, x| acc + x.clone())
-    });
-    repl_env.insert("-", |a| {
-        a.iter()
-            .skip(1)
-            .fold(a[0].clone(), |acc, x| acc - x.clone())
-    });
-    repl_env.insert("*", |a| {
-        a.iter()
-            .skip(1)
-            .fold(a[0].clone(), |acc, x| acc * x.clone())
-    });
-    repl_env.insert("/", |a| {
-        a.iter()
-            .skip(1)
-            .fold(a[0].clone(), |acc, x| acc / x.clone())
-    });
+    let repl_env: ReplEnv = core::ns()
+        .into_iter()
+        .filter(|(symbol, _)| ["+", "-", "*", "/"].contains(symbol))
+        .collect();
 
-    loop {
-        let readline = rl.readline("user> ");
-        match readline {
-            Ok(line) => {
-                rl.add_history_entry(line.as_str()).unwrap();
-                let output = rep(line, &repl_env);
-                match output {
-                    Ok(val) => println!("{}", val),
-                    Err(e) => eprintln!("Error: {}", e),
-                }
-            }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
-            }
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break;
-            }
+    while let Some(line) = input.line("user> ") {
+        let output = rep(line, &repl_env);
+        match output {
+            Ok(val) => println!("{}", val),
+            Err(e) => eprintln!("Error: {}", e.message()),
         }
     }
-
-    rl.save_history("history.txt").unwrap();
-    Ok(())
+    input.close();
 }
@@ -1,32 +1,43 @@
-use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
-mod env;
-use env::Env;
-mod errors;
-use errors::MalErr;
-mod printer;
-mod reader;
-mod types;
-use types::MalType;
+#![allow(clippy::mutable_key_type)]
+
+use std::rc::Rc;
+
+use mal::env::Env;
+use mal::errors::MalErr;
+use mal::types::MalType;
+use mal::{core, hashmap, list, reader, vector};
+
+mod input;
+use input::Input;
+
+/// The special forms of this step, whose shape is checked before they are evaluated
+const SPECIAL_FORMS: [&str; 2] = ["def!", "let*"];
 
 fn read(s: String) -> Result<MalType, MalErr> {
     reader::read_str(s)
 }
 
-fn eval(ast: MalType, env: &mut Env) -> Result<MalType, MalErr> {
+fn eval(ast: MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
     match ast.clone() {
         MalType::List(l, _) => {
-            if l.len() == 0 {
+            if l.is_empty() {
                 return Ok(ast);
             }
-            match l[0].to_string().as_str() {
+            let head = match &l[0] {
+                MalType::Symbol(s) => s.as_str(),
+                _ => "",
+            };
+            if SPECIAL_FORMS.contains(&head) {
+                mal::check_form(&l)?;
+            }
+            match head {
                 "def!" => {
                     let result = eval(l[2].clone(), env)?;
                     env.set(l[1].to_string(), result.clone());
                     Ok(result)
                 }
                 "let*" => {
-                    let mut new_env = Env::new(Some(env.clone()));
+                    let new_env = Rc::new(Env::new(Some(Rc::clone(env))));
                     match &l[1] {
                         MalType::List(binding_list, _) | MalType::Vector(binding_list, _) => {
                             if binding_list.len() % 2 != 0 {
@@ -35,10 +46,7 @@ fn eval(ast: MalType, env: &mut Env) -> Result<MalType, MalErr> {
                                 ));
                             }
                             for w in binding_list.chunks(2) {
-                                new_env.set(
-                                    w[0].to_string(),
-                                    eval(w[1].clone(), &mut new_env.clone())?,
-                                );
+                                new_env.set(w[0].to_string(), eval(w[1].clone(), &new_env)?);
                             }
                         }
                         _ => {
@@ -47,15 +55,16 @@ fn eval(ast: MalType, env: &mut Env) -> Result<MalType, MalErr> {
                             ))
                         }
                     };
-                    eval(l[2].clone(), &mut new_env.clone())
+                    eval(l[2].clone(), &new_env)
+                }
+                _ => {
+                    let f = eval(l[0].clone(), env)?;
+                    let args = l[1..]
+                        .iter()
+                        .map(|arg| eval(arg.clone(), env))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    f.apply(args)
                 }
-                _ => match eval_ast(&ast, env)? {
-                    MalType::List(ref el, _) => match el.split_first() {
-                        Some((f, args)) => f.apply(args.to_vec()),
-                        _ => Err(MalErr::Generic("Something bad happened".to_string())),
-                    },
-                    _ => Err(MalErr::Generic("Expected a list".to_string())),
-                },
             }
         }
         _ => eval_ast(&ast, env),
@@ -63,19 +72,19 @@ fn eval(ast: MalType, env: &mut Env) -> Result<MalType, MalErr> {
 }
 
 fn print(ast: MalType) -> String {
-    ast.pr_str()
+    ast.pr_str(true)
 }
 
-fn rep(s: String, env: &mut Env) -> Result<String, MalErr> {
+fn rep(s: String, env: &Rc<Env>) -> Result<String, MalErr> {
     let r = read(s)?;
     let e = eval(r, env)?;
     let p = print(e);
     Ok(p)
 }
 
-fn eval_ast(ast: &MalType, env: &mut Env) -> Result<MalType, MalErr> {
+fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
     match ast {
-        MalType::Symbol(s) => env.get(s.as_str()),
+        MalType::Symbol(s) => env.get(s),
         MalType::List(l, _) => {
             let mut results = Vec::new();
             for ast in l.iter() {
@@ -102,72 +111,28 @@ fn eval_ast(ast: &MalType, env: &mut Env) -> Result<MalType, MalErr> {
     }
 }
 
-fn main() -> rustyline::Result<()> {
-    let mut rl = DefaultEditor::new()?;
-    if rl.load_history("history.txt").is_err() {
-        println!("No previous history.");
-    }
-
-    let mut repl_env: Env = Env::default();
-    repl_env.set(
-        "+".to_string(),
-        MalType::Function(|a| {
-            a.iter()
-                .skip(1)
-                .fold(a[0].clone(), |acc, x| acc + x.clone())
-        }),
-    );
-    repl_env.set(
-        "-".to_string(),
-        MalType::Function(|a| {
-            a.iter()
-                .skip(1)
-                .fold(a[0].clone(), |acc, x| acc - x.clone())
-        }),
-    );
-    repl_env.set(
-        "*".to_string(),
-        MalType::Function(|a| {
-            a.iter()
-                .skip(1)
-                .fold(a[0].clone(), |acc, x| acc * x.clone())
-        }),
-    );
-    repl_env.set(
-        "/".to_string(),
-        MalType::Function(|a| {
-            a.iter()
-                .skip(1)
-                .fold(a[0].clone(), |acc, x| acc / x.clone())
-        }),
-    );
+fn main() {
+    let mut input = match Input::open() {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    loop {
-        let readline = rl.readline("user> ");
-        match readline {
-            Ok(line) => {
-                rl.add_history_entry(line.as_str()).unwrap();
-                let output = rep(line, &mut repl_env);
-                match output {
-                    Ok(val) => println!("{}", val),
-                    Err(e) => eprintln!("Error: {}", e),
-                }
-            }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
-            }
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break;
-            }
+    let repl_env = Rc::new(Env::default());
+    for (symbol, value) in core::ns() {
+        if ["+", "-", "*", "/"].contains(&symbol) {
+            repl_env.set(symbol.to_string(), value);
         }
     }
 
-    rl.save_history("history.txt").unwrap();
-    Ok(())
+    while let Some(line) = input.line("user> ") {
+        let output = rep(line, &repl_env);
+        match output {
+            Ok(val) => println!("{}", val),
+            Err(e) => eprintln!("Error: {}", e.message()),
+        }
+    }
+    input.close();
 }
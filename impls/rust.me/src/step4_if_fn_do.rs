@@ -1,16 +1,17 @@
-use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
+#![allow(clippy::mutable_key_type)]
+
 use std::rc::Rc;
 
-mod core;
-mod env;
-use env::Env;
-mod errors;
-use errors::MalErr;
-mod printer;
-mod reader;
-mod types;
-use types::MalType;
+use mal::env::Env;
+use mal::errors::MalErr;
+use mal::types::MalType;
+use mal::{core, hashmap, list, reader, vector};
+
+mod input;
+use input::Input;
+
+/// The special forms of this step, whose shape is checked before they are evaluated
+const SPECIAL_FORMS: [&str; 5] = ["def!", "let*", "do", "if", "fn*"];
 
 fn read(s: &str) -> Result<MalType, MalErr> {
     reader::read_str(s.to_string())
@@ -19,10 +20,17 @@ fn read(s: &str) -> Result<MalType, MalErr> {
 fn eval(ast: MalType, env: Rc<Env>) -> Result<MalType, MalErr> {
     match ast.clone() {
         MalType::List(l, _) => {
-            if l.len() == 0 {
+            if l.is_empty() {
                 return Ok(ast);
             }
-            match l[0].to_string().as_str() {
+            let head = match &l[0] {
+                MalType::Symbol(s) => s.as_str(),
+                _ => "",
+            };
+            if SPECIAL_FORMS.contains(&head) {
+                mal::check_form(&l)?;
+            }
+            match head {
                 "def!" => {
                     let result = eval(l[2].clone(), Rc::clone(&env))?;
                     env.set(l[1].to_string(), result.clone());
@@ -52,10 +60,13 @@ fn eval(ast: MalType, env: Rc<Env>) -> Result<MalType, MalErr> {
                     };
                     eval(l[2].clone(), let_env)
                 }
-                "do" => match eval_ast(&list!(l[1..].to_vec()), &env)? {
-                    MalType::List(el, _) => Ok(el.last().unwrap_or(&MalType::Nil).clone()),
-                    _ => Err(MalErr::InvalidDo("Invalid do construction".to_string())),
-                },
+                "do" => {
+                    let mut value = MalType::Nil;
+                    for form in &l[1..] {
+                        value = eval(form.clone(), Rc::clone(&env))?;
+                    }
+                    Ok(value)
+                }
                 "if" => match eval(l[1].clone(), Rc::clone(&env))? {
                     MalType::Nil | MalType::Bool(false) => {
                         l.get(3).map_or(Ok(MalType::Nil), |else_branch| {
@@ -67,23 +78,26 @@ fn eval(ast: MalType, env: Rc<Env>) -> Result<MalType, MalErr> {
                 "fn*" => match &l[1..] {
                     [params @ (MalType::List(..) | MalType::Vector(..)), body] => {
                         Ok(MalType::MalFunction {
-                            eval: eval,
+                            eval,
                             params: Rc::new(params.clone()),
-                            body: Rc::new(body.clone()),
-                            env: env,
+                            ast: Rc::new(body.clone()),
+                            env,
+                            is_macro: false,
+                            meta: Rc::new(MalType::Nil),
                         })
                     }
                     _ => Err(MalErr::MalFunctionErr(
                         "fn* expects two parameters".to_string(),
                     )),
                 },
-                _ => match eval_ast(&ast, &env)? {
-                    MalType::List(ref el, _) => match el.split_first() {
-                        Some((f, args)) => f.apply(args.to_vec()),
-                        _ => Err(MalErr::Generic("Something bad happened".to_string())),
-                    },
-                    _ => Err(MalErr::Generic("Expected a list".to_string())),
-                },
+                _ => {
+                    let f = eval(l[0].clone(), Rc::clone(&env))?;
+                    let args = l[1..]
+                        .iter()
+                        .map(|arg| eval(arg.clone(), Rc::clone(&env)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    f.apply(args)
+                }
             }
         }
         _ => eval_ast(&ast, &env),
@@ -96,25 +110,25 @@ fn print(ast: MalType) -> String {
 
 fn rep(s: &str, env: &Rc<Env>) -> Result<String, MalErr> {
     let r = read(s)?;
-    let e = eval(r, Rc::clone(&env))?;
+    let e = eval(r, Rc::clone(env))?;
     let p = print(e);
     Ok(p)
 }
 
 fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
     match ast {
-        MalType::Symbol(s) => env.get(s.as_str()),
+        MalType::Symbol(s) => env.get(s),
         MalType::List(l, _) => {
             let mut results = Vec::new();
             for ast in l.iter() {
-                results.push(eval(ast.clone(), Rc::clone(&env))?);
+                results.push(eval(ast.clone(), Rc::clone(env))?);
             }
             Ok(list!(results))
         }
         MalType::Vector(l, _) => {
             let mut results = Vec::new();
             for ast in l.iter() {
-                results.push(eval(ast.clone(), Rc::clone(&env))?);
+                results.push(eval(ast.clone(), Rc::clone(env))?);
             }
             Ok(vector!(results))
         }
@@ -122,7 +136,7 @@ fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
             let mut results = Vec::new();
             for (k, v) in hm.iter() {
                 results.push(k.clone());
-                results.push(eval(v.clone(), Rc::clone(&env))?);
+                results.push(eval(v.clone(), Rc::clone(env))?);
             }
             hashmap!(results)
         }
@@ -130,45 +144,34 @@ fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
     }
 }
 
-fn main() -> rustyline::Result<()> {
-    let mut rl = DefaultEditor::new()?;
-    if rl.load_history("history.txt").is_err() {
-        println!("No previous history.");
-    }
+fn main() {
+    let mut input = match Input::open() {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     let repl_env = Rc::new(Env::default());
     for (symbol, value) in core::ns() {
         repl_env.set(symbol.to_string(), value);
     }
 
-    let _ = rep("(def! not (fn* (a) (if a false true)))", &repl_env);
-
-    loop {
-        let readline = rl.readline("user> ");
-        match readline {
-            Ok(line) => {
-                rl.add_history_entry(&line).unwrap();
-                let output = rep(&line, &repl_env);
-                match output {
-                    Ok(val) => println!("{}", val),
-                    Err(e) => eprintln!("Error: {}", e),
-                }
-            }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
-            }
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break;
-            }
+    if !std::env::args().skip(1).any(|arg| arg == "--no-prelude") {
+        // only the functions: this step can't expand the prelude's macros, and they go through
+        // its own eval, which is what their closures then run on
+        if let Err(e) = rep(&format!("(do {}\nnil)", mal::PRELUDE), &repl_env) {
+            eprintln!("Error loading prelude: {}", e);
         }
     }
 
-    rl.save_history("history.txt").unwrap();
-    Ok(())
+    while let Some(line) = input.line("user> ") {
+        let output = rep(&line, &repl_env);
+        match output {
+            Ok(val) => println!("{}", val),
+            Err(e) => eprintln!("Error: {}", e.message()),
+        }
+    }
+    input.close();
 }
@@ -1,16 +1,17 @@
-use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
+#![allow(clippy::mutable_key_type)]
+
 use std::rc::Rc;
 
-mod core;
-mod env;
-use env::Env;
-mod errors;
-use errors::MalErr;
-mod printer;
-mod reader;
-mod types;
-use types::MalType;
+use mal::env::Env;
+use mal::errors::MalErr;
+use mal::types::MalType;
+use mal::{core, hashmap, list, reader, vector};
+
+mod input;
+use input::Input;
+
+/// The special forms of this step, whose shape is checked before they are evaluated
+const SPECIAL_FORMS: [&str; 5] = ["def!", "let*", "do", "if", "fn*"];
 
 fn read(s: &str) -> Result<MalType, MalErr> {
     reader::read_str(s.to_string())
@@ -22,10 +23,17 @@ fn eval(mut ast: MalType, mut env: Rc<Env>) -> Result<MalType, MalErr> {
     loop {
         res = match ast.clone() {
             MalType::List(l, _) => {
-                if l.len() == 0 {
+                if l.is_empty() {
                     return Ok(ast);
                 }
-                match l[0].to_string().as_str() {
+                let head = match &l[0] {
+                    MalType::Symbol(s) => s.as_str(),
+                    _ => "",
+                };
+                if SPECIAL_FORMS.contains(&head) {
+                    mal::check_form(&l)?;
+                }
+                match head {
                     "def!" => {
                         let result = eval(l[2].clone(), Rc::clone(&env))?;
                         env.set(l[1].to_string(), result.clone());
@@ -58,12 +66,15 @@ fn eval(mut ast: MalType, mut env: Rc<Env>) -> Result<MalType, MalErr> {
                         env = let_env;
                         continue;
                     }
-                    "do" => match eval_ast(&list!(l[1..l.len() - 1].to_vec()), &env)? {
-                        MalType::List(_, _) => {
-                            ast = l.last().unwrap_or(&MalType::Nil).clone();
+                    "do" => match l[1..].split_last() {
+                        Some((last, body)) => {
+                            for form in body {
+                                eval(form.clone(), Rc::clone(&env))?;
+                            }
+                            ast = last.clone();
                             continue;
                         }
-                        _ => Err(MalErr::InvalidDo("Invalid do construction".to_string())),
+                        None => Ok(MalType::Nil),
                     },
                     "if" => match eval(l[1].clone(), Rc::clone(&env))? {
                         MalType::Nil | MalType::Bool(false) => {
@@ -80,38 +91,41 @@ fn eval(mut ast: MalType, mut env: Rc<Env>) -> Result<MalType, MalErr> {
                     "fn*" => match &l[1..] {
                         [params @ (MalType::List(..) | MalType::Vector(..)), body] => {
                             return Ok(MalType::MalFunction {
-                                eval: eval,
+                                eval,
                                 params: Rc::new(params.clone()),
                                 ast: Rc::new(body.clone()),
-                                env: env,
+                                env,
+                                is_macro: false,
+                                meta: Rc::new(MalType::Nil),
                             });
                         }
                         _ => Err(MalErr::MalFunctionErr(
                             "fn* expects two parameters".to_string(),
                         )),
                     },
-                    _ => match eval_ast(&ast, &env)? {
-                        MalType::List(ref el, _) => match el.split_first() {
-                            Some((f, args)) => match f {
-                                MalType::Function(_) => f.apply(args.to_vec()),
-                                MalType::MalFunction {
-                                    params,
-                                    ast: mfast,
-                                    env: mfenv,
-                                    ..
-                                } => {
-                                    let fn_env = Rc::new(Env::new(Some(Rc::clone(&mfenv))));
-                                    fn_env.bind((**params).clone(), args.to_vec())?;
-                                    ast = (**mfast).clone();
-                                    env = fn_env;
-                                    continue;
-                                }
-                                _ => Err(MalErr::Generic("Cannot apply non-function".to_string())),
-                            },
-                            _ => Err(MalErr::Generic("Something bad happened".to_string())),
-                        },
-                        _ => Err(MalErr::Generic("Expected a list".to_string())),
-                    },
+                    _ => {
+                        let f = eval(l[0].clone(), Rc::clone(&env))?;
+                        let args = l[1..]
+                            .iter()
+                            .map(|arg| eval(arg.clone(), Rc::clone(&env)))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        match f {
+                            MalType::Function(..) => f.apply(args),
+                            MalType::MalFunction {
+                                params,
+                                ast: mfast,
+                                env: mfenv,
+                                ..
+                            } => {
+                                let fn_env = Rc::new(Env::new(Some(mfenv)));
+                                fn_env.bind((*params).clone(), args)?;
+                                ast = (*mfast).clone();
+                                env = fn_env;
+                                continue;
+                            }
+                            _ => Err(MalErr::Generic("Cannot apply non-function".to_string())),
+                        }
+                    }
                 }
             }
             _ => eval_ast(&ast, &env),
@@ -129,25 +143,25 @@ fn print(ast: MalType) -> String {
 
 fn rep(s: &str, env: &Rc<Env>) -> Result<String, MalErr> {
     let r = read(s)?;
-    let e = eval(r, Rc::clone(&env))?;
+    let e = eval(r, Rc::clone(env))?;
     let p = print(e);
     Ok(p)
 }
 
 fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
     match ast {
-        MalType::Symbol(s) => env.get(s.as_str()),
+        MalType::Symbol(s) => env.get(s),
         MalType::List(l, _) => {
             let mut results = Vec::new();
             for ast in l.iter() {
-                results.push(eval(ast.clone(), Rc::clone(&env))?);
+                results.push(eval(ast.clone(), Rc::clone(env))?);
             }
             Ok(list!(results))
         }
         MalType::Vector(l, _) => {
             let mut results = Vec::new();
             for ast in l.iter() {
-                results.push(eval(ast.clone(), Rc::clone(&env))?);
+                results.push(eval(ast.clone(), Rc::clone(env))?);
             }
             Ok(vector!(results))
         }
@@ -155,7 +169,7 @@ fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
             let mut results = Vec::new();
             for (k, v) in hm.iter() {
                 results.push(k.clone());
-                results.push(eval(v.clone(), Rc::clone(&env))?);
+                results.push(eval(v.clone(), Rc::clone(env))?);
             }
             hashmap!(results)
         }
@@ -163,45 +177,34 @@ fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
     }
 }
 
-fn main() -> rustyline::Result<()> {
-    let mut rl = DefaultEditor::new()?;
-    if rl.load_history("history.txt").is_err() {
-        println!("No previous history.");
-    }
+fn main() {
+    let mut input = match Input::open() {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     let repl_env = Rc::new(Env::default());
     for (symbol, value) in core::ns() {
         repl_env.set(symbol.to_string(), value);
     }
 
-    let _ = rep("(def! not (fn* (a) (if a false true)))", &repl_env);
-
-    loop {
-        let readline = rl.readline("user> ");
-        match readline {
-            Ok(line) => {
-                rl.add_history_entry(&line).unwrap();
-                let output = rep(&line, &repl_env);
-                match output {
-                    Ok(val) => println!("{}", val),
-                    Err(e) => eprintln!("Error: {}", e),
-                }
-            }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
-            }
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break;
-            }
+    if !std::env::args().skip(1).any(|arg| arg == "--no-prelude") {
+        // only the functions: this step can't expand the prelude's macros, and they go through
+        // its own eval, which is what their closures then run on
+        if let Err(e) = rep(&format!("(do {}\nnil)", mal::PRELUDE), &repl_env) {
+            eprintln!("Error loading prelude: {}", e);
         }
     }
 
-    rl.save_history("history.txt").unwrap();
-    Ok(())
+    while let Some(line) = input.line("user> ") {
+        let output = rep(&line, &repl_env);
+        match output {
+            Ok(val) => println!("{}", val),
+            Err(e) => eprintln!("Error: {}", e.message()),
+        }
+    }
+    input.close();
 }
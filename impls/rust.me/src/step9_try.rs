@@ -1,83 +1,96 @@
-use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
+#![allow(clippy::mutable_key_type)]
+
 use std::rc::Rc;
 
-mod core;
-mod env;
-use env::Env;
-mod errors;
-use errors::MalErr;
-mod printer;
-mod reader;
-mod types;
-use types::MalType;
+use mal::env::Env;
+use mal::errors::MalErr;
+use mal::types::{symbol, MalType};
+use mal::{core, hashmap, list, reader, vector};
+
+mod cli;
+mod input;
+use cli::Cli;
+use input::Input;
+
+const FLAGS: cli::Flags = &[(
+    "--no-prelude",
+    "Don't define the functions and macros of the prelude",
+)];
+
+/// The special forms of this step, whose shape is checked before they are evaluated
+const SPECIAL_FORMS: [&str; 12] = [
+    "def!",
+    "defmacro!",
+    "let*",
+    "do",
+    "if",
+    "fn*",
+    "eval",
+    "quote",
+    "quasiquote",
+    "quasiquoteexpand",
+    "macroexpand",
+    "try*",
+];
 
 fn read(s: &str) -> Result<MalType, MalErr> {
     reader::read_str(s.to_string())
 }
 
-fn qq_inner(l: &Vec<MalType>) -> MalType {
+fn qq_inner(l: &[MalType]) -> Result<MalType, MalErr> {
     match l.first() {
         // If ast is empty return it unchanged
-        None => list!(vec![]),
+        None => Ok(list!(vec![])),
         // If elt is a list starting with the "splice-unquote" symbol, return a list containing:
         // the "concat" symbol, the second element of elt, then the result of processing the rest of ast recursively.
-        Some(MalType::List(elt, _))
-            if elt.first() == Some(&MalType::Symbol("splice-unquote".to_string())) =>
-        {
-            list![
-                MalType::Symbol("concat".to_string()),
-                elt[1].clone(),
-                qq_inner(&l[1..].to_vec())
-            ]
+        Some(MalType::List(elt, _)) if elt.first() == Some(&symbol("splice-unquote")) => {
+            Ok(list![symbol("concat"), unquoted(elt)?, qq_inner(&l[1..])?])
         }
         // Else return a list containing:
         // the "cons" symbol, the result of calling quasiquote with elt as argument, then the result of processing the rest of ast.
-        Some(elt) => {
-            list![
-                MalType::Symbol("cons".to_string()),
-                quasiquote(elt),
-                qq_inner(&l[1..].to_vec())
-            ]
-        }
+        Some(elt) => Ok(list![symbol("cons"), quasiquote(elt)?, qq_inner(&l[1..])?]),
     }
 }
 
-fn quasiquote(ast: &MalType) -> MalType {
+fn quasiquote(ast: &MalType) -> Result<MalType, MalErr> {
     match ast {
         MalType::List(l, _) => match l.first() {
-            Some(MalType::Symbol(s)) if s == "unquote" => return l[1].clone(),
+            Some(MalType::Symbol(s)) if s == "unquote" => unquoted(l),
             _ => qq_inner(l),
         },
-        MalType::Vector(l, _) => list![MalType::Symbol("vec".to_string()), qq_inner(l)],
-        MalType::HashMap(..) | MalType::Symbol(_) => {
-            list![MalType::Symbol("quote".to_string()), ast.clone()]
-        }
-        _ => ast.clone(),
+        MalType::Vector(l, _) => Ok(list![symbol("vec"), qq_inner(l)?]),
+        MalType::HashMap(..) | MalType::Symbol(_) => Ok(list![symbol("quote"), ast.clone()]),
+        _ => Ok(ast.clone()),
     }
 }
 
-fn is_macro_call(ast: &MalType, env: Rc<Env>) -> bool {
-    match ast {
-        MalType::List(l, _) => match l.first() {
-            Some(MalType::Symbol(s)) => match env.get(s) {
-                Ok(MalType::MalFunction { is_macro, .. }) => is_macro,
-                _ => false,
-            },
-            _ => false,
+/// The one argument of an (unquote x) or (splice-unquote x) form
+fn unquoted(l: &[MalType]) -> Result<MalType, MalErr> {
+    match l {
+        [_, x] => Ok(x.clone()),
+        _ => Err(MalErr::InvalidForm(
+            l[0].to_string(),
+            format!("expected 1 arguments, got {}", l.len() - 1),
+        )),
+    }
+}
+
+/// The macro a list form calls, if its head is a symbol bound to one
+fn macro_of(l: &[MalType], env: &Env) -> Option<MalType> {
+    match l.first() {
+        Some(MalType::Symbol(s)) => match env.lookup(s) {
+            Some(mac @ MalType::MalFunction { is_macro: true, .. }) => Some(mac),
+            _ => None,
         },
-        _ => false,
+        _ => None,
     }
 }
 
 fn macroexpand(mut ast: MalType, env: Rc<Env>) -> Result<MalType, MalErr> {
-    while is_macro_call(&ast, Rc::clone(&env)) {
-        match ast {
-            MalType::List(l, _) => {
-                let mal_func = env.get(&l[0].to_string()).unwrap();
-                ast = mal_func.apply(l[1..].to_vec())?
-            }
-            _ => panic!("Expected a macro call!"),
+    while let MalType::List(l, _) = &ast {
+        match macro_of(l, &env) {
+            Some(mac) => ast = mac.apply(l[1..].to_vec())?,
+            None => break,
         }
     }
     Ok(ast)
@@ -94,10 +107,17 @@ fn eval(mut ast: MalType, mut env: Rc<Env>) -> Result<MalType, MalErr> {
         }
         res = match ast.clone() {
             MalType::List(l, _) => {
-                if l.len() == 0 {
+                if l.is_empty() {
                     return Ok(ast);
                 }
-                match l[0].to_string().as_str() {
+                let head = match &l[0] {
+                    MalType::Symbol(s) => s.as_str(),
+                    _ => "",
+                };
+                if SPECIAL_FORMS.contains(&head) {
+                    mal::check_form(&l)?;
+                }
+                match head {
                     "def!" => {
                         let result = eval(l[2].clone(), Rc::clone(&env))?;
                         env.set(l[1].to_string(), result.clone());
@@ -119,6 +139,7 @@ fn eval(mut ast: MalType, mut env: Rc<Env>) -> Result<MalType, MalErr> {
                                     ast,
                                     env: Rc::clone(&env),
                                     is_macro: true,
+                                    meta: Rc::new(MalType::Nil),
                                 };
                                 env.set(l[1].to_string(), new_macro.clone());
                                 Ok(new_macro)
@@ -155,12 +176,15 @@ fn eval(mut ast: MalType, mut env: Rc<Env>) -> Result<MalType, MalErr> {
                         env = let_env;
                         continue;
                     }
-                    "do" => match eval_ast(&list!(l[1..l.len() - 1].to_vec()), &env)? {
-                        MalType::List(_, _) => {
-                            ast = l.last().unwrap_or(&MalType::Nil).clone();
+                    "do" => match l[1..].split_last() {
+                        Some((last, body)) => {
+                            for form in body {
+                                eval(form.clone(), Rc::clone(&env))?;
+                            }
+                            ast = last.clone();
                             continue;
                         }
-                        _ => Err(MalErr::InvalidDo("Invalid do construction".to_string())),
+                        None => Ok(MalType::Nil),
                     },
                     "if" => match eval(l[1].clone(), Rc::clone(&env))? {
                         MalType::Nil | MalType::Bool(false) => {
@@ -177,11 +201,12 @@ fn eval(mut ast: MalType, mut env: Rc<Env>) -> Result<MalType, MalErr> {
                     "fn*" => match &l[1..] {
                         [params @ (MalType::List(..) | MalType::Vector(..)), body] => {
                             return Ok(MalType::MalFunction {
-                                eval: eval,
+                                eval,
                                 params: Rc::new(params.clone()),
                                 ast: Rc::new(body.clone()),
-                                env: env,
+                                env,
                                 is_macro: false,
+                                meta: Rc::new(MalType::Nil),
                             });
                         }
                         _ => Err(MalErr::MalFunctionErr(
@@ -191,58 +216,56 @@ fn eval(mut ast: MalType, mut env: Rc<Env>) -> Result<MalType, MalErr> {
                     "eval" => {
                         ast = eval(l[1].clone(), Rc::clone(&env))?;
                         while let Some(ref e) = Rc::clone(&env).outer {
-                            env = Rc::clone(&e);
+                            env = Rc::clone(e);
                         }
                         continue;
                     }
                     "quote" => Ok(l[1].clone()),
                     "quasiquote" => {
-                        ast = quasiquote(&l[1]);
+                        ast = quasiquote(&l[1])?;
                         continue;
                     }
-                    "quasiquoteexpand" => Ok(quasiquote(&l[1])),
+                    "quasiquoteexpand" => quasiquote(&l[1]),
                     "macroexpand" => macroexpand(l[1].clone(), env),
                     "try*" => match eval(l[1].clone(), Rc::clone(&env)) {
                         Err(e) if l.len() > 2 => match &l[2] {
                             MalType::List(c, _)
-                                if c.first() == Some(&MalType::Symbol("catch*".to_string())) =>
+                                if c.len() == 3 && c.first() == Some(&symbol("catch*")) =>
                             {
-                                let err = match e {
-                                    MalErr::Throw(mt) => mt,
-                                    _ => MalType::Str(e.to_string()),
-                                };
+                                let err = e.to_value();
                                 let catch_env = Rc::new(Env::new(Some(Rc::clone(&env))));
                                 catch_env.bind(list!(vec![c[1].clone()]), vec![err])?;
                                 eval(c[2].clone(), catch_env)
                             }
                             _ => Err(MalErr::Generic(
-                                "expected catch* branch as a list".to_string(),
+                                "expected a (catch* symbol body) branch".to_string(),
                             )),
                         },
                         res => res,
                     },
-                    _ => match eval_ast(&ast, &env)? {
-                        MalType::List(ref el, _) => match el.split_first() {
-                            Some((f, args)) => match f {
-                                MalType::Function(_) => f.apply(args.to_vec()),
-                                MalType::MalFunction {
-                                    params,
-                                    ast: mfast,
-                                    env: mfenv,
-                                    ..
-                                } => {
-                                    let fn_env = Rc::new(Env::new(Some(Rc::clone(&mfenv))));
-                                    fn_env.bind((**params).clone(), args.to_vec())?;
-                                    ast = (**mfast).clone();
-                                    env = fn_env;
-                                    continue;
-                                }
-                                _ => Err(MalErr::Generic("Cannot apply non-function".to_string())),
-                            },
-                            _ => Err(MalErr::Generic("Something bad happened".to_string())),
-                        },
-                        _ => Err(MalErr::Generic("Expected a list".to_string())),
-                    },
+                    _ => {
+                        let f = eval(l[0].clone(), Rc::clone(&env))?;
+                        let args = l[1..]
+                            .iter()
+                            .map(|arg| eval(arg.clone(), Rc::clone(&env)))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        match f {
+                            MalType::Function(..) => f.apply(args),
+                            MalType::MalFunction {
+                                params,
+                                ast: mfast,
+                                env: mfenv,
+                                ..
+                            } => {
+                                let fn_env = Rc::new(Env::new(Some(mfenv)));
+                                fn_env.bind((*params).clone(), args)?;
+                                ast = (*mfast).clone();
+                                env = fn_env;
+                                continue;
+                            }
+                            _ => Err(MalErr::Generic("Cannot apply non-function".to_string())),
+                        }
+                    }
                 }
             }
             _ => eval_ast(&ast, &env),
@@ -260,25 +283,25 @@ fn print(ast: MalType) -> String {
 
 fn rep(s: &str, env: &Rc<Env>) -> Result<String, MalErr> {
     let r = read(s)?;
-    let e = eval(r, Rc::clone(&env))?;
+    let e = eval(r, Rc::clone(env))?;
     let p = print(e);
     Ok(p)
 }
 
 fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
     match ast {
-        MalType::Symbol(s) => env.get(s.as_str()),
+        MalType::Symbol(s) => env.get(s),
         MalType::List(l, _) => {
             let mut results = Vec::new();
             for ast in l.iter() {
-                results.push(eval(ast.clone(), Rc::clone(&env))?);
+                results.push(eval(ast.clone(), Rc::clone(env))?);
             }
             Ok(list!(results))
         }
         MalType::Vector(l, _) => {
             let mut results = Vec::new();
             for ast in l.iter() {
-                results.push(eval(ast.clone(), Rc::clone(&env))?);
+                results.push(eval(ast.clone(), Rc::clone(env))?);
             }
             Ok(vector!(results))
         }
@@ -286,7 +309,7 @@ fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
             let mut results = Vec::new();
             for (k, v) in hm.iter() {
                 results.push(k.clone());
-                results.push(eval(v.clone(), Rc::clone(&env))?);
+                results.push(eval(v.clone(), Rc::clone(env))?);
             }
             hashmap!(results)
         }
@@ -294,14 +317,16 @@ fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
     }
 }
 
-fn main() -> rustyline::Result<()> {
-    let mut rl = DefaultEditor::new()?;
-    if rl.load_history("history.txt").is_err() {
-        println!("No previous history.");
-    }
+/// Exit after printing an error from running the command line
+fn exit_on_error<T>(result: Result<T, MalErr>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("Error: {}", e.message());
+        std::process::exit(1);
+    })
+}
 
-    let mut args = std::env::args();
-    let arg1 = args.nth(1); // The preceding and returned elements are consumed from args
+fn main() {
+    let cli = Cli::parse(FLAGS);
 
     let repl_env = Rc::new(Env::default());
     for (symbol, value) in core::ns() {
@@ -311,10 +336,17 @@ fn main() -> rustyline::Result<()> {
     // programs that are run with load-file have access to their calling environment
     repl_env.set(
         "*ARGV*".to_string(),
-        list!(args.map(MalType::Str).collect()),
+        list!(cli.argv.iter().cloned().map(MalType::Str).collect()),
     );
 
-    let _ = rep("(def! not (fn* (a) (if a false true)))", &repl_env);
+    if !cli.flag("--no-prelude") {
+        // through this step's own eval, which is what the prelude's closures then run on
+        for source in [mal::PRELUDE, mal::PRELUDE_MACROS] {
+            if let Err(e) = rep(&format!("(do {}\nnil)", source), &repl_env) {
+                eprintln!("Error loading prelude: {}", e);
+            }
+        }
+    }
     let _ = rep(
         "(def! load-file (fn* (f) (eval (read-string (str \"(do \" (slurp f) \"\nnil)\")))))",
         &repl_env,
@@ -324,44 +356,43 @@ fn main() -> rustyline::Result<()> {
         &repl_env,
     );
 
-    // If called with arguments, treat the first argument as a filename and use rep to call load-file on that filename,
-    // and finally exit/terminate execution
-    if let Some(f) = arg1 {
-        match rep(&format!("(load-file \"{}\")", f), &repl_env) {
-            Ok(_) => std::process::exit(0),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
-        }
+    // Call load-file on the files given with --load, evaluate and print any expressions given
+    // with -e, then load the file if there is one, and exit if there was either. Paths go in as
+    // string values rather than being spliced into source, where quotes or backslashes in them
+    // would break the form.
+    let load = |f: &String| {
+        exit_on_error(eval(
+            list!(symbol("load-file"), MalType::Str(f.clone())),
+            Rc::clone(&repl_env),
+        ));
+    };
+    for f in &cli.loads {
+        load(f);
+    }
+    for expr in &cli.exprs {
+        println!("{}", exit_on_error(rep(expr, &repl_env)));
+    }
+    if let Some(f) = &cli.file {
+        load(f);
+    }
+    if cli.file.is_some() || !cli.exprs.is_empty() {
+        std::process::exit(0);
     }
 
-    loop {
-        let readline = rl.readline("user> ");
-        match readline {
-            Ok(line) => {
-                rl.add_history_entry(&line).unwrap();
-                let output = rep(&line, &repl_env);
-                match output {
-                    Ok(val) => println!("{}", val),
-                    Err(e) => eprintln!("Error: {}", e),
-                }
-            }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
-            }
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break;
-            }
+    let mut input = match Input::open() {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
         }
-    }
+    };
 
-    rl.save_history("history.txt").unwrap();
-    Ok(())
+    while let Some(line) = input.line("user> ") {
+        let output = rep(&line, &repl_env);
+        match output {
+            Ok(val) => println!("{}", val),
+            Err(e) => eprintln!("Error: {}", e.message()),
+        }
+    }
+    input.close();
 }
@@ -1,376 +1,302 @@
 #![allow(non_snake_case)]
 
-use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
+use std::io::{IsTerminal, Read};
+use std::ops::ControlFlow;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
-mod core;
-mod env;
-use env::Env;
-mod errors;
-use errors::MalErr;
-mod printer;
-mod reader;
-mod types;
-use types::MalType;
+use mal::env::Env;
+use mal::errors::MalErr;
+use mal::symbol::Sym;
+use mal::types::MalType;
+use mal::{list, load_file, print, read_eval, rep, rep_forms};
 
-fn read(s: &str) -> Result<MalType, MalErr> {
-    reader::read_str(s.to_string())
-}
+mod cli;
+mod commands;
+mod input;
+mod session;
+#[cfg(feature = "watch")]
+mod watch;
+use cli::Cli;
+use commands::Commands;
+use input::Input;
 
-fn qq_inner(l: &Vec<MalType>) -> MalType {
-    match l.first() {
-        // If ast is empty return it unchanged
-        None => list!(vec![]),
-        // If elt is a list starting with the "splice-unquote" symbol, return a list containing:
-        // the "concat" symbol, the second element of elt, then the result of processing the rest of ast recursively.
-        Some(MalType::List(elt, _))
-            if elt.first() == Some(&MalType::Symbol("splice-unquote".to_string())) =>
-        {
-            list![
-                MalType::Symbol("concat".to_string()),
-                elt[1].clone(),
-                qq_inner(&l[1..].to_vec())
-            ]
-        }
-        // Else return a list containing:
-        // the "cons" symbol, the result of calling quasiquote with elt as argument, then the result of processing the rest of ast.
-        Some(elt) => {
-            list![
-                MalType::Symbol("cons".to_string()),
-                quasiquote(elt),
-                qq_inner(&l[1..].to_vec())
-            ]
-        }
-    }
-}
+const DEFAULT_PROMPT: &str = "user> ";
 
-fn quasiquote(ast: &MalType) -> MalType {
-    match ast {
-        MalType::List(l, _) => match l.first() {
-            Some(MalType::Symbol(s)) if s == "unquote" => return l[1].clone(),
-            _ => qq_inner(l),
-        },
-        MalType::Vector(l, _) => list![MalType::Symbol("vec".to_string()), qq_inner(l)],
-        MalType::HashMap(..) | MalType::Symbol(_) => {
-            list![MalType::Symbol("quote".to_string()), ast.clone()]
-        }
-        _ => ast.clone(),
+const FLAGS: cli::Flags = &[
+    (
+        "--no-prelude",
+        "Don't define the functions and macros of the prelude",
+    ),
+    ("--vm", "Run code on the bytecode VM"),
+    ("--watch", "Run file again, afresh, each time it changes"),
+    (
+        "--record <file>",
+        "Append what is typed at the REPL and its results to file",
+    ),
+    (
+        "--json-errors",
+        "Report errors as JSON diagnostics on stderr, one per line",
+    ),
+];
+
+/// The exit status when running a file, -e expressions or piped stdin fails with an error from
+/// mal itself, such as a type or arity error. A command line that doesn't make sense exits with
+/// 2, as cli does.
+const EXIT_ERROR: i32 = 1;
+/// ... when source can't be read
+const EXIT_READ_ERROR: i32 = 3;
+/// ... when a value thrown with throw isn't caught
+const EXIT_THROWN: i32 = 4;
+
+/// The exit status for a run that ended with e
+fn exit_status(e: &MalErr) -> i32 {
+    match e.code() {
+        "read-error" => EXIT_READ_ERROR,
+        "thrown" => EXIT_THROWN,
+        _ => EXIT_ERROR,
     }
 }
 
-fn is_macro_call(ast: &MalType, env: Rc<Env>) -> bool {
-    match ast {
-        MalType::List(l, _) => match l.first() {
-            Some(MalType::Symbol(s)) => match env.get(s) {
-                Ok(MalType::MalFunction { is_macro, .. }) => is_macro,
-                _ => false,
-            },
-            _ => false,
-        },
-        _ => false,
+/// Whether --json-errors was given, for tools reading the errors rather than people
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Bind *1 to the value just printed at the REPL, moving the ones before it to *2 and *3
+fn remember(env: &Env, val: MalType) {
+    for (to, from) in [("*3", "*2"), ("*2", "*1")] {
+        env.set(
+            to.to_string(),
+            env.lookup(&Sym::new(from)).unwrap_or(MalType::Nil),
+        );
     }
+    env.set("*1".to_string(), val);
 }
 
-fn macroexpand(mut ast: MalType, env: Rc<Env>) -> Result<MalType, MalErr> {
-    while is_macro_call(&ast, Rc::clone(&env)) {
-        match ast {
-            MalType::List(l, _) => {
-                let mal_func = env.get(&l[0].to_string()).unwrap();
-                ast = mal_func.apply(l[1..].to_vec())?
-            }
-            _ => panic!("Expected a macro call!"),
+/// The prompt: *prompt* if it is a string, or what it returns if it is a function
+fn prompt(env: &Env) -> String {
+    let prompt = match env.get(&Sym::new("*prompt*")) {
+        Ok(f @ (MalType::Function(..) | MalType::MalFunction { .. })) => f.apply(vec![]),
+        other => other,
+    };
+    match prompt {
+        Ok(MalType::Str(s)) => s,
+        Ok(other) => other.pr_str(false),
+        Err(e) => {
+            eprintln!("Error in *prompt*: {}", e);
+            DEFAULT_PROMPT.to_string()
         }
     }
-    Ok(ast)
 }
 
-fn eval(mut ast: MalType, mut env: Rc<Env>) -> Result<MalType, MalErr> {
-    let res: Result<MalType, MalErr>;
-
-    loop {
-        ast = macroexpand(ast, Rc::clone(&env))?;
-        match ast {
-            MalType::List(..) => (), // do nothing, continue with with rest of switch
-            _ => return eval_ast(&ast, &env),
-        }
-        res = match ast.clone() {
-            MalType::List(l, _) => {
-                if l.len() == 0 {
-                    return Ok(ast);
-                }
-                match l[0].to_string().as_str() {
-                    "def!" => {
-                        let result = eval(l[2].clone(), Rc::clone(&env))?;
-                        env.set(l[1].to_string(), result.clone());
-                        return Ok(result);
-                    }
-                    "defmacro!" => {
-                        let result = eval(l[2].clone(), Rc::clone(&env))?;
-                        match result {
-                            MalType::MalFunction {
-                                eval,
-                                params,
-                                ast,
-                                env,
-                                ..
-                            } => {
-                                let new_macro = MalType::MalFunction {
-                                    eval,
-                                    params,
-                                    ast,
-                                    env: Rc::clone(&env),
-                                    is_macro: true,
-                                    meta: Rc::new(MalType::Nil),
-                                };
-                                env.set(l[1].to_string(), new_macro.clone());
-                                Ok(new_macro)
-                            }
-                            _ => Err(MalErr::Generic(
-                                "cannot set non-function as a macro".to_string(),
-                            )),
-                        }
-                    }
-                    "let*" => {
-                        let let_env = Rc::new(Env::new(Some(Rc::clone(&env))));
-                        match &l[1] {
-                            MalType::List(binding_list, _) | MalType::Vector(binding_list, _) => {
-                                if binding_list.len() % 2 != 0 {
-                                    return Err(MalErr::InvalidLet(
-                                        "Odd number of parameters in the binding list".to_string(),
-                                    ));
-                                }
-                                for w in binding_list.chunks(2) {
-                                    let_env.set(
-                                        w[0].to_string(),
-                                        eval(w[1].clone(), Rc::clone(&let_env))?,
-                                    );
-                                }
-                            }
-                            _ => {
-                                return Err(MalErr::InvalidLet(
-                                    "let* expects a list or vector as the first parameter"
-                                        .to_string(),
-                                ))
-                            }
-                        };
-                        ast = l[2].clone();
-                        env = let_env;
-                        continue;
-                    }
-                    "do" => match eval_ast(&list!(l[1..l.len() - 1].to_vec()), &env)? {
-                        MalType::List(_, _) => {
-                            ast = l.last().unwrap_or(&MalType::Nil).clone();
-                            continue;
-                        }
-                        _ => Err(MalErr::InvalidDo("Invalid do construction".to_string())),
-                    },
-                    "if" => match eval(l[1].clone(), Rc::clone(&env))? {
-                        MalType::Nil | MalType::Bool(false) => {
-                            ast = l
-                                .get(3)
-                                .map_or(MalType::Nil, |else_branch| else_branch.clone());
-                            continue;
-                        }
-                        _ => {
-                            ast = l[2].clone();
-                            continue;
-                        }
-                    },
-                    "fn*" => match &l[1..] {
-                        [params @ (MalType::List(..) | MalType::Vector(..)), body] => {
-                            return Ok(MalType::MalFunction {
-                                eval: eval,
-                                params: Rc::new(params.clone()),
-                                ast: Rc::new(body.clone()),
-                                env: env,
-                                is_macro: false,
-                                meta: Rc::new(MalType::Nil),
-                            });
-                        }
-                        _ => Err(MalErr::MalFunctionErr(
-                            "fn* expects two parameters".to_string(),
-                        )),
-                    },
-                    "eval" => {
-                        ast = eval(l[1].clone(), Rc::clone(&env))?;
-                        while let Some(ref e) = Rc::clone(&env).outer {
-                            env = Rc::clone(&e);
-                        }
-                        continue;
-                    }
-                    "quote" => Ok(l[1].clone()),
-                    "quasiquote" => {
-                        ast = quasiquote(&l[1]);
-                        continue;
-                    }
-                    "quasiquoteexpand" => Ok(quasiquote(&l[1])),
-                    "macroexpand" => macroexpand(l[1].clone(), env),
-                    "try*" => match eval(l[1].clone(), Rc::clone(&env)) {
-                        Err(e) if l.len() > 2 => match &l[2] {
-                            MalType::List(c, _)
-                                if c.first() == Some(&MalType::Symbol("catch*".to_string())) =>
-                            {
-                                let err = match e {
-                                    MalErr::Throw(mt) => mt,
-                                    _ => MalType::Str(e.to_string()),
-                                };
-                                let catch_env = Rc::new(Env::new(Some(Rc::clone(&env))));
-                                catch_env.bind(list!(vec![c[1].clone()]), vec![err])?;
-                                eval(c[2].clone(), catch_env)
-                            }
-                            _ => Err(MalErr::Generic(
-                                "expected catch* branch as a list".to_string(),
-                            )),
-                        },
-                        res => res,
-                    },
-                    _ => match eval_ast(&ast, &env)? {
-                        MalType::List(ref el, _) => match el.split_first() {
-                            Some((f, args)) => match f {
-                                MalType::Function(..) => f.apply(args.to_vec()),
-                                MalType::MalFunction {
-                                    params,
-                                    ast: mfast,
-                                    env: mfenv,
-                                    ..
-                                } => {
-                                    let fn_env = Rc::new(Env::new(Some(Rc::clone(&mfenv))));
-                                    fn_env.bind((**params).clone(), args.to_vec())?;
-                                    ast = (**mfast).clone();
-                                    env = fn_env;
-                                    continue;
-                                }
-                                _ => Err(MalErr::Generic("Cannot apply non-function".to_string())),
-                            },
-                            _ => Err(MalErr::Generic("Something bad happened".to_string())),
-                        },
-                        _ => Err(MalErr::Generic("Expected a list".to_string())),
-                    },
-                }
-            }
-            _ => eval_ast(&ast, &env),
-        };
-
-        break;
+/// Print an error, and for a read error where in source, or the file it came from, it happened,
+/// or with --json-errors, its diagnostic as JSON
+fn report(e: &MalErr, source: Option<&str>) {
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        eprintln!("{}", e.to_diagnostic().to_json());
+        return;
     }
+    eprintln!("Error: {}", e.message());
+    if let Some(diagnostic) = e.diagnostic(source) {
+        eprintln!("{}", diagnostic);
+    }
+}
 
-    res
+/// Exit after printing an error from running the command line
+fn exit_on_error<T>(result: Result<T, MalErr>) -> T {
+    result.unwrap_or_else(|e| {
+        report(&e, None);
+        std::process::exit(exit_status(&e));
+    })
 }
 
-fn print(ast: MalType) -> String {
-    ast.pr_str(true)
+/// The environment the command line runs in, with *ARGV* and, unless --no-prelude was given,
+/// the prelude
+fn new_env(cli: &Cli) -> Rc<Env> {
+    let env = mal::repl_env();
+    // Add the rest of the command line arguments to your REPL environment so that
+    // programs that are run with load-file have access to their calling environment
+    env.set(
+        "*ARGV*".to_string(),
+        list!(cli.argv.iter().cloned().map(MalType::Str).collect()),
+    );
+    #[cfg(feature = "process")]
+    mal::core::set_argv(&cli.argv);
+    if !cli.flag("--no-prelude") {
+        if let Err(e) = mal::load_prelude(&env) {
+            eprintln!("Error loading prelude: {}", e);
+        }
+    }
+    env
 }
 
-fn rep(s: &str, env: &Rc<Env>) -> Result<String, MalErr> {
-    let r = read(s)?;
-    let e = eval(r, Rc::clone(&env))?;
-    let p = print(e);
-    Ok(p)
+/// Load the files given with --load into env, in order
+fn load_files(cli: &Cli, env: &Rc<Env>) -> Result<(), MalErr> {
+    for f in &cli.loads {
+        load_file(&MalType::Str(f.clone()), env)?;
+    }
+    Ok(())
 }
 
-fn eval_ast(ast: &MalType, env: &Rc<Env>) -> Result<MalType, MalErr> {
-    match ast {
-        MalType::Symbol(s) => env.get(s.as_str()),
-        MalType::List(l, _) => {
-            let mut results = Vec::new();
-            for ast in l.iter() {
-                results.push(eval(ast.clone(), Rc::clone(&env))?);
-            }
-            Ok(list!(results))
-        }
-        MalType::Vector(l, _) => {
-            let mut results = Vec::new();
-            for ast in l.iter() {
-                results.push(eval(ast.clone(), Rc::clone(&env))?);
-            }
-            Ok(vector!(results))
+/// Load the --load files, evaluate the -e expressions and run the file in a new environment, and
+/// again each time the file changes, printing what goes wrong rather than exiting
+#[cfg(feature = "watch")]
+fn watch(cli: &Cli) -> ! {
+    let file = match &cli.file {
+        Some(file) => file,
+        None => {
+            eprintln!("Error: --watch needs a file to run");
+            std::process::exit(2);
         }
-        MalType::HashMap(hm, _) => {
-            let mut results = Vec::new();
-            for (k, v) in hm.iter() {
-                results.push(k.clone());
-                results.push(eval(v.clone(), Rc::clone(&env))?);
+    };
+    let watching = watch::watch(file, || {
+        mal::forget_modules();
+        mal::cancel::reset();
+        let env = new_env(cli);
+        let result = mal::cancel::interruptible(|| {
+            load_files(cli, &env)?;
+            for expr in &cli.exprs {
+                println!("{}", rep(expr, &env)?);
             }
-            hashmap!(results)
+            load_file(&MalType::Str(file.clone()), &env)
+        });
+        if let Err(e) = result {
+            report(&e, None);
         }
-        _ => Ok(ast.clone()),
+    });
+    if let Err(e) = watching {
+        eprintln!("Error watching {}: {}", file, e);
     }
+    std::process::exit(1);
 }
 
-fn main() -> rustyline::Result<()> {
-    let mut rl = DefaultEditor::new()?;
-    if rl.load_history("history.txt").is_err() {
-        println!("No previous history.");
+fn main() {
+    let cli = Cli::parse(FLAGS);
+    JSON_ERRORS.store(cli.flag("--json-errors"), Ordering::Relaxed);
+    if cli.flag("--vm") {
+        #[cfg(feature = "vm")]
+        mal::vm::enable();
+        #[cfg(not(feature = "vm"))]
+        {
+            eprintln!("Error: --vm requires mal to be built with the \"vm\" feature");
+            std::process::exit(1);
+        }
     }
 
-    let mut args = std::env::args();
-    let arg1 = args.nth(1); // The preceding and returned elements are consumed from args
-
-    let repl_env = Rc::new(Env::default());
-    for (symbol, value) in core::ns() {
-        repl_env.set(symbol.to_string(), value);
+    if cli.flag("--watch") {
+        #[cfg(feature = "watch")]
+        watch(&cli);
+        #[cfg(not(feature = "watch"))]
+        {
+            eprintln!("Error: --watch requires mal to be built with the \"watch\" feature");
+            std::process::exit(1);
+        }
     }
-    repl_env.set(
-        "*host-language*".to_string(),
-        MalType::Str("rust".to_string()),
-    );
-    // Add the rest of the command line arguments to your REPL environment so that
-    // programs that are run with load-file have access to their calling environment
-    repl_env.set(
-        "*ARGV*".to_string(),
-        list!(args.map(MalType::Str).collect()),
-    );
 
-    let _ = rep("(def! not (fn* (a) (if a false true)))", &repl_env);
-    let _ = rep(
-        "(def! load-file (fn* (f) (eval (read-string (str \"(do \" (slurp f) \"\nnil)\")))))",
-        &repl_env,
-    );
-    let _ = rep(
-        "(defmacro! cond (fn* (& xs) (if (> (count xs) 0) (list 'if (first xs) (if (> (count xs) 1) (nth xs 1) (throw \"odd number of forms to cond\")) (cons 'cond (rest (rest xs)))))))",
-        &repl_env,
-    );
+    let repl_env = new_env(&cli);
 
-    // If called with arguments, treat the first argument as a filename and use rep to call load-file on that filename,
-    // and finally exit/terminate execution
-    if let Some(f) = arg1 {
-        match rep(&format!("(load-file \"{}\")", f), &repl_env) {
-            Ok(_) => std::process::exit(0),
+    // Load the files given with --load, evaluate and print any expressions given with -e, then
+    // run the file if there is one, and exit if there was either. Otherwise the REPL starts with
+    // what the --load files defined.
+    exit_on_error(load_files(&cli, &repl_env));
+    for expr in &cli.exprs {
+        match rep(expr, &repl_env) {
+            Ok(printed) => println!("{}", printed),
             Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+                report(&e, Some(expr));
+                std::process::exit(exit_status(&e));
             }
         }
     }
+    if let Some(f) = &cli.file {
+        exit_on_error(load_file(&MalType::Str(f.clone()), &repl_env));
+    }
+    if cli.file.is_some() || !cli.exprs.is_empty() {
+        std::process::exit(0);
+    }
 
-    let _ = rep("(println (str \"Mal [\" *host-language* \"]\"))", &repl_env);
-    loop {
-        let readline = rl.readline("user> ");
-        match readline {
-            Ok(line) => {
-                rl.add_history_entry(&line).unwrap();
-                let output = rep(&line, &repl_env);
-                match output {
-                    Ok(val) => println!("{}", val),
-                    Err(e) => eprintln!("Error: {}", e),
+    // With stdin piped in rather than typed, evaluate its forms and print just what they give
+    if !std::io::stdin().is_terminal() {
+        let mut source = String::new();
+        exit_on_error(
+            std::io::stdin()
+                .read_to_string(&mut source)
+                .map_err(|e| MalErr::IoError(Some("stdin".to_string()), e)),
+        );
+        // the status is that of the first form that failed
+        let mut status = 0;
+        for res in rep_forms(&source, &repl_env) {
+            match res {
+                Ok(val) => println!("{}", val),
+                Err(e) => {
+                    report(&e, Some(&source));
+                    if status == 0 {
+                        status = exit_status(&e);
+                    }
                 }
             }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
+        }
+        std::process::exit(status);
+    }
+
+    let mut input = match Input::open() {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    input.complete_from(&repl_env);
+    let _ = rep("(println (str \"Mal [\" *host-language* \"]\"))", &repl_env);
+    // the last three values printed and the last error
+    for var in ["*1", "*2", "*3", "*e"] {
+        repl_env.set(var.to_string(), MalType::Nil);
+    }
+    repl_env.set("*time-results*".to_string(), MalType::Bool(false));
+    repl_env.set(
+        "*prompt*".to_string(),
+        MalType::Str(DEFAULT_PROMPT.to_string()),
+    );
+    let mut commands = Commands::new(&repl_env);
+    if let Some(path) = cli.value("--record") {
+        if let Err(e) = commands.record(path) {
+            eprintln!("Error: {}", e);
+        }
+    }
+    while let Some(line) = input.line(&prompt(&repl_env)) {
+        match commands.run(&line) {
+            Some(ControlFlow::Continue(())) => continue,
+            Some(ControlFlow::Break(())) => break,
+            None => (),
+        }
+        let start = Instant::now();
+        let result = match mal::cancel::interruptible(|| read_eval(&line, &repl_env)) {
+            Ok(val) => {
+                let printed = print(val.clone());
+                println!("{}", printed);
+                remember(&repl_env, val);
+                Ok(printed)
             }
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break;
+            Err(e) => {
+                report(&e, Some(&line));
+                // a read error with a span is in the line itself, rather than from read-string,
+                // so offer the line again to fix
+                if let MalErr::ReadErr(_, Some(_)) = e {
+                    input.retry(&line);
+                }
+                repl_env.set("*e".to_string(), e.to_value());
+                Err(e)
             }
+        };
+        if !line.trim().is_empty() {
+            commands.transcribe(&line, &result);
+        }
+        if commands::timing(&repl_env) {
+            println!(
+                "Elapsed time: {:.3} msecs",
+                start.elapsed().as_secs_f64() * 1000.0
+            );
         }
     }
-
-    rl.save_history("history.txt").unwrap();
-    Ok(())
+    input.close();
 }
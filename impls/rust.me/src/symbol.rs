@@ -0,0 +1,132 @@
+//! Names of symbols and keywords.
+//!
+//! With the "intern" feature, every Sym for the same name made on a thread shares one
+//! allocation, held by that thread's table for as long as the thread lives, so cloning one never
+//! allocates, and comparing and hashing two are pointer operations that never look at the names.
+//! Without it each Sym is an allocation of its own, freed with it, which bounds memory by the
+//! names in use rather than every name ever seen, and Syms compare and hash by name. Either way
+//! they order by name, so that bindings and maps list in the same order whichever is built.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+
+#[cfg(feature = "intern")]
+use std::cell::RefCell;
+#[cfg(feature = "intern")]
+use std::collections::HashSet;
+
+#[derive(Clone)]
+pub struct Sym(Rc<str>);
+
+#[cfg(feature = "intern")]
+thread_local! {
+    static NAMES: RefCell<HashSet<Rc<str>>> = RefCell::new(HashSet::new());
+}
+
+impl Sym {
+    #[cfg(feature = "intern")]
+    pub fn new(name: &str) -> Sym {
+        NAMES.with(|names| {
+            let mut names = names.borrow_mut();
+            if let Some(interned) = names.get(name) {
+                return Sym(Rc::clone(interned));
+            }
+            let interned: Rc<str> = Rc::from(name);
+            names.insert(Rc::clone(&interned));
+            Sym(interned)
+        })
+    }
+
+    #[cfg(not(feature = "intern"))]
+    pub fn new(name: &str) -> Sym {
+        Sym(Rc::from(name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Sym {
+    fn from(name: &str) -> Self {
+        Sym::new(name)
+    }
+}
+
+impl From<String> for Sym {
+    fn from(name: String) -> Self {
+        Sym::new(&name)
+    }
+}
+
+impl Deref for Sym {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Sym {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Sym {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+// Interned, two Syms have the same name exactly when they share the allocation
+impl PartialEq for Sym {
+    #[cfg(feature = "intern")]
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    #[cfg(not(feature = "intern"))]
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Sym {}
+
+impl PartialEq<str> for Sym {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialOrd for Sym {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Sym {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if Rc::ptr_eq(&self.0, &other.0) {
+            Ordering::Equal
+        } else {
+            self.0.cmp(&other.0)
+        }
+    }
+}
+
+impl Hash for Sym {
+    #[cfg(feature = "intern")]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).cast::<u8>().hash(state)
+    }
+
+    #[cfg(not(feature = "intern"))]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
@@ -0,0 +1,141 @@
+//! Source code as it is written, for tools that work on the text of a program rather than on the
+//! values the reader makes of it, such as mal-fmt. The forms of a source are read into a tree of
+//! the reader's own tokens, comments included, each with where it is in the source, and nothing
+//! is lost or reordered the way a hash-map's keys are when it is read.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::ops::Range;
+
+use crate::reader::spans;
+use crate::types::MalType;
+
+/// A form or a comment, as written
+#[derive(Debug)]
+pub struct Node<'a> {
+    /// where it is in the source, from its first character to just past its last
+    pub span: Range<usize>,
+    pub kind: Kind<'a>,
+}
+
+#[derive(Debug)]
+pub enum Kind<'a> {
+    /// a symbol, keyword, string, number, nil, true or false
+    Atom(&'a str),
+    /// a comment, from its ; or #! to the end of the line
+    Comment(&'a str),
+    /// a reader macro such as ' or ~@ with the form it applies to, or for ^ the metadata and then
+    /// the form, along with any comments in between
+    Macro(&'a str, Vec<Node<'a>>),
+    /// a list, vector or hash-map, by its opening bracket, with its elements and comments
+    Seq(char, Vec<Node<'a>>),
+}
+
+impl Node<'_> {
+    pub fn is_comment(&self) -> bool {
+        matches!(self.kind, Kind::Comment(_))
+    }
+}
+
+/// Why a source couldn't be read, and where
+#[derive(Debug)]
+pub struct SyntaxError {
+    /// the byte offset of the token at fault
+    pub at: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The line and column, both counting from 1, of a byte offset in source
+pub fn line_col(source: &str, at: usize) -> (usize, usize) {
+    let before = &source[..at];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+fn closer(open: char) -> char {
+    match open {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+/// The forms and comments of source, in order
+pub fn parse(source: &str) -> Result<Vec<Node<'_>>, SyntaxError> {
+    let mut tokens = spans(source).peekable();
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        nodes.push(node(source, token, &mut tokens)?);
+    }
+    Ok(nodes)
+}
+
+/// The node that starts with token, reading the rest of it from tokens
+fn node<'a>(
+    source: &'a str,
+    token: Range<usize>,
+    tokens: &mut Peekable<impl Iterator<Item = Range<usize>>>,
+) -> Result<Node<'a>, SyntaxError> {
+    let text = &source[token.clone()];
+    let error = |message: String| SyntaxError {
+        at: token.start,
+        message,
+    };
+    let kind = match text {
+        "(" | "[" | "{" => {
+            let open = text.chars().next().unwrap();
+            let mut items = Vec::new();
+            loop {
+                let next = tokens
+                    .next()
+                    .ok_or_else(|| error(format!("Unexpected EOF: '{}' is never closed", open)))?;
+                match &source[next.clone()] {
+                    close @ (")" | "]" | "}") => {
+                        if !close.starts_with(closer(open)) {
+                            return Err(SyntaxError {
+                                at: next.start,
+                                message: format!("Expected '{}', got '{}'", closer(open), close),
+                            });
+                        }
+                        return Ok(Node {
+                            span: token.start..next.end,
+                            kind: Kind::Seq(open, items),
+                        });
+                    }
+                    _ => items.push(node(source, next, tokens)?),
+                }
+            }
+        }
+        ")" | "]" | "}" => return Err(error(format!("Unexpected '{}'", text))),
+        "'" | "`" | "~" | "~@" | "@" | "^" => {
+            let wanted = if text == "^" { 2 } else { 1 };
+            let mut items: Vec<Node> = Vec::new();
+            while items.iter().filter(|item| !item.is_comment()).count() < wanted {
+                let next = tokens
+                    .next()
+                    .ok_or_else(|| error(format!("Unexpected EOF after '{}'", text)))?;
+                items.push(node(source, next, tokens)?);
+            }
+            let end = items.last().unwrap().span.end;
+            return Ok(Node {
+                span: token.start..end,
+                kind: Kind::Macro(text, items),
+            });
+        }
+        _ if text.starts_with(';') || text.starts_with("#!") => Kind::Comment(text),
+        _ if text.starts_with('"') && MalType::try_from(text.to_string()).is_err() => {
+            return Err(error("Unexpected EOF: unbalanced string".to_string()));
+        }
+        _ => Kind::Atom(text),
+    };
+    Ok(Node { span: token, kind })
+}
@@ -0,0 +1,545 @@
+//! OS threads for mal functions (cargo feature `sync`).
+//!
+//! Values are reference counted with Rc and can't be shared between threads, so `(thread f
+//! & args)` deep-copies f, its arguments and every environment f closes over into a plain
+//! `Portable` tree, rebuilds them on the new thread and copies the result back on `(join t)`.
+//! The thread therefore works on a snapshot: definitions made and atoms swapped on either side
+//! after the spawn are not seen by the other.
+//!
+//! Channels are the exception: the same channel is shared by every thread that has a copy of
+//! it, and values sent on one are copied the same way to whichever thread receives them.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+use crate::env::Env;
+use crate::errors::MalErr;
+use crate::types::{keyword, symbol, MalType, Seq};
+
+type Builtin = fn(Vec<MalType>) -> Result<MalType, MalErr>;
+type Eval = fn(MalType, Rc<Env>) -> Result<MalType, MalErr>;
+
+/// A copy of a value that owns all of its data, so it can be sent to another thread
+enum Portable {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Keyword(String),
+    Symbol(String),
+    List(Vec<Portable>, Box<Portable>),
+    Vector(Vec<Portable>, Box<Portable>),
+    HashMap(Vec<(Portable, Portable)>, Box<Portable>),
+    Function(Builtin, Box<Portable>),
+    MalFunction {
+        eval: Eval,
+        params: Box<Portable>,
+        ast: Box<Portable>,
+        env: usize,
+        is_macro: bool,
+        meta: Box<Portable>,
+    },
+    Atom(Box<Portable>),
+    Reduced(Box<Portable>),
+    Bytes(Vec<u8>),
+    /// the source of a regex, compiled again on the other side
+    Pattern(String),
+    Channel(Arc<Channel>),
+}
+
+/// Environments referenced by MalFunctions, by index, each listed after its outer environment
+struct PortableEnv {
+    outer: Option<usize>,
+    bindings: Vec<(String, Portable)>,
+}
+
+/// A value together with the environments its functions close over
+struct Snapshot {
+    value: Portable,
+    envs: Vec<PortableEnv>,
+}
+
+#[derive(Default)]
+struct Exporter {
+    envs: Vec<PortableEnv>,
+    seen: HashMap<*const Env, usize>,
+}
+
+impl Exporter {
+    fn env(&mut self, env: &Rc<Env>) -> usize {
+        if let Some(i) = self.seen.get(&Rc::as_ptr(env)) {
+            return *i;
+        }
+        let outer = env.outer.as_ref().map(|outer| self.env(outer));
+        let i = self.envs.len();
+        self.envs.push(PortableEnv {
+            outer,
+            bindings: Vec::new(),
+        });
+        // registered before its bindings are copied, since they usually refer back to it
+        self.seen.insert(Rc::as_ptr(env), i);
+        // bindings that can't be copied (thread handles) are left out rather than failing the
+        // whole snapshot; the thread only sees an error if it actually uses one
+        let bindings = env
+            .bindings()
+            .into_iter()
+            .filter_map(|(k, v)| self.value(&v).ok().map(|v| (k, v)))
+            .collect();
+        self.envs[i].bindings = bindings;
+        i
+    }
+
+    fn values(&mut self, l: &[MalType]) -> Result<Vec<Portable>, MalErr> {
+        l.iter().map(|v| self.value(v)).collect()
+    }
+
+    fn boxed(&mut self, value: &MalType) -> Result<Box<Portable>, MalErr> {
+        Ok(Box::new(self.value(value)?))
+    }
+
+    fn value(&mut self, value: &MalType) -> Result<Portable, MalErr> {
+        Ok(match value {
+            MalType::Nil => Portable::Nil,
+            MalType::Bool(b) => Portable::Bool(*b),
+            MalType::Int(i) => Portable::Int(*i),
+            MalType::Str(s) => Portable::Str(s.clone()),
+            MalType::Keyword(k) => Portable::Keyword(k.to_string()),
+            MalType::Symbol(s) => Portable::Symbol(s.to_string()),
+            MalType::List(l, meta) => Portable::List(self.values(l)?, self.boxed(meta)?),
+            MalType::Vector(l, meta) => Portable::Vector(self.values(l)?, self.boxed(meta)?),
+            MalType::HashMap(hm, meta) => {
+                let mut entries = Vec::new();
+                for (k, v) in hm.iter() {
+                    entries.push((self.value(k)?, self.value(v)?));
+                }
+                Portable::HashMap(entries, self.boxed(meta)?)
+            }
+            MalType::Function(f, meta) => Portable::Function(*f, self.boxed(meta)?),
+            MalType::MalFunction {
+                eval,
+                params,
+                ast,
+                env,
+                is_macro,
+                meta,
+            } => Portable::MalFunction {
+                eval: *eval,
+                params: self.boxed(params)?,
+                ast: self.boxed(ast)?,
+                env: self.env(env),
+                is_macro: *is_macro,
+                meta: self.boxed(meta)?,
+            },
+            MalType::Atom(a) => Portable::Atom(self.boxed(&a.borrow())?),
+            MalType::Reduced(v) => Portable::Reduced(self.boxed(v)?),
+            MalType::Bytes(b) => Portable::Bytes(b.to_vec()),
+            MalType::Pattern(p) => Portable::Pattern(p.0.as_str().to_string()),
+            MalType::Channel(c) => Portable::Channel(Arc::clone(c)),
+            MalType::Thread(_) => {
+                return Err(MalErr::FunctionErr(
+                    "A thread handle can't be passed to another thread".to_string(),
+                ))
+            }
+        })
+    }
+}
+
+fn export(value: &MalType) -> Result<Snapshot, MalErr> {
+    let mut exporter = Exporter::default();
+    let value = exporter.value(value)?;
+    Ok(Snapshot {
+        value,
+        envs: exporter.envs,
+    })
+}
+
+fn import(snapshot: Snapshot) -> MalType {
+    // outer environments come first, so each one's outer already exists when it is created
+    let mut envs: Vec<Rc<Env>> = Vec::new();
+    for e in &snapshot.envs {
+        let outer = e.outer.map(|i| Rc::clone(&envs[i]));
+        envs.push(Rc::new(Env::new(outer)));
+    }
+    for (env, e) in envs.iter().zip(snapshot.envs) {
+        for (k, v) in e.bindings {
+            env.set(k, rebuild(v, &envs));
+        }
+    }
+    rebuild(snapshot.value, &envs)
+}
+
+fn rebuild(value: Portable, envs: &[Rc<Env>]) -> MalType {
+    let rc = |v: Box<Portable>| Rc::new(rebuild(*v, envs));
+    let all = |l: Vec<Portable>| l.into_iter().map(|v| rebuild(v, envs)).collect::<Seq>();
+    match value {
+        Portable::Nil => MalType::Nil,
+        Portable::Bool(b) => MalType::Bool(b),
+        Portable::Int(i) => MalType::Int(i),
+        Portable::Str(s) => MalType::Str(s),
+        Portable::Keyword(k) => keyword(&k),
+        Portable::Symbol(s) => symbol(&s),
+        Portable::List(l, meta) => MalType::List(all(l), rc(meta)),
+        Portable::Vector(l, meta) => MalType::Vector(all(l), rc(meta)),
+        Portable::HashMap(entries, meta) => MalType::HashMap(
+            Rc::new(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (rebuild(k, envs), rebuild(v, envs)))
+                    .collect::<BTreeMap<_, _>>(),
+            ),
+            rc(meta),
+        ),
+        Portable::Function(f, meta) => MalType::Function(f, rc(meta)),
+        Portable::MalFunction {
+            eval,
+            params,
+            ast,
+            env,
+            is_macro,
+            meta,
+        } => MalType::MalFunction {
+            eval,
+            params: rc(params),
+            ast: rc(ast),
+            env: Rc::clone(&envs[env]),
+            is_macro,
+            meta: rc(meta),
+        },
+        Portable::Atom(v) => MalType::Atom(Rc::new(RefCell::new(rebuild(*v, envs)))),
+        Portable::Reduced(v) => MalType::Reduced(rc(v)),
+        Portable::Bytes(b) => MalType::Bytes(Rc::new(b)),
+        Portable::Pattern(p) => MalType::Pattern(Rc::new(crate::re::Pattern(
+            regex::Regex::new(&p).expect("it compiled before"),
+        ))),
+        Portable::Channel(c) => MalType::Channel(c),
+    }
+}
+
+type Outcome = Result<Snapshot, Result<Snapshot, String>>;
+
+#[derive(Debug)]
+enum State {
+    Running(JoinHandle<Outcome>),
+    /// the result, or what the thread threw
+    Done(Result<MalType, MalType>),
+}
+
+/// A running or finished thread. Its result is kept once it has been waited for, so joining or
+/// dereferencing it again gives the same value (or throws the same error).
+#[derive(Debug)]
+pub struct Handle {
+    /// started by future rather than thread, which only changes how it prints
+    pub future: bool,
+    state: RefCell<State>,
+}
+
+// Handles are only equal to themselves, and ordered by address so they can be map keys
+impl PartialEq for Handle {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+impl Eq for Handle {}
+impl PartialOrd for Handle {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Handle {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self as *const Self).cmp(&(other as *const Self))
+    }
+}
+
+impl Handle {
+    /// Block until the thread has finished and return its result
+    pub fn wait(&self) -> Result<MalType, MalErr> {
+        let mut state = self.state.borrow_mut();
+        if let State::Running(_) = *state {
+            let State::Running(handle) =
+                std::mem::replace(&mut *state, State::Done(Ok(MalType::Nil)))
+            else {
+                unreachable!()
+            };
+            *state = State::Done(match handle.join() {
+                Ok(outcome) => received(outcome),
+                Err(_) => Err(MalType::Str("Thread panicked".to_string())),
+            });
+        }
+        match &*state {
+            State::Done(Ok(v)) => Ok(v.clone()),
+            State::Done(Err(thrown)) => Err(MalErr::Throw(thrown.clone())),
+            State::Running(_) => unreachable!(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        match &*self.state.borrow() {
+            State::Running(handle) => handle.is_finished(),
+            State::Done(_) => true,
+        }
+    }
+}
+
+// Snapshots hold nothing but owned data and fn pointers; Portable is Send by construction
+const _: fn() = || {
+    fn send<T: Send>() {}
+    send::<Snapshot>();
+};
+
+/// Apply f on this thread, ready to send the result or what it threw back. Any other error is
+/// sent as the value catch* would give for it, so an I/O error still arrives as its map.
+fn run(f: &MalType, args: Vec<MalType>) -> Outcome {
+    match f.apply(args) {
+        Ok(v) => export(&v).map_err(|e| Err(e.to_string())),
+        Err(e) => Err(export(&e.to_value()).map_err(|e| e.to_string())),
+    }
+}
+
+/// The result of a call made on another thread, or what it threw
+fn received(outcome: Outcome) -> Result<MalType, MalType> {
+    match outcome {
+        Ok(v) => Ok(import(v)),
+        Err(Ok(thrown)) => Err(import(thrown)),
+        Err(Err(message)) => Err(MalType::Str(message)),
+    }
+}
+
+fn start(args: Vec<MalType>, future: bool) -> Result<MalType, MalErr> {
+    match args.first() {
+        Some(MalType::Function(..) | MalType::MalFunction { .. }) => (),
+        _ => return Err(MalErr::expected("a function")),
+    }
+    let call = export(&crate::list!(args))?;
+    let handle = std::thread::Builder::new()
+        // the same stack the main thread gets, since eval recurses on nested forms
+        .stack_size(8 * 1024 * 1024)
+        .spawn(move || {
+            let (f, args) = match import(call) {
+                MalType::List(l, _) => (l[0].clone(), l[1..].to_vec()),
+                _ => unreachable!("a call is exported as a list"),
+            };
+            run(&f, args)
+        })?;
+    Ok(MalType::Thread(Rc::new(Handle {
+        future,
+        state: RefCell::new(State::Running(handle)),
+    })))
+}
+
+/// (thread f & args) runs (f args...) on a new thread
+pub fn spawn(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    start(args, false)
+}
+
+/// (future-call f) is what the future macro expands to; deref waits for the result
+pub fn future_call(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match args.as_slice() {
+        [_] => start(args, true),
+        _ => Err(MalErr::expected("a function of no arguments")),
+    }
+}
+
+/// (join t) waits for the thread and returns its result, rethrowing what it threw
+pub fn join(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match args.as_slice() {
+        [MalType::Thread(h)] => h.wait(),
+        _ => Err(MalErr::expected("a thread")),
+    }
+}
+
+/// (pmap f coll) is (map f coll) with the calls spread over a pool of threads, one per CPU.
+/// Each thread works on its own copy of f, as with thread. The results are in the order of
+/// coll; if any call throws, pmap throws what the first of them (in that order) threw.
+pub fn pmap(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (f, coll) = match args.as_slice() {
+        [f @ (MalType::Function(..) | MalType::MalFunction { .. }), coll] => (f, coll),
+        _ => return Err(MalErr::expected("a function and a collection")),
+    };
+    let len = match coll {
+        MalType::List(l, _) | MalType::Vector(l, _) => l.len(),
+        MalType::Nil => 0,
+        _ => return Err(MalErr::type_error("a list or vector", coll)),
+    };
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    // each worker rebuilds its own copy of the function and collection
+    let jobs = (0..workers.min(len))
+        .map(|_| export(&crate::list!(vec![f.clone(), coll.clone()])))
+        .collect::<Result<Vec<_>, _>>()?;
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results: Mutex<Vec<Option<Outcome>>> = Mutex::new((0..len).map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for job in jobs {
+            std::thread::Builder::new()
+                .stack_size(8 * 1024 * 1024)
+                .spawn_scoped(scope, || {
+                    let (f, items) = match import(job) {
+                        MalType::List(l, _) => (l[0].clone(), l[1].clone()),
+                        _ => unreachable!("a job is exported as a list"),
+                    };
+                    let items = match items {
+                        MalType::List(l, _) | MalType::Vector(l, _) => l,
+                        _ => Seq::default(),
+                    };
+                    // items are taken in order, so once one fails every earlier one has been
+                    // taken and will finish, and later ones can be skipped
+                    while !failed.load(atomic::Ordering::SeqCst) {
+                        let i = next.fetch_add(1, atomic::Ordering::SeqCst);
+                        let Some(item) = items.get(i) else { break };
+                        let outcome = run(&f, vec![item.clone()]);
+                        if outcome.is_err() {
+                            failed.store(true, atomic::Ordering::SeqCst);
+                        }
+                        results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(outcome);
+                    }
+                })?;
+        }
+        Ok::<_, MalErr>(())
+    })?;
+    let mut values = Vec::new();
+    for outcome in results.into_inner().unwrap_or_else(|e| e.into_inner()) {
+        match outcome.map(received) {
+            Some(Ok(v)) => values.push(v),
+            Some(Err(thrown)) => return Err(MalErr::Throw(thrown)),
+            None => return Err(MalErr::Throw(MalType::Str("Thread panicked".to_string()))),
+        }
+    }
+    Ok(crate::list!(values))
+}
+
+pub fn is_realized(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match args.as_slice() {
+        [MalType::Thread(h)] => Ok(MalType::Bool(h.is_finished())),
+        _ => Err(MalErr::expected("a future")),
+    }
+}
+
+struct Queue {
+    items: VecDeque<Snapshot>,
+    closed: bool,
+}
+
+/// A queue of values shared between threads, optionally bounded
+pub struct Channel {
+    capacity: Option<usize>,
+    queue: Mutex<Queue>,
+    /// signalled whenever a value is sent or received, or the channel is closed
+    changed: Condvar,
+}
+
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Channel")
+    }
+}
+
+// Like handles, channels are only equal to themselves and ordered by address
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+impl Eq for Channel {}
+impl PartialOrd for Channel {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Channel {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self as *const Self).cmp(&(other as *const Self))
+    }
+}
+
+impl Channel {
+    fn queue(&self) -> std::sync::MutexGuard<'_, Queue> {
+        // a thread can't panic while holding the lock, but don't lose the queue if one did
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait<'a>(
+        &self,
+        queue: std::sync::MutexGuard<'a, Queue>,
+    ) -> std::sync::MutexGuard<'a, Queue> {
+        self.changed.wait(queue).unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// (chan) is an unbounded channel and (chan n) one that holds at most n values, so that send!
+/// blocks while it is full
+pub fn chan(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let capacity = match args.as_slice() {
+        [] => None,
+        [MalType::Int(n)] if *n > 0 => Some(*n as usize),
+        _ => return Err(MalErr::expected("an optional positive buffer size")),
+    };
+    Ok(MalType::Channel(Arc::new(Channel {
+        capacity,
+        queue: Mutex::new(Queue {
+            items: VecDeque::new(),
+            closed: false,
+        }),
+        changed: Condvar::new(),
+    })))
+}
+
+/// (send! c v) waits for room in c and sends it a copy of v. Returns false, without sending,
+/// once c is closed. nil can't be sent, as recv! uses it to signal the end of the channel.
+pub fn send(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let (c, value) = match args.as_slice() {
+        [MalType::Channel(_), MalType::Nil] => {
+            return Err(MalErr::FunctionErr(
+                "Can't send nil on a channel".to_string(),
+            ))
+        }
+        [MalType::Channel(c), value] => (c, export(value)?),
+        _ => return Err(MalErr::expected("a channel and a value")),
+    };
+    let mut queue = c.queue();
+    while !queue.closed && c.capacity.is_some_and(|n| queue.items.len() >= n) {
+        queue = c.wait(queue);
+    }
+    if queue.closed {
+        return Ok(MalType::Bool(false));
+    }
+    queue.items.push_back(value);
+    c.changed.notify_all();
+    Ok(MalType::Bool(true))
+}
+
+/// (recv! c) waits for a value from c, or returns nil once c is closed and empty
+pub fn recv(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    let c = match args.as_slice() {
+        [MalType::Channel(c)] => c,
+        _ => return Err(MalErr::expected("a channel")),
+    };
+    let mut queue = c.queue();
+    loop {
+        if let Some(value) = queue.items.pop_front() {
+            c.changed.notify_all();
+            return Ok(import(value));
+        }
+        if queue.closed {
+            return Ok(MalType::Nil);
+        }
+        queue = c.wait(queue);
+    }
+}
+
+/// (close! c) stops further sends on c; values already sent can still be received
+pub fn close(args: Vec<MalType>) -> Result<MalType, MalErr> {
+    match args.as_slice() {
+        [MalType::Channel(c)] => {
+            c.queue().closed = true;
+            c.changed.notify_all();
+            Ok(MalType::Nil)
+        }
+        _ => Err(MalErr::expected("a channel")),
+    }
+}
@@ -0,0 +1,54 @@
+//! DEBUG-EVAL tracing: when `DEBUG-EVAL` is bound to a truthy value, eval prints every form
+//! before evaluating it, indented by the depth of nested eval calls. Binding it to a symbol
+//! (or a list/vector of symbols) only traces calls whose head is one of those symbols.
+
+use std::cell::Cell;
+
+use crate::core::err_line;
+use crate::types::MalType;
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Tracks the nesting of eval calls for as long as it is alive
+pub struct Depth;
+
+impl Depth {
+    pub fn enter() -> Self {
+        DEPTH.with(|d| d.set(d.get() + 1));
+        Depth
+    }
+}
+
+impl Drop for Depth {
+    fn drop(&mut self) {
+        DEPTH.with(|d| d.set(d.get().saturating_sub(1)));
+    }
+}
+
+fn selected(ast: &MalType, filter: &MalType) -> bool {
+    let head = match ast {
+        MalType::List(l, _) => l.first(),
+        _ => None,
+    };
+    match filter {
+        MalType::Nil | MalType::Bool(false) => false,
+        MalType::Symbol(_) => head == Some(filter),
+        MalType::List(symbols, _) | MalType::Vector(symbols, _) => {
+            head.is_some_and(|h| symbols.contains(h))
+        }
+        _ => true,
+    }
+}
+
+pub fn trace(ast: &MalType, filter: &MalType) {
+    if selected(ast, filter) {
+        let depth = DEPTH.with(|d| d.get());
+        err_line(&format!(
+            "{}EVAL: {}",
+            "  ".repeat(depth.saturating_sub(1)),
+            ast
+        ));
+    }
+}
@@ -1,21 +1,25 @@
 use std::cell::RefCell;
 use std::collections::BTreeMap;
 use std::fmt;
-use std::ops::{Add, Div, Mul, Sub};
 use std::rc::Rc;
 
 use crate::env::Env;
 use crate::errors::MalErr;
+pub use crate::seq::Seq;
+pub use crate::symbol::Sym;
 
+// Builtins are ordered by address only so that MalType can be used as a map key
+#[allow(unpredictable_function_pointer_comparisons)]
 #[derive(Clone, Debug, Ord, PartialOrd)]
 pub enum MalType {
     Nil,
     Bool(bool),
     Int(i64),
     Str(String),
-    Symbol(String),
-    List(Rc<Vec<MalType>>, Rc<MalType>),
-    Vector(Rc<Vec<MalType>>, Rc<MalType>),
+    Keyword(Sym),
+    Symbol(Sym),
+    List(Seq, Rc<MalType>),
+    Vector(Seq, Rc<MalType>),
     HashMap(Rc<BTreeMap<MalType, MalType>>, Rc<MalType>),
     Function(fn(Vec<MalType>) -> Result<MalType, MalErr>, Rc<MalType>),
     MalFunction {
@@ -27,6 +31,16 @@ pub enum MalType {
         meta: Rc<MalType>,
     },
     Atom(Rc<RefCell<MalType>>),
+    /// A value wrapped by (reduced x) to stop a reduce early
+    Reduced(Rc<MalType>),
+    /// Raw binary data, as read by slurp-bytes
+    Bytes(Rc<Vec<u8>>),
+    /// A compiled regex, made by re-pattern
+    Pattern(Rc<crate::re::Pattern>),
+    #[cfg(feature = "sync")]
+    Thread(Rc<crate::thread::Handle>),
+    #[cfg(feature = "sync")]
+    Channel(std::sync::Arc<crate::thread::Channel>),
 }
 
 impl fmt::Display for MalType {
@@ -44,66 +58,42 @@ impl PartialEq for MalType {
             (MalType::Bool(ref a), MalType::Bool(ref b)) => a == b,
             (MalType::Int(ref a), MalType::Int(ref b)) => a == b,
             (MalType::Str(ref a), MalType::Str(ref b)) => a == b,
+            (MalType::Keyword(ref a), MalType::Keyword(ref b)) => a == b,
             (MalType::Symbol(ref a), MalType::Symbol(ref b)) => a == b,
             (MalType::List(ref a, _), MalType::List(ref b, _))
             | (MalType::Vector(ref a, _), MalType::Vector(ref b, _))
             | (MalType::List(ref a, _), MalType::Vector(ref b, _))
             | (MalType::Vector(ref a, _), MalType::List(ref b, _)) => a == b,
             (MalType::HashMap(ref a, _), MalType::HashMap(ref b, _)) => a == b,
+            (MalType::Bytes(ref a), MalType::Bytes(ref b)) => a == b,
+            (MalType::Pattern(ref a), MalType::Pattern(ref b)) => a == b,
+            #[cfg(feature = "sync")]
+            (MalType::Channel(ref a), MalType::Channel(ref b)) => a == b,
             (MalType::MalFunction { .. }, MalType::MalFunction { .. }) => false,
             _ => false,
         }
     }
 }
 
-impl Add for MalType {
-    type Output = Self;
-
-    fn add(self, other: Self) -> Self {
-        match (self, other) {
-            (MalType::Int(lhs), MalType::Int(rhs)) => MalType::Int(lhs + rhs),
-            _ => todo!(),
-        }
-    }
-}
-
-impl Sub for MalType {
-    type Output = Self;
-
-    fn sub(self, other: Self) -> Self {
-        match (self, other) {
-            (MalType::Int(lhs), MalType::Int(rhs)) => MalType::Int(lhs - rhs),
-            _ => todo!(),
-        }
-    }
-}
-
-impl Mul for MalType {
-    type Output = Self;
-
-    fn mul(self, other: Self) -> Self {
-        match (self, other) {
-            (MalType::Int(lhs), MalType::Int(rhs)) => MalType::Int(lhs * rhs),
-            _ => todo!(),
-        }
-    }
-}
-
-impl Div for MalType {
-    type Output = Self;
-
-    fn div(self, other: Self) -> Self {
-        match (self, other) {
-            (MalType::Int(lhs), MalType::Int(rhs)) => MalType::Int(lhs / rhs),
-            _ => todo!(),
-        }
+impl MalType {
+    pub fn is_keyword(&self) -> bool {
+        matches!(self, MalType::Keyword(_))
     }
-}
 
-impl MalType {
     pub fn apply(&self, args: Vec<MalType>) -> Result<MalType, MalErr> {
         match self {
-            MalType::Function(f, _) => f(args),
+            // Tail calls handed back by builtins like apply are followed in a loop, as eval
+            // follows them, so a chain of them doesn't grow the stack
+            MalType::Function(f, _) => {
+                let mut res = f(args);
+                while let Err(MalErr::TailCall(g, args)) = res {
+                    res = match *g {
+                        MalType::Function(g, _) => g(args),
+                        g => return g.apply(args),
+                    };
+                }
+                res
+            }
             MalType::MalFunction {
                 eval,
                 params,
@@ -111,10 +101,46 @@ impl MalType {
                 env,
                 ..
             } => {
-                let fn_env = Rc::new(Env::new(Some(Rc::clone(&env))));
+                let fn_env = Rc::new(Env::new(Some(Rc::clone(env))));
                 fn_env.bind((**params).clone(), args)?;
                 eval((**ast).clone(), fn_env)
             }
+            // Keywords look themselves up in a map, and maps look up their argument
+            MalType::Keyword(_) => match args.as_slice() {
+                [MalType::HashMap(hm, _), rest @ ..] if rest.len() <= 1 => Ok(hm
+                    .get(self)
+                    .or(rest.first())
+                    .cloned()
+                    .unwrap_or(MalType::Nil)),
+                [_, rest @ ..] if rest.len() <= 1 => {
+                    Ok(rest.first().cloned().unwrap_or(MalType::Nil))
+                }
+                _ => Err(MalErr::expected(format!(
+                    "a map and an optional default for {}",
+                    self
+                ))),
+            },
+            MalType::HashMap(hm, _) => match args.as_slice() {
+                [k, rest @ ..] if rest.len() <= 1 => {
+                    Ok(hm.get(k).or(rest.first()).cloned().unwrap_or(MalType::Nil))
+                }
+                _ => Err(MalErr::expected(
+                    "a key and an optional default for a hash-map",
+                )),
+            },
+            // and vectors look up an index, which must be in range
+            MalType::Vector(v, _) => match args.as_slice() {
+                [MalType::Int(i)] => usize::try_from(*i)
+                    .ok()
+                    .and_then(|i| v.get(i))
+                    .cloned()
+                    .ok_or(MalErr::IndexOutOfBounds {
+                        index: *i,
+                        of: "vector",
+                        len: v.len(),
+                    }),
+                _ => Err(MalErr::expected("an index for a vector")),
+            },
             _ => Err(MalErr::Generic("Cannot apply non-function".to_string())),
         }
     }
@@ -153,25 +179,44 @@ pub fn atom(a: &MalType) -> MalType {
     MalType::Atom(Rc::new(RefCell::new(a.clone())))
 }
 
+/// A list of items, sharing them with whatever else they are part of
+pub fn list_of(items: Seq) -> MalType {
+    MalType::List(items, Rc::new(MalType::Nil))
+}
+
+pub fn vector_of(items: Seq) -> MalType {
+    MalType::Vector(items, Rc::new(MalType::Nil))
+}
+
+pub fn symbol(name: &str) -> MalType {
+    MalType::Symbol(Sym::new(name))
+}
+
+pub fn keyword(name: &str) -> MalType {
+    MalType::Keyword(Sym::new(name))
+}
+
 #[macro_export]
 macro_rules! list {
     ( $l:expr ) => {{
-        MalType::List(std::rc::Rc::new($l), std::rc::Rc::new(MalType::Nil))
+        let v: Vec<MalType> = $l;
+        MalType::List($crate::seq::Seq::from(v), std::rc::Rc::new(MalType::Nil))
     }};
     [ $($args:expr),* ] => {{
         let v: Vec<MalType> = vec![$($args),*];
-        MalType::List(std::rc::Rc::new(v), std::rc::Rc::new(MalType::Nil))
+        MalType::List($crate::seq::Seq::from(v), std::rc::Rc::new(MalType::Nil))
     }};
 }
 
 #[macro_export]
 macro_rules! vector {
     ( $l:expr ) => {{
-        MalType::Vector(std::rc::Rc::new($l), std::rc::Rc::new(MalType::Nil))
+        let v: Vec<MalType> = $l;
+        MalType::Vector($crate::seq::Seq::from(v), std::rc::Rc::new(MalType::Nil))
     }};
     [ $($args:expr),* ] => {{
         let v: Vec<MalType> = vec![$($args),*];
-        MalType::Vector(std::rc::Rc::new(v), std::rc::Rc::new(MalType::Nil))
+        MalType::Vector($crate::seq::Seq::from(v), std::rc::Rc::new(MalType::Nil))
     }};
 }
 
@@ -179,7 +224,7 @@ macro_rules! vector {
 macro_rules! hashmap {
     ( $l:expr ) => {{
         if $l.len() % 2 != 0 {
-            return Err(crate::errors::MalErr::Generic(
+            return Err($crate::errors::MalErr::Generic(
                 "Odd number of arguments".to_string(),
             ));
         }
@@ -187,7 +232,7 @@ macro_rules! hashmap {
         for w in $l.chunks(2) {
             hm.insert(w[0].clone(), w[1].clone());
         }
-        Ok(MalType::HashMap(
+        Ok::<_, $crate::errors::MalErr>(MalType::HashMap(
             std::rc::Rc::new(hm),
             std::rc::Rc::new(MalType::Nil),
         ))
@@ -0,0 +1,360 @@
+//! Optional bytecode backend (cargo feature `vm`, enabled at runtime with `--vm`).
+//!
+//! Forms are compiled into a flat list of instructions run on a small stack machine.
+//! Calls to mal functions in tail position reuse the current frame, and function bodies are
+//! compiled once and cached. Special forms the compiler doesn't lower (fn*, try*, quasiquote, ...)
+//! and macro calls are handed back to the tree-walking eval.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::cancel;
+use crate::env::Env;
+use crate::errors::MalErr;
+use crate::special_forms::SpecialForm;
+use crate::symbol::Sym;
+use crate::types::MalType;
+
+type Eval = fn(MalType, Rc<Env>) -> Result<MalType, MalErr>;
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    /// push a constant
+    Const(usize),
+    /// push the value bound to the symbol constant
+    Load(usize),
+    /// bind the top of the stack to the symbol constant, leaving the value on the stack
+    Def(usize),
+    /// pop the condition and jump when it is falsy
+    JumpIfFalse(usize),
+    Jump(usize),
+    Pop,
+    /// open a new child environment
+    PushEnv,
+    /// pop a value and bind it to the symbol constant in the current environment
+    Bind(usize),
+    PopEnv,
+    /// pop the arguments and the function, and push the result of the call
+    Call(usize),
+    /// like Call, but a mal function replaces the current frame
+    TailCall(usize),
+    /// pop a form and evaluate it in the outermost environment
+    EvalTop,
+    /// evaluate the constant form with the tree-walking eval
+    Interpret(usize),
+    Return,
+}
+
+#[derive(Debug, Default)]
+struct Chunk {
+    code: Vec<Op>,
+    constants: Vec<MalType>,
+}
+
+impl Chunk {
+    fn constant(&mut self, value: MalType) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.code.push(op);
+        self.code.len() - 1
+    }
+
+    fn patch(&mut self, at: usize, target: usize) {
+        self.code[at] = match self.code[at] {
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            Op::Jump(_) => Op::Jump(target),
+            op => op,
+        };
+    }
+}
+
+// Compiled function bodies, keyed by the address of the body in the MalFunction
+struct BodyCache {
+    bodies: HashMap<*const MalType, (Weak<MalType>, Rc<Chunk>)>,
+    /// how many bodies there can be before the dead ones are dropped
+    limit: usize,
+}
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static CACHE: RefCell<BodyCache> = RefCell::new(BodyCache {
+        bodies: HashMap::new(),
+        limit: 4096,
+    });
+}
+
+pub fn enable() {
+    ENABLED.with(|e| e.set(true));
+}
+
+pub fn enabled() -> bool {
+    ENABLED.with(|e| e.get())
+}
+
+/// Compile and run a form
+pub fn eval(ast: MalType, env: Rc<Env>, fallback: Eval) -> Result<MalType, MalErr> {
+    let mut chunk = Chunk::default();
+    compile(&ast, true, &mut chunk, &env);
+    chunk.emit(Op::Return);
+    run(Rc::new(chunk), env, fallback)
+}
+
+fn compile(ast: &MalType, tail: bool, chunk: &mut Chunk, env: &Rc<Env>) {
+    let l = match ast {
+        MalType::Symbol(_) => {
+            let i = chunk.constant(ast.clone());
+            chunk.emit(Op::Load(i));
+            return;
+        }
+        MalType::List(l, _) if !l.is_empty() => l,
+        MalType::Vector(..) | MalType::HashMap(..) => {
+            let i = chunk.constant(ast.clone());
+            chunk.emit(Op::Interpret(i));
+            return;
+        }
+        _ => {
+            let i = chunk.constant(ast.clone());
+            chunk.emit(Op::Const(i));
+            return;
+        }
+    };
+    let form = SpecialForm::resolve(&l[0]);
+    if form.is_some_and(|sf| sf.check(l).is_err()) || is_macro(&l[0], env) {
+        // let the tree-walker expand the macro or report the malformed form
+        let i = chunk.constant(ast.clone());
+        chunk.emit(Op::Interpret(i));
+        return;
+    }
+    match form {
+        Some(SpecialForm::Quote) => {
+            let i = chunk.constant(l[1].clone());
+            chunk.emit(Op::Const(i));
+        }
+        Some(SpecialForm::Def) => {
+            compile(&l[2], false, chunk, env);
+            let i = chunk.constant(l[1].clone());
+            chunk.emit(Op::Def(i));
+        }
+        Some(SpecialForm::Do) => match l[1..].split_last() {
+            Some((last, init)) => {
+                for form in init {
+                    compile(form, false, chunk, env);
+                    chunk.emit(Op::Pop);
+                }
+                compile(last, tail, chunk, env);
+            }
+            None => {
+                let i = chunk.constant(MalType::Nil);
+                chunk.emit(Op::Const(i));
+            }
+        },
+        Some(SpecialForm::If) => {
+            compile(&l[1], false, chunk, env);
+            let to_else = chunk.emit(Op::JumpIfFalse(0));
+            compile(&l[2], tail, chunk, env);
+            let to_end = chunk.emit(Op::Jump(0));
+            chunk.patch(to_else, chunk.code.len());
+            compile(l.get(3).unwrap_or(&MalType::Nil), tail, chunk, env);
+            chunk.patch(to_end, chunk.code.len());
+        }
+        Some(SpecialForm::Let) => match &l[1] {
+            // anything but symbols to bind is left to the interpreter to reject
+            MalType::List(bindings, _) | MalType::Vector(bindings, _)
+                if bindings.len() % 2 == 0
+                    && bindings
+                        .iter()
+                        .step_by(2)
+                        .all(|b| matches!(b, MalType::Symbol(_))) =>
+            {
+                chunk.emit(Op::PushEnv);
+                for w in bindings.chunks(2) {
+                    compile(&w[1], false, chunk, env);
+                    let i = chunk.constant(w[0].clone());
+                    chunk.emit(Op::Bind(i));
+                }
+                compile(&l[2], tail, chunk, env);
+                chunk.emit(Op::PopEnv);
+            }
+            _ => {
+                let i = chunk.constant(ast.clone());
+                chunk.emit(Op::Interpret(i));
+            }
+        },
+        Some(SpecialForm::Eval) => {
+            compile(&l[1], false, chunk, env);
+            chunk.emit(Op::EvalTop);
+        }
+        Some(_) => {
+            let i = chunk.constant(ast.clone());
+            chunk.emit(Op::Interpret(i));
+        }
+        None => {
+            for el in l.iter() {
+                compile(el, false, chunk, env);
+            }
+            let argc = l.len() - 1;
+            chunk.emit(if tail {
+                Op::TailCall(argc)
+            } else {
+                Op::Call(argc)
+            });
+        }
+    }
+}
+
+fn is_macro(head: &MalType, env: &Rc<Env>) -> bool {
+    match head {
+        MalType::Symbol(s) => matches!(
+            env.lookup(s),
+            Some(MalType::MalFunction { is_macro: true, .. })
+        ),
+        _ => false,
+    }
+}
+
+/// Fetch (or compile and cache) the body of a mal function
+fn compiled_body(ast: &Rc<MalType>, env: &Rc<Env>) -> Rc<Chunk> {
+    let key = Rc::as_ptr(ast);
+    let cached = CACHE.with(|c| {
+        c.borrow()
+            .bodies
+            .get(&key)
+            .filter(|(weak, _)| weak.upgrade().is_some_and(|live| Rc::ptr_eq(&live, ast)))
+            .map(|(_, chunk)| Rc::clone(chunk))
+    });
+    if let Some(chunk) = cached {
+        return chunk;
+    }
+    let mut chunk = Chunk::default();
+    compile(ast, true, &mut chunk, env);
+    chunk.emit(Op::Return);
+    let chunk = Rc::new(chunk);
+    CACHE.with(|c| {
+        let mut cache = c.borrow_mut();
+        if cache.bodies.len() >= cache.limit {
+            cache.bodies.retain(|_, (weak, _)| weak.strong_count() > 0);
+            cache.limit = (cache.bodies.len() * 2).max(4096);
+        }
+        cache
+            .bodies
+            .insert(key, (Rc::downgrade(ast), Rc::clone(&chunk)));
+    });
+    chunk
+}
+
+fn call(f: MalType, args: Vec<MalType>, fallback: Eval) -> Result<MalType, MalErr> {
+    match f {
+        MalType::MalFunction {
+            params, ast, env, ..
+        } => {
+            let fn_env = Rc::new(Env::new(Some(env)));
+            fn_env.bind((*params).clone(), args)?;
+            let chunk = compiled_body(&ast, &fn_env);
+            run(chunk, fn_env, fallback)
+        }
+        f => f.apply(args),
+    }
+}
+
+fn pop(stack: &mut Vec<MalType>) -> Result<MalType, MalErr> {
+    stack
+        .pop()
+        .ok_or_else(|| MalErr::Generic("VM stack underflow".to_string()))
+}
+
+fn pop_call(stack: &mut Vec<MalType>, argc: usize) -> Result<(MalType, Vec<MalType>), MalErr> {
+    let args = stack.split_off(stack.len().saturating_sub(argc));
+    Ok((pop(stack)?, args))
+}
+
+fn symbol(chunk: &Chunk, i: usize) -> Sym {
+    match &chunk.constants[i] {
+        MalType::Symbol(s) => s.clone(),
+        other => Sym::from(other.to_string()),
+    }
+}
+
+fn run(mut chunk: Rc<Chunk>, mut env: Rc<Env>, fallback: Eval) -> Result<MalType, MalErr> {
+    let mut stack: Vec<MalType> = Vec::new();
+    let mut envs: Vec<Rc<Env>> = Vec::new();
+    let mut ip = 0;
+    loop {
+        let op = chunk.code[ip];
+        ip += 1;
+        match op {
+            Op::Const(i) => stack.push(chunk.constants[i].clone()),
+            Op::Load(i) => stack.push(env.get(&symbol(&chunk, i))?),
+            Op::Def(i) => {
+                let value = pop(&mut stack)?;
+                env.set(symbol(&chunk, i), value.clone());
+                stack.push(value);
+            }
+            Op::JumpIfFalse(target) => {
+                if let MalType::Nil | MalType::Bool(false) = pop(&mut stack)? {
+                    ip = target;
+                }
+            }
+            Op::Jump(target) => ip = target,
+            Op::Pop => {
+                pop(&mut stack)?;
+            }
+            Op::PushEnv => {
+                let child = Rc::new(Env::new(Some(Rc::clone(&env))));
+                envs.push(std::mem::replace(&mut env, child));
+            }
+            Op::Bind(i) => {
+                let value = pop(&mut stack)?;
+                env.set(symbol(&chunk, i), value);
+            }
+            Op::PopEnv => {
+                if let Some(outer) = envs.pop() {
+                    env = outer;
+                }
+            }
+            Op::Call(argc) => {
+                cancel::check()?;
+                let (f, args) = pop_call(&mut stack, argc)?;
+                stack.push(call(f, args, fallback)?);
+            }
+            Op::TailCall(argc) => {
+                cancel::check()?;
+                match pop_call(&mut stack, argc)? {
+                    (
+                        MalType::MalFunction {
+                            params,
+                            ast,
+                            env: fenv,
+                            ..
+                        },
+                        args,
+                    ) => {
+                        let fn_env = Rc::new(Env::new(Some(fenv)));
+                        fn_env.bind((*params).clone(), args)?;
+                        chunk = compiled_body(&ast, &fn_env);
+                        env = fn_env;
+                        envs.clear();
+                        stack.clear();
+                        ip = 0;
+                    }
+                    (f, args) => stack.push(call(f, args, fallback)?),
+                }
+            }
+            Op::EvalTop => {
+                let form = pop(&mut stack)?;
+                let mut root = Rc::clone(&env);
+                while let Some(ref outer) = Rc::clone(&root).outer {
+                    root = Rc::clone(outer);
+                }
+                stack.push(eval(form, root, fallback)?);
+            }
+            Op::Interpret(i) => {
+                stack.push(fallback(chunk.constants[i].clone(), Rc::clone(&env))?);
+            }
+            Op::Return => return pop(&mut stack),
+        }
+    }
+}
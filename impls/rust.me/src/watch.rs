@@ -0,0 +1,37 @@
+//! `--watch`: running a script again each time it is saved, for a quick edit and run loop.
+
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+/// How long to wait for the rest of the events an editor makes when it saves a file
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// Whether event is the file at path being written or put in place
+fn changes(event: &Event, path: &Path) -> bool {
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+        && event.paths.iter().any(|p| p == path)
+}
+
+/// Call run now and then each time the file at path changes, until the process is ended
+pub fn watch(path: &str, mut run: impl FnMut()) -> notify::Result<()> {
+    let file = std::path::absolute(path)?;
+    // editors often save by replacing the file, which ends a watch on the file itself, so the
+    // directory it is in is watched instead
+    let dir = file.parent().unwrap_or(Path::new("/"));
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    run();
+    for event in &rx {
+        if !changes(&event?, &file) {
+            continue;
+        }
+        while rx.recv_timeout(SETTLE).is_ok() {}
+        eprintln!("\n;; {} changed, running it again", path);
+        run();
+    }
+    Ok(())
+}